@@ -0,0 +1,296 @@
+use crate::utils::files::{delete_existing, ensure_is_file};
+use crate::utils::hash::HashType;
+use crate::utils::zip::{entry_path, extract_file, read_entry, ZipError};
+use log::{debug, info};
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+const VERSIONS_LIST: &str = "META-INF/versions.list";
+const LIBRARIES_LIST: &str = "META-INF/libraries.list";
+const MAIN_CLASS: &str = "META-INF/main-class";
+
+#[derive(Debug, Error)]
+pub enum BundlerError {
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    #[error(transparent)]
+    Zip(#[from] ZipError),
+    #[error("Invalid bundler list line {0}: {1:?}")]
+    InvalidLine(usize, String),
+    #[error("Missing bundled file: {0}")]
+    MissingFile(String),
+    #[error("Hash of bundled file {0} didn't match the expected hash")]
+    HashMismatch(String),
+    #[error("Bundler doesn't contain a server jar for {0}")]
+    MissingVersion(String),
+}
+
+type BundlerResult<T> = Result<T, BundlerError>;
+
+/// Kind of bundled file which determines which directory within
+/// META-INF the file is stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundlerKind {
+    Version,
+    Library,
+}
+
+impl BundlerKind {
+    /// The directory within the jar containing the files of this kind
+    fn directory(&self) -> &'static str {
+        match self {
+            Self::Version => "META-INF/versions/",
+            Self::Library => "META-INF/libraries/",
+        }
+    }
+}
+
+/// Entry from one of the bundler list files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundlerEntry {
+    /// The SHA-256 hash of the file
+    pub hash: String,
+    /// The id of the file (version or maven coordinate)
+    pub id: String,
+    /// The path of the file relative to the kind directory
+    pub path: String,
+    pub kind: BundlerKind,
+}
+
+impl BundlerEntry {
+    /// The full path of this file within the bundler jar
+    pub fn jar_path(&self) -> String {
+        format!("{}{}", self.kind.directory(), self.path)
+    }
+
+    /// Checks whether the file at the provided path exists and
+    /// matches the hash of this entry
+    pub async fn is_match(&self, path: &Path) -> BundlerResult<bool> {
+        if !ensure_is_file(path).await? {
+            return Ok(false);
+        }
+        Ok(HashType::SHA256
+            .is_file_match(&self.hash, path)
+            .await?)
+    }
+
+    /// Extracts this entry from the `jar` to the `output` path verifying
+    /// the extracted file against the entry hash. Returns false if the
+    /// file already existed with a matching hash
+    pub async fn extract(&self, jar: &Path, output: &Path) -> BundlerResult<bool> {
+        if self.is_match(output).await? {
+            return Ok(false);
+        }
+        let jar_path = self.jar_path();
+        let exists = extract_file(&jar.to_path_buf(), &output.to_path_buf(), &jar_path).await?;
+        if !exists {
+            return Err(BundlerError::MissingFile(jar_path));
+        }
+        if !self.is_match(output).await? {
+            delete_existing(output).await?;
+            return Err(BundlerError::HashMismatch(jar_path));
+        }
+        Ok(true)
+    }
+}
+
+/// Contents of the bundler metadata within a 1.18+ server jar which
+/// contains the real server jar and its libraries. The versions.list and
+/// libraries.list list the embedded files and the main-class file
+/// contains the main class of the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundlerInfo {
+    /// The main class of the bundled server
+    pub main_class: Option<String>,
+    /// The embedded server jars
+    pub versions: Vec<BundlerEntry>,
+    /// The bundled libraries
+    pub libraries: Vec<BundlerEntry>,
+}
+
+impl BundlerInfo {
+    /// Reads the bundler metadata from the jar at the provided path.
+    /// Returns None if the jar isn't a bundler jar
+    pub async fn read(jar: &Path) -> BundlerResult<Option<Self>> {
+        let Some(versions) = read_entry(jar, VERSIONS_LIST).await? else {
+            return Ok(None);
+        };
+        let versions = parse_list(&versions, BundlerKind::Version)?;
+        let libraries = match read_entry(jar, LIBRARIES_LIST).await? {
+            Some(libraries) => parse_list(&libraries, BundlerKind::Library)?,
+            None => Vec::new(),
+        };
+        let main_class = read_entry(jar, MAIN_CLASS)
+            .await?
+            .map(|value| {
+                String::from_utf8_lossy(&value)
+                    .trim()
+                    .to_string()
+            })
+            .filter(|value| !value.is_empty());
+        Ok(Some(Self {
+            main_class,
+            versions,
+            libraries,
+        }))
+    }
+
+    /// Finds the embedded server jar for the provided Minecraft version.
+    /// Falls back to the only embedded jar if there is exactly one
+    pub fn server(&self, version: &str) -> BundlerResult<&BundlerEntry> {
+        let entry = self
+            .versions
+            .iter()
+            .find(|entry| entry.id == version);
+        match (entry, self.versions.as_slice()) {
+            (Some(entry), _) | (None, [entry]) => Ok(entry),
+            _ => Err(BundlerError::MissingVersion(version.to_string())),
+        }
+    }
+
+    /// Extracts all the bundled libraries to the `output` directory
+    /// using their maven layout paths. Libraries already present with
+    /// matching hashes are skipped. Returns the paths of the libraries
+    pub async fn extract_libraries(
+        &self,
+        jar: &Path,
+        output: &Path,
+    ) -> BundlerResult<Vec<PathBuf>> {
+        let mut paths = Vec::with_capacity(self.libraries.len());
+        let mut extracted = 0usize;
+        for library in &self.libraries {
+            let path = output.join(entry_path(&library.path)?);
+            if library
+                .extract(jar, &path)
+                .await?
+            {
+                debug!("Extracted bundled library {}", library.id);
+                extracted += 1;
+            }
+            paths.push(path);
+        }
+        info!(
+            "Extracted {} bundled libraries ({} already present)",
+            extracted,
+            self.libraries.len() - extracted
+        );
+        Ok(paths)
+    }
+}
+
+/// Parses the contents of a bundler list file where each line is
+/// formatted as `<sha256>\t<id>\t<path>`
+fn parse_list(contents: &[u8], kind: BundlerKind) -> BundlerResult<Vec<BundlerEntry>> {
+    String::from_utf8_lossy(contents)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let mut parts = line.trim().split('\t');
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(hash), Some(id), Some(path), None)
+                    if hash.len() == 64 && !id.is_empty() && !path.is_empty() =>
+                {
+                    Ok(BundlerEntry {
+                        hash: hash.to_ascii_lowercase(),
+                        id: id.to_string(),
+                        path: path.to_string(),
+                        kind,
+                    })
+                }
+                _ => Err(BundlerError::InvalidLine(index + 1, line.to_string())),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::build_tools::bundler::{BundlerError, BundlerInfo, BundlerKind};
+    use crate::utils::hash::HashType;
+    use crate::utils::testing::test_dir;
+    use async_zip::{tokio::write::ZipFileWriter, Compression, ZipEntryBuilder};
+    use tokio::fs::File;
+
+    /// Tests reading the bundler metadata from a jar and extracting
+    /// the server jar and libraries with hash verification
+    #[tokio::test]
+    async fn test_bundler() {
+        let root = test_dir("bundler");
+
+        let server = b"server".as_slice();
+        let library = b"library".as_slice();
+        let versions_list = format!(
+            "{}\t1.20.1\t1.20.1/server-1.20.1.jar\n",
+            HashType::SHA256.digest(server)
+        );
+        let libraries_list = format!(
+            "{}\tcom.mojang:brigadier:1.1.8\tcom/mojang/brigadier/1.1.8/brigadier-1.1.8.jar\n\
+            {}\tcom.mojang:bad:1.0\tcom/mojang/bad/1.0/bad-1.0.jar\n",
+            HashType::SHA256.digest(library),
+            HashType::SHA256.digest(server)
+        );
+
+        let jar = root.join("server.jar");
+        let file = File::create(&jar)
+            .await
+            .unwrap();
+        let mut writer = ZipFileWriter::new(file);
+        for (name, contents) in [
+            (
+                "META-INF/main-class",
+                b"net.minecraft.server.Main\n".as_slice(),
+            ),
+            ("META-INF/versions.list", versions_list.as_bytes()),
+            ("META-INF/libraries.list", libraries_list.as_bytes()),
+            ("META-INF/versions/1.20.1/server-1.20.1.jar", server),
+            (
+                "META-INF/libraries/com/mojang/brigadier/1.1.8/brigadier-1.1.8.jar",
+                library,
+            ),
+            ("META-INF/libraries/com/mojang/bad/1.0/bad-1.0.jar", library),
+        ] {
+            let builder = ZipEntryBuilder::new(name.to_string(), Compression::Deflate);
+            writer
+                .write_entry_whole(builder.build(), contents)
+                .await
+                .unwrap();
+        }
+        writer.close().await.unwrap();
+
+        let info = BundlerInfo::read(&jar)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            info.main_class.as_deref(),
+            Some("net.minecraft.server.Main")
+        );
+        assert_eq!(info.libraries.len(), 2);
+        assert_eq!(info.libraries[0].kind, BundlerKind::Library);
+
+        let entry = info.server("1.20.1").unwrap();
+        let server_path = root.join("server-1.20.1.jar");
+        assert!(entry
+            .extract(&jar, &server_path)
+            .await
+            .unwrap());
+        assert!(!entry
+            .extract(&jar, &server_path)
+            .await
+            .unwrap());
+
+        let libraries = root.join("libraries");
+        let result = info
+            .extract_libraries(&jar, &libraries)
+            .await;
+        assert!(matches!(result, Err(BundlerError::HashMismatch(_))));
+        assert!(libraries
+            .join("com/mojang/brigadier/1.1.8/brigadier-1.1.8.jar")
+            .is_file());
+        assert!(!libraries
+            .join("com/mojang/bad/1.0/bad-1.0.jar")
+            .exists());
+    }
+}
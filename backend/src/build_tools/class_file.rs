@@ -0,0 +1,764 @@
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Magic number at the start of every class file
+const MAGIC: u32 = 0xCAFEBABE;
+
+/// Largest number of slots the constant pool can contain
+const MAX_CONSTANTS: usize = u16::MAX as usize;
+
+#[derive(Debug, Error)]
+pub enum ClassFileError {
+    #[error("Not a class file")]
+    InvalidMagic,
+    #[error("Unexpected end of class file")]
+    UnexpectedEnd,
+    #[error("Unknown constant tag {0}")]
+    UnknownTag(u8),
+    #[error("Invalid constant pool index {0}")]
+    InvalidConstant(u16),
+    #[error("Constant {0} is not valid UTF-8")]
+    InvalidUtf8(u16),
+    #[error("Constant pool is full")]
+    PoolFull,
+    #[error("Unknown opcode {0:#04x}")]
+    UnknownOpcode(u8),
+}
+
+pub type ClassFileResult<T> = Result<T, ClassFileError>;
+
+/// Entry within the constant pool. Entries reference each other
+/// by their index within the pool
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Constant {
+    /// Modified UTF-8 bytes of the value
+    Utf8(Vec<u8>),
+    Integer(u32),
+    Float(u32),
+    Long(u64),
+    Double(u64),
+    Class(u16),
+    String(u16),
+    FieldRef {
+        class: u16,
+        name_and_type: u16,
+    },
+    MethodRef {
+        class: u16,
+        name_and_type: u16,
+    },
+    InterfaceMethodRef {
+        class: u16,
+        name_and_type: u16,
+    },
+    NameAndType {
+        name: u16,
+        descriptor: u16,
+    },
+    MethodHandle {
+        kind: u8,
+        reference: u16,
+    },
+    MethodType(u16),
+    Dynamic {
+        bootstrap: u16,
+        name_and_type: u16,
+    },
+    InvokeDynamic {
+        bootstrap: u16,
+        name_and_type: u16,
+    },
+    Module(u16),
+    Package(u16),
+    /// Slot following a long or double which can't be used
+    Unusable,
+}
+
+/// The constant pool of a class file. New entries are only ever
+/// appended so existing indices (used by bytecode) stay valid
+#[derive(Debug, Clone, Default)]
+pub struct ConstantPool {
+    /// Entries of the pool. Index 0 is never valid so holds Unusable
+    entries: Vec<Constant>,
+    /// Lookup from entries to their index used to avoid adding
+    /// duplicates. Built the first time an entry is added
+    lookup: HashMap<Constant, u16>,
+}
+
+impl ConstantPool {
+    /// Gets the entry at the provided index
+    pub fn get(&self, index: u16) -> ClassFileResult<&Constant> {
+        match self
+            .entries
+            .get(index as usize)
+        {
+            Some(Constant::Unusable) | None => Err(ClassFileError::InvalidConstant(index)),
+            Some(constant) => Ok(constant),
+        }
+    }
+
+    /// Replaces the entry at the provided index
+    pub fn set(&mut self, index: u16, constant: Constant) -> ClassFileResult<()> {
+        self.get(index)?;
+        let old = std::mem::replace(&mut self.entries[index as usize], constant.clone());
+        if !self.lookup.is_empty() {
+            if self.lookup.get(&old) == Some(&index) {
+                self.lookup.remove(&old);
+            }
+            self.lookup
+                .entry(constant)
+                .or_insert(index);
+        }
+        Ok(())
+    }
+
+    /// The number of slots in the pool including the unused
+    /// slot at index 0
+    pub fn slots(&self) -> u16 {
+        self.entries.len() as u16
+    }
+
+    /// Gets the string value of the UTF-8 entry at the provided index
+    pub fn utf8(&self, index: u16) -> ClassFileResult<&str> {
+        match self.get(index)? {
+            Constant::Utf8(bytes) => {
+                std::str::from_utf8(bytes).map_err(|_| ClassFileError::InvalidUtf8(index))
+            }
+            _ => Err(ClassFileError::InvalidConstant(index)),
+        }
+    }
+
+    /// Gets the name of the class entry at the provided index
+    pub fn class_name(&self, index: u16) -> ClassFileResult<&str> {
+        match self.get(index)? {
+            Constant::Class(name) => self.utf8(*name),
+            _ => Err(ClassFileError::InvalidConstant(index)),
+        }
+    }
+
+    /// Gets the name and descriptor of the name and type
+    /// entry at the provided index
+    pub fn name_and_type(&self, index: u16) -> ClassFileResult<(&str, &str)> {
+        match self.get(index)? {
+            Constant::NameAndType { name, descriptor } => {
+                Ok((self.utf8(*name)?, self.utf8(*descriptor)?))
+            }
+            _ => Err(ClassFileError::InvalidConstant(index)),
+        }
+    }
+
+    /// Adds the provided entry to the pool returning its index. Existing
+    /// matching entries are reused instead of adding duplicates
+    pub fn add(&mut self, constant: Constant) -> ClassFileResult<u16> {
+        if self.lookup.is_empty() {
+            for (index, entry) in self
+                .entries
+                .iter()
+                .enumerate()
+                .rev()
+            {
+                if !matches!(entry, Constant::Unusable) {
+                    self.lookup
+                        .insert(entry.clone(), index as u16);
+                }
+            }
+        }
+        if let Some(index) = self.lookup.get(&constant) {
+            return Ok(*index);
+        }
+
+        let wide = matches!(constant, Constant::Long(_) | Constant::Double(_));
+        let slots = if wide { 2 } else { 1 };
+        if self.entries.len() + slots > MAX_CONSTANTS {
+            return Err(ClassFileError::PoolFull);
+        }
+        let index = self.entries.len() as u16;
+        self.lookup
+            .insert(constant.clone(), index);
+        self.entries.push(constant);
+        if wide {
+            self.entries
+                .push(Constant::Unusable);
+        }
+        Ok(index)
+    }
+
+    /// Adds a UTF-8 entry with the provided value
+    pub fn add_utf8(&mut self, value: &str) -> ClassFileResult<u16> {
+        self.add(Constant::Utf8(value.as_bytes().to_vec()))
+    }
+
+    /// Adds a class entry with the provided name
+    pub fn add_class(&mut self, name: &str) -> ClassFileResult<u16> {
+        let name = self.add_utf8(name)?;
+        self.add(Constant::Class(name))
+    }
+
+    /// Adds a name and type entry with the provided name and descriptor
+    pub fn add_name_and_type(&mut self, name: &str, descriptor: &str) -> ClassFileResult<u16> {
+        let name = self.add_utf8(name)?;
+        let descriptor = self.add_utf8(descriptor)?;
+        self.add(Constant::NameAndType { name, descriptor })
+    }
+
+    fn read(reader: &mut ClassReader) -> ClassFileResult<Self> {
+        let count = reader.u16()? as usize;
+        let mut entries = Vec::with_capacity(count);
+        entries.push(Constant::Unusable);
+        while entries.len() < count {
+            let tag = reader.u8()?;
+            let constant = match tag {
+                1 => {
+                    let length = reader.u16()? as usize;
+                    Constant::Utf8(reader.bytes(length)?.to_vec())
+                }
+                3 => Constant::Integer(reader.u32()?),
+                4 => Constant::Float(reader.u32()?),
+                5 => Constant::Long(reader.u64()?),
+                6 => Constant::Double(reader.u64()?),
+                7 => Constant::Class(reader.u16()?),
+                8 => Constant::String(reader.u16()?),
+                9 => Constant::FieldRef {
+                    class: reader.u16()?,
+                    name_and_type: reader.u16()?,
+                },
+                10 => Constant::MethodRef {
+                    class: reader.u16()?,
+                    name_and_type: reader.u16()?,
+                },
+                11 => Constant::InterfaceMethodRef {
+                    class: reader.u16()?,
+                    name_and_type: reader.u16()?,
+                },
+                12 => Constant::NameAndType {
+                    name: reader.u16()?,
+                    descriptor: reader.u16()?,
+                },
+                15 => Constant::MethodHandle {
+                    kind: reader.u8()?,
+                    reference: reader.u16()?,
+                },
+                16 => Constant::MethodType(reader.u16()?),
+                17 => Constant::Dynamic {
+                    bootstrap: reader.u16()?,
+                    name_and_type: reader.u16()?,
+                },
+                18 => Constant::InvokeDynamic {
+                    bootstrap: reader.u16()?,
+                    name_and_type: reader.u16()?,
+                },
+                19 => Constant::Module(reader.u16()?),
+                20 => Constant::Package(reader.u16()?),
+                tag => return Err(ClassFileError::UnknownTag(tag)),
+            };
+            let wide = matches!(constant, Constant::Long(_) | Constant::Double(_));
+            entries.push(constant);
+            if wide {
+                entries.push(Constant::Unusable);
+            }
+        }
+        if entries.len() > count {
+            return Err(ClassFileError::UnexpectedEnd);
+        }
+        Ok(Self {
+            entries,
+            lookup: HashMap::new(),
+        })
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_u16(out, self.slots());
+        for constant in &self.entries {
+            match constant {
+                Constant::Unusable => {}
+                Constant::Utf8(bytes) => {
+                    out.push(1);
+                    write_u16(out, bytes.len() as u16);
+                    out.extend_from_slice(bytes);
+                }
+                Constant::Integer(value) => {
+                    out.push(3);
+                    write_u32(out, *value);
+                }
+                Constant::Float(value) => {
+                    out.push(4);
+                    write_u32(out, *value);
+                }
+                Constant::Long(value) => {
+                    out.push(5);
+                    out.extend_from_slice(&value.to_be_bytes());
+                }
+                Constant::Double(value) => {
+                    out.push(6);
+                    out.extend_from_slice(&value.to_be_bytes());
+                }
+                Constant::Class(name) => {
+                    out.push(7);
+                    write_u16(out, *name);
+                }
+                Constant::String(value) => {
+                    out.push(8);
+                    write_u16(out, *value);
+                }
+                Constant::FieldRef {
+                    class,
+                    name_and_type,
+                } => write_pair(out, 9, *class, *name_and_type),
+                Constant::MethodRef {
+                    class,
+                    name_and_type,
+                } => write_pair(out, 10, *class, *name_and_type),
+                Constant::InterfaceMethodRef {
+                    class,
+                    name_and_type,
+                } => write_pair(out, 11, *class, *name_and_type),
+                Constant::NameAndType { name, descriptor } => {
+                    write_pair(out, 12, *name, *descriptor)
+                }
+                Constant::MethodHandle { kind, reference } => {
+                    out.push(15);
+                    out.push(*kind);
+                    write_u16(out, *reference);
+                }
+                Constant::MethodType(descriptor) => {
+                    out.push(16);
+                    write_u16(out, *descriptor);
+                }
+                Constant::Dynamic {
+                    bootstrap,
+                    name_and_type,
+                } => write_pair(out, 17, *bootstrap, *name_and_type),
+                Constant::InvokeDynamic {
+                    bootstrap,
+                    name_and_type,
+                } => write_pair(out, 18, *bootstrap, *name_and_type),
+                Constant::Module(name) => {
+                    out.push(19);
+                    write_u16(out, *name);
+                }
+                Constant::Package(name) => {
+                    out.push(20);
+                    write_u16(out, *name);
+                }
+            }
+        }
+    }
+}
+
+/// Attribute of a class, member or code attribute. The contents
+/// are left unparsed
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    /// Index of the UTF-8 entry containing the name
+    pub name: u16,
+    pub data: Vec<u8>,
+}
+
+impl Attribute {
+    /// Reads a list of attributes prefixed by their count
+    pub fn read_all(reader: &mut ClassReader) -> ClassFileResult<Vec<Attribute>> {
+        let count = reader.u16()?;
+        let mut attributes = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let name = reader.u16()?;
+            let length = reader.u32()? as usize;
+            let data = reader.bytes(length)?.to_vec();
+            attributes.push(Attribute { name, data });
+        }
+        Ok(attributes)
+    }
+
+    /// Writes a list of attributes prefixed by their count
+    pub fn write_all(attributes: &[Attribute], out: &mut Vec<u8>) {
+        write_u16(out, attributes.len() as u16);
+        for attribute in attributes {
+            write_u16(out, attribute.name);
+            write_u32(out, attribute.data.len() as u32);
+            out.extend_from_slice(&attribute.data);
+        }
+    }
+}
+
+/// Field or method of a class
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub access: u16,
+    /// Index of the UTF-8 entry containing the name
+    pub name: u16,
+    /// Index of the UTF-8 entry containing the descriptor
+    pub descriptor: u16,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Member {
+    fn read_all(reader: &mut ClassReader) -> ClassFileResult<Vec<Member>> {
+        let count = reader.u16()?;
+        let mut members = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            members.push(Member {
+                access: reader.u16()?,
+                name: reader.u16()?,
+                descriptor: reader.u16()?,
+                attributes: Attribute::read_all(reader)?,
+            });
+        }
+        Ok(members)
+    }
+
+    fn write_all(members: &[Member], out: &mut Vec<u8>) {
+        write_u16(out, members.len() as u16);
+        for member in members {
+            write_u16(out, member.access);
+            write_u16(out, member.name);
+            write_u16(out, member.descriptor);
+            Attribute::write_all(&member.attributes, out);
+        }
+    }
+}
+
+/// The contents of a Code attribute. The bytecode and exception
+/// table are left unparsed as they only reference constant indices
+#[derive(Debug, Clone)]
+pub struct CodeAttribute {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub code: Vec<u8>,
+    /// Raw exception table entries excluding the length
+    pub exception_table: Vec<u8>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl CodeAttribute {
+    /// Parses the data of a Code attribute
+    pub fn parse(data: &[u8]) -> ClassFileResult<Self> {
+        let mut reader = ClassReader::new(data);
+        let max_stack = reader.u16()?;
+        let max_locals = reader.u16()?;
+        let length = reader.u32()? as usize;
+        let code = reader.bytes(length)?.to_vec();
+        let exceptions = reader.u16()? as usize;
+        let exception_table = reader
+            .bytes(exceptions * 8)?
+            .to_vec();
+        let attributes = Attribute::read_all(&mut reader)?;
+        Ok(Self {
+            max_stack,
+            max_locals,
+            code,
+            exception_table,
+            attributes,
+        })
+    }
+
+    /// Writes the data of a Code attribute
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.code.len() + 32);
+        write_u16(&mut out, self.max_stack);
+        write_u16(&mut out, self.max_locals);
+        write_u32(&mut out, self.code.len() as u32);
+        out.extend_from_slice(&self.code);
+        write_u16(&mut out, (self.exception_table.len() / 8) as u16);
+        out.extend_from_slice(&self.exception_table);
+        Attribute::write_all(&self.attributes, &mut out);
+        out
+    }
+}
+
+/// A single bytecode instruction
+#[derive(Debug, Clone, Copy)]
+pub struct Instruction<'a> {
+    pub opcode: u8,
+    /// The bytes following the opcode
+    pub operands: &'a [u8],
+}
+
+impl Instruction<'_> {
+    /// The constant pool index used by the instruction if it
+    /// references the pool
+    pub fn constant(&self) -> Option<u16> {
+        match self.opcode {
+            // ldc
+            0x12 => Some(self.operands[0] as u16),
+            // ldc_w, ldc2_w, field and method instructions, new,
+            // anewarray, checkcast, instanceof and multianewarray
+            0x13 | 0x14 | 0xb2..=0xbb | 0xbd | 0xc0 | 0xc1 | 0xc5 => {
+                Some(u16::from_be_bytes([self.operands[0], self.operands[1]]))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Splits the provided bytecode into its instructions
+pub fn instructions(code: &[u8]) -> ClassFileResult<Vec<Instruction<'_>>> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let length = instruction_length(code, offset)?;
+        let operands = code
+            .get(offset + 1..offset + length)
+            .ok_or(ClassFileError::UnexpectedEnd)?;
+        instructions.push(Instruction {
+            opcode: code[offset],
+            operands,
+        });
+        offset += length;
+    }
+    Ok(instructions)
+}
+
+/// The length in bytes of the instruction at `offset` including its opcode
+fn instruction_length(code: &[u8], offset: usize) -> ClassFileResult<usize> {
+    let opcode = code[offset];
+    Ok(match opcode {
+        0x10 | 0x12 | 0x15..=0x19 | 0x36..=0x3a | 0xa9 | 0xbc => 2,
+        0x11
+        | 0x13
+        | 0x14
+        | 0x84
+        | 0x99..=0xa8
+        | 0xb2..=0xb8
+        | 0xbb
+        | 0xbd
+        | 0xc0
+        | 0xc1
+        | 0xc6
+        | 0xc7 => 3,
+        0xc5 => 4,
+        0xb9 | 0xba | 0xc8 | 0xc9 => 5,
+        // wide iinc has a two byte index and constant
+        0xc4 if code.get(offset + 1) == Some(&0x84) => 6,
+        0xc4 => 4,
+        // Switches are padded so their operands are 4 byte aligned
+        0xaa | 0xab => {
+            let padding = 3 - offset % 4;
+            let mut reader = ClassReader::new(
+                code.get(offset + 1 + padding..)
+                    .ok_or(ClassFileError::UnexpectedEnd)?,
+            );
+            let _default = reader.u32()?;
+            let entries = if opcode == 0xaa {
+                let low = reader.u32()? as i32;
+                let high = reader.u32()? as i32;
+                (high as i64 - low as i64 + 1) * 4 + 8
+            } else {
+                reader.u32()? as i64 * 8 + 4
+            };
+            let entries = usize::try_from(entries).map_err(|_| ClassFileError::UnexpectedEnd)?;
+            1 + padding + 4 + entries
+        }
+        0x00..=0xc3 => 1,
+        _ => return Err(ClassFileError::UnknownOpcode(opcode)),
+    })
+}
+
+/// Parsed class file. Only the structure of the class is parsed
+/// attributes and bytecode are kept as their raw bytes
+#[derive(Debug, Clone)]
+pub struct ClassFile {
+    pub minor_version: u16,
+    pub major_version: u16,
+    pub pool: ConstantPool,
+    pub access: u16,
+    /// Index of the class entry for this class
+    pub this_class: u16,
+    /// Index of the class entry for the super class. Zero for
+    /// java/lang/Object and module-info
+    pub super_class: u16,
+    /// Indices of the class entries for the implemented interfaces
+    pub interfaces: Vec<u16>,
+    pub fields: Vec<Member>,
+    pub methods: Vec<Member>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl ClassFile {
+    /// Parses the provided class file bytes
+    pub fn parse(data: &[u8]) -> ClassFileResult<Self> {
+        let mut reader = ClassReader::new(data);
+        if reader.u32()? != MAGIC {
+            return Err(ClassFileError::InvalidMagic);
+        }
+        let minor_version = reader.u16()?;
+        let major_version = reader.u16()?;
+        let pool = ConstantPool::read(&mut reader)?;
+        let access = reader.u16()?;
+        let this_class = reader.u16()?;
+        let super_class = reader.u16()?;
+        let interface_count = reader.u16()?;
+        let mut interfaces = Vec::with_capacity(interface_count as usize);
+        for _ in 0..interface_count {
+            interfaces.push(reader.u16()?);
+        }
+        let fields = Member::read_all(&mut reader)?;
+        let methods = Member::read_all(&mut reader)?;
+        let attributes = Attribute::read_all(&mut reader)?;
+        Ok(Self {
+            minor_version,
+            major_version,
+            pool,
+            access,
+            this_class,
+            super_class,
+            interfaces,
+            fields,
+            methods,
+            attributes,
+        })
+    }
+
+    /// The name of this class
+    pub fn name(&self) -> ClassFileResult<&str> {
+        self.pool
+            .class_name(self.this_class)
+    }
+
+    /// The name of the super class if there is one
+    pub fn super_name(&self) -> ClassFileResult<Option<&str>> {
+        if self.super_class == 0 {
+            return Ok(None);
+        }
+        self.pool
+            .class_name(self.super_class)
+            .map(Some)
+    }
+
+    /// The names of the implemented interfaces
+    pub fn interface_names(&self) -> ClassFileResult<Vec<&str>> {
+        self.interfaces
+            .iter()
+            .map(|index| self.pool.class_name(*index))
+            .collect()
+    }
+
+    /// Writes the class file to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_u32(&mut out, MAGIC);
+        write_u16(&mut out, self.minor_version);
+        write_u16(&mut out, self.major_version);
+        self.pool.write(&mut out);
+        write_u16(&mut out, self.access);
+        write_u16(&mut out, self.this_class);
+        write_u16(&mut out, self.super_class);
+        write_u16(&mut out, self.interfaces.len() as u16);
+        for interface in &self.interfaces {
+            write_u16(&mut out, *interface);
+        }
+        Member::write_all(&self.fields, &mut out);
+        Member::write_all(&self.methods, &mut out);
+        Attribute::write_all(&self.attributes, &mut out);
+        out
+    }
+}
+
+/// Reader over big-endian class file data
+pub struct ClassReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ClassReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    /// Reads the next `length` bytes
+    pub fn bytes(&mut self, length: usize) -> ClassFileResult<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(ClassFileError::UnexpectedEnd)?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> ClassFileResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> ClassFileResult<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> ClassFileResult<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn u64(&mut self) -> ClassFileResult<u64> {
+        let high = self.u32()? as u64;
+        let low = self.u32()? as u64;
+        Ok(high << 32 | low)
+    }
+}
+
+pub fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Writes a constant made of a tag and two indices
+fn write_pair(out: &mut Vec<u8>, tag: u8, first: u16, second: u16) {
+    out.push(tag);
+    write_u16(out, first);
+    write_u16(out, second);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::build_tools::class_file::{ClassFile, ClassFileError};
+    use crate::utils::zip::read_files;
+
+    /// Tests that the fixture classes are written back unchanged and
+    /// that existing constants are reused when adding entries
+    #[tokio::test]
+    async fn test_round_trip() {
+        let files = read_files("test/remapper/input.jar", |name| name.ends_with(".class"))
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 9);
+        for (name, contents) in files {
+            let mut class = ClassFile::parse(&contents).unwrap();
+            assert_eq!(class.to_bytes(), contents, "{name} should be unchanged");
+
+            let slots = class.pool.slots();
+            let class_name = class
+                .name()
+                .unwrap()
+                .to_string();
+            assert_eq!(
+                class
+                    .pool
+                    .add_class(&class_name)
+                    .unwrap(),
+                class.this_class
+            );
+            let index = class
+                .pool
+                .add_utf8("added")
+                .unwrap();
+            assert_eq!(index, slots);
+
+            let class = ClassFile::parse(&class.to_bytes()).unwrap();
+            assert_eq!(
+                class
+                    .pool
+                    .utf8(index)
+                    .unwrap(),
+                "added"
+            );
+        }
+
+        assert!(matches!(
+            ClassFile::parse(b"nope"),
+            Err(ClassFileError::InvalidMagic)
+        ));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::models::build_tools::BuildDataInfo;
+use crate::utils::cmd::{CommandError, CommandTemplate};
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+
+/// Characters which can come directly before a directory token within
+/// an argument (e.g. `-o=BuildData/out.jar` or `a.jar:Spigot/b.jar`)
+const TOKEN_PREFIXES: [char; 4] = ['=', ':', ';', ','];
+
+/// Environment variable which when set to `true` runs the SpecialSource
+/// commands for the mapping steps instead of remapping natively
+pub const USE_SPECIAL_SOURCE_KEY: &str = "USE_SPECIAL_SOURCE";
+
+/// Steps of the build which BuildData can provide custom commands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildStep {
+    Decompile,
+    ClassMap,
+    MemberMap,
+    FinalMap,
+}
+
+impl BuildStep {
+    /// All the build steps in the order they run
+    pub const ALL: [BuildStep; 4] = [
+        Self::ClassMap,
+        Self::MemberMap,
+        Self::FinalMap,
+        Self::Decompile,
+    ];
+
+    /// The custom command for this step from the BuildData info
+    pub fn custom_command<'a>(&self, info: &'a BuildDataInfo) -> Option<&'a str> {
+        match self {
+            Self::Decompile => info
+                .decompile_command
+                .as_deref(),
+            Self::ClassMap => info
+                .class_map_command
+                .as_deref(),
+            Self::MemberMap => info
+                .member_map_command
+                .as_deref(),
+            Self::FinalMap => info
+                .final_map_command
+                .as_deref(),
+        }
+    }
+
+    /// The command used when BuildData doesn't provide one. These use
+    /// the upstream directory names like the BuildData commands
+    pub fn default_command(&self) -> &'static str {
+        match self {
+            Self::Decompile => {
+                "java -jar BuildData/bin/fernflower.jar -dgs=1 -hdc=0 -rbr=0 -asc=1 -udv=0 {0} {1}"
+            }
+            Self::ClassMap | Self::MemberMap => {
+                "java -jar BuildData/bin/SpecialSource-2.jar map -i {0} -m {1} -o {2}"
+            }
+            Self::FinalMap => {
+                "java -jar BuildData/bin/SpecialSource.jar --kill-lvt -i {0} --access-transformer {1} -m {2} -o {3}"
+            }
+        }
+    }
+
+    /// Whether this step remaps the jar using SpecialSource
+    pub fn is_mapping(&self) -> bool {
+        !matches!(self, Self::Decompile)
+    }
+
+    /// The number of arguments provided to the command for this step
+    pub fn arg_count(&self) -> usize {
+        match self {
+            Self::Decompile => 2,
+            Self::ClassMap | Self::MemberMap => 3,
+            Self::FinalMap => 4,
+        }
+    }
+}
+
+impl Display for BuildStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Decompile => "decompile",
+            Self::ClassMap => "class map",
+            Self::MemberMap => "member map",
+            Self::FinalMap => "final map",
+        })
+    }
+}
+
+/// Resolves the commands for each build step rewriting the upstream
+/// directory names used by BuildData commands (BuildData, Bukkit,
+/// CraftBukkit and Spigot) to the paths of our repositories
+pub struct CommandResolver {
+    /// Upstream directory names and the paths they are replaced with
+    directories: Vec<(&'static str, String)>,
+    /// Whether the mapping steps run their SpecialSource commands
+    special_source: bool,
+}
+
+impl CommandResolver {
+    /// Creates a resolver for the repositories within the build path
+    /// using the environment variables
+    pub fn new(build_path: &Path) -> Self {
+        Self::new_with(build_path, |key| env::var(key).ok())
+    }
+
+    /// Creates a resolver for the repositories within the build path
+    /// using the provided `lookup` for the environment variables
+    pub fn new_with<F>(build_path: &Path, lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let path = |name: &str| {
+            build_path
+                .join(name)
+                .to_string_lossy()
+                .to_string()
+        };
+        Self {
+            directories: vec![
+                ("BuildData", path("build_data")),
+                ("Bukkit", path("bukkit")),
+                ("CraftBukkit", path("craftbukkit")),
+                ("Spigot", path("spigot")),
+            ],
+            special_source: lookup(USE_SPECIAL_SOURCE_KEY)
+                .is_some_and(|value| matches!(value.trim(), "1" | "true")),
+        }
+    }
+
+    /// Whether the SpecialSource commands of the mapping steps should be
+    /// run instead of remapping natively. Enabled through the
+    /// `USE_SPECIAL_SOURCE` environment variable
+    pub fn use_special_source(&self) -> bool {
+        self.special_source
+    }
+
+    /// Resolves the command template for the provided step using the
+    /// BuildData override when present. Templates referencing arguments
+    /// that the step doesn't provide are rejected
+    pub fn resolve(
+        &self,
+        step: BuildStep,
+        info: &BuildDataInfo,
+    ) -> Result<CommandTemplate, CommandError> {
+        let command = step
+            .custom_command(info)
+            .unwrap_or_else(|| step.default_command());
+        let mut template = CommandTemplate::parse(command)?;
+        template.validate(step.arg_count(), &[])?;
+        template.map_literals(|value, start| self.rewrite(value, start));
+        Ok(template)
+    }
+
+    /// Replaces the directory tokens in the provided literal text. Tokens
+    /// are only replaced when they are a whole leading path component at
+    /// the start of an argument or directly after one of `TOKEN_PREFIXES`
+    fn rewrite(&self, value: &str, start: bool) -> String {
+        let mut output = String::with_capacity(value.len());
+        let mut boundary = start;
+        let mut index = 0;
+        while index < value.len() {
+            let rest = &value[index..];
+            if boundary {
+                let matched = self
+                    .directories
+                    .iter()
+                    .find(|(token, _)| {
+                        rest.strip_prefix(token)
+                            .is_some_and(|after| {
+                                after
+                                    .chars()
+                                    .next()
+                                    .is_none_or(|c| {
+                                        c == '/' || c == '\\' || TOKEN_PREFIXES.contains(&c)
+                                    })
+                            })
+                    });
+                if let Some((token, path)) = matched {
+                    output.push_str(path);
+                    index += token.len();
+                    boundary = false;
+                    continue;
+                }
+            }
+            let c = rest
+                .chars()
+                .next()
+                .expect("Index is within the value");
+            output.push(c);
+            boundary = TOKEN_PREFIXES.contains(&c);
+            index += c.len_utf8();
+        }
+        output
+    }
+}
+
+/// Formats the command line for logging quoting arguments
+/// that contain whitespace
+pub fn format_command_line(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("\"{}\"", arg.replace('"', "\\\""))
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::build_tools::commands::{
+        format_command_line, BuildStep, CommandResolver, USE_SPECIAL_SOURCE_KEY,
+    };
+    use crate::models::build_tools::BuildDataInfo;
+    use std::path::Path;
+
+    /// Tests that each step uses its own override falling back to its
+    /// default and that only whole directory tokens are rewritten
+    #[test]
+    fn test_resolve_commands() {
+        let info = BuildDataInfo {
+            class_map_command: Some(
+                "java -jar BuildData/bin/ss.jar map -i {0} -m {1} -o {2}".to_string(),
+            ),
+            member_map_command: Some(
+                "java -cp BuildData/bin/ss.jar:Spigot/lib.jar org.Bukkit.Main -i={0} \
+                -m {1} -o {2} --name BuildDataX CraftBukkit"
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        let resolver = CommandResolver::new(Path::new("build"));
+
+        let class_map = resolver
+            .resolve(BuildStep::ClassMap, &info)
+            .unwrap()
+            .render(&["in.jar", "cl.csrg", "out.jar"], &[])
+            .unwrap();
+        assert_eq!(class_map[2], "build/build_data/bin/ss.jar");
+
+        let member_map = resolver
+            .resolve(BuildStep::MemberMap, &info)
+            .unwrap()
+            .render(&["BuildData.jar", "members.csrg", "out.jar"], &[])
+            .unwrap();
+        assert_eq!(
+            format_command_line(&member_map),
+            "java -cp build/build_data/bin/ss.jar:build/spigot/lib.jar org.Bukkit.Main \
+            -i=BuildData.jar -m members.csrg -o out.jar --name BuildDataX build/craftbukkit"
+        );
+
+        let final_map = resolver
+            .resolve(BuildStep::FinalMap, &info)
+            .unwrap()
+            .render(&["a", "b", "c", "d"], &[])
+            .unwrap();
+        assert_eq!(final_map[2], "build/build_data/bin/SpecialSource.jar");
+        let decompile = resolver
+            .resolve(BuildStep::Decompile, &info)
+            .unwrap()
+            .render(&["classes", "out"], &[])
+            .unwrap();
+        assert_eq!(decompile[2], "build/build_data/bin/fernflower.jar");
+
+        assert!(!resolver.use_special_source());
+        let resolver = CommandResolver::new_with(Path::new("build"), |key| {
+            (key == USE_SPECIAL_SOURCE_KEY).then(|| "true".to_string())
+        });
+        assert!(resolver.use_special_source());
+
+        let info = BuildDataInfo {
+            decompile_command: Some("java -jar fernflower.jar {0} {1} {2}".to_string()),
+            ..Default::default()
+        };
+        assert!(resolver
+            .resolve(BuildStep::Decompile, &info)
+            .is_err());
+    }
+}
@@ -3,7 +3,7 @@ use hashcow::CowHashMap;
 /// Cow HashMaps are used for holding mappings because the mojang mappings
 /// are modified so they become owned strings but the bukkit mappings are
 /// not owned
-type CowMapping<'a> = CowHashMap<'a, str, str>;
+pub(crate) type CowMapping<'a> = CowHashMap<'a, str, str>;
 
 /// Structure for manipulating, converting and merging
 /// mapping files.
@@ -75,7 +75,7 @@ impl<'a> Mapper<'a> {
     /// Determines the mapped value to the provided value by
     /// looking in the provided map. If the value is made of
     /// of nested values seperated by $ they are mapped too.
-    fn mapped_value(value: &str, map: &CowMapping) -> Option<String> {
+    pub(crate) fn mapped_value(value: &str, map: &CowMapping) -> Option<String> {
         // Non nested values can be retrieved immediately
         if let Some(mapped) = map.get(value) {
             return Some(mapped.to_string());
@@ -1,19 +1,47 @@
+use crate::build_tools::maven_output::{MavenEvent, MavenFailure, MavenOutputParser};
 use crate::build_tools::spigot::SpigotVersion;
 use crate::models::build_tools::BuildDataInfo;
-use crate::utils::cmd::piped_command;
-use crate::utils::constants::{MAVEN_DOWNLOAD_URL, MAVEN_VERSION};
-use crate::utils::net::create_reqwest;
+use crate::utils::cmd::piped_command_with;
+use crate::utils::constants::MAVEN_VERSION;
+use crate::utils::endpoints::endpoints;
+use crate::utils::files::{
+    copy_contents, delete_existing, ensure_dir_exists, move_directory, write_atomic,
+};
+use crate::utils::hash::HashType;
+use crate::utils::net::{create_reqwest, download, NetworkError};
 use crate::utils::zip::{unzip, ZipError};
 use log::{debug, info};
-use std::env::current_dir;
+use std::env::{self, current_dir};
+use std::fmt::Write;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
+use std::sync::Mutex;
 use thiserror::Error;
-use tokio::fs::{remove_file, File};
-use tokio::io::AsyncWriteExt;
+use tokio::fs::remove_file;
 use tokio::process::Command;
 
+/// Environment variable for the path of a system maven executable
+/// to use instead of the managed maven (e.g. /usr/bin/mvn or mvn)
+pub const MAVEN_PATH_KEY: &str = "MAVEN_PATH";
+/// Environment variable for the version of the managed maven
+pub const MAVEN_VERSION_KEY: &str = "MAVEN_VERSION";
+/// Environment variable for the expected SHA-512 of the managed maven
+/// distribution. When not set the published .sha512 file is used
+pub const MAVEN_SHA512_KEY: &str = "MAVEN_SHA512";
+/// Environment variable for the maven mirrors separated by `;` and
+/// formatted as `MIRROR_OF=URL` (e.g. `central=https://example.com/maven2`)
+pub const MAVEN_MIRRORS_KEY: &str = "MAVEN_MIRRORS";
+/// Environment variable for the path of a shared read-only maven
+/// repository that new workspace repositories are seeded from
+pub const MAVEN_SEED_REPO_KEY: &str = "MAVEN_SEED_REPO";
+
+/// Directory within the build path containing the maven workspace
+const WORKSPACE_DIR: &str = "maven";
+/// Marker written within the workspace once the local repository
+/// has been completely created or seeded
+const SEEDED_MARKER: &str = ".seeded";
+
 #[derive(Debug, Error)]
 pub enum MavenError {
     #[error(transparent)]
@@ -22,52 +50,351 @@ pub enum MavenError {
     Request(#[from] reqwest::Error),
     #[error(transparent)]
     IO(#[from] io::Error),
-    #[error("Failed to execute maven")]
-    ExecutionFailed,
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+    #[error("Maven build failed: {0}")]
+    BuildFailed(MavenFailure),
+    #[error("Invalid maven version \"{0}\"")]
+    InvalidVersion(String),
+    #[error("Invalid SHA-512 checksum for maven distribution {0}")]
+    InvalidChecksum(String),
+    #[error("Maven distribution is missing {0}")]
+    MissingExecutable(PathBuf),
+    #[error("Invalid maven mirror \"{0}\" (expected MIRROR_OF=URL)")]
+    InvalidMirror(String),
 }
 
-/// Downloads and unzips maven from the `MAVEN_DOWNLOAD_URL`
-pub async fn setup(path: &Path) -> Result<PathBuf, MavenError> {
-    let maven_path_name = format!("{}-bin.zip", MAVEN_VERSION);
-    let maven_path = path.join(&maven_path_name);
-
-    let extracted_path = path.join(MAVEN_VERSION);
-    if !extracted_path.exists() {
-        let url = format!("{}{}", MAVEN_DOWNLOAD_URL, &maven_path_name);
-
-        {
-            info!("Starting download for maven: {}", &url);
-            let client = create_reqwest()?;
-
-            let bytes = client
-                .get(url)
-                .send()
-                .await?
-                .bytes()
-                .await?;
-            let mut file = File::create(&maven_path).await?;
-            file.write_all(bytes.as_ref())
-                .await?;
-            info!("Finished downloading maven");
+/// The maven installation used to run maven commands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MavenToolchain {
+    /// Maven distribution downloaded and verified by us
+    Managed {
+        version: String,
+        /// The directory the distribution was extracted to
+        home: PathBuf,
+    },
+    /// Maven installed on the system
+    System {
+        /// Path to the mvn executable (or name for lookup on the PATH).
+        /// On windows the mvn.cmd script is used when there's no extension
+        executable: PathBuf,
+    },
+}
+
+impl MavenToolchain {
+    /// Path to the script used to run maven
+    pub fn executable(&self) -> PathBuf {
+        match self {
+            Self::Managed { home, .. } => {
+                let bin_path = home.join("bin");
+                #[cfg(target_family = "windows")]
+                let script_path = bin_path.join("mvn.cmd");
+                #[cfg(target_family = "unix")]
+                let script_path = bin_path.join("mvn");
+                script_path
+            }
+            #[cfg(target_family = "windows")]
+            Self::System { executable }
+                if executable
+                    .extension()
+                    .is_none() =>
+            {
+                executable.with_extension("cmd")
+            }
+            Self::System { executable } => executable.clone(),
         }
+    }
+
+    /// Creates the command for running maven. The managed scripts are
+    /// run through `sh` on unix because the executable permission isn't
+    /// kept when the distribution is extracted
+    pub fn command(&self) -> Command {
+        let executable = self.executable();
+        match self {
+            #[cfg(target_family = "unix")]
+            Self::Managed { home, .. } => {
+                let mut command = Command::new("sh");
+                command.arg(executable);
+                command.env("MAVEN_HOME", home);
+                command
+            }
+            #[cfg(target_family = "windows")]
+            Self::Managed { home, .. } => {
+                let mut command = Command::new(executable);
+                command.env("MAVEN_HOME", home);
+                command
+            }
+            Self::System { .. } => Command::new(executable),
+        }
+    }
+}
+
+/// Sets up the maven toolchain using the environment. See `setup_with`
+pub async fn setup(path: &Path) -> Result<MavenToolchain, MavenError> {
+    setup_with(path, |key| env::var(key).ok()).await
+}
+
+/// Sets up the maven toolchain using the provided `lookup` for environment
+/// variables. The system maven at `MAVEN_PATH` is used when it is set
+/// otherwise the maven distribution for `MAVEN_VERSION` (or the default
+/// version) is downloaded from the maven download url, verified against
+/// its SHA-512 and extracted into the provided path
+pub async fn setup_with<F: Fn(&str) -> Option<String>>(
+    path: &Path,
+    lookup: F,
+) -> Result<MavenToolchain, MavenError> {
+    if let Some(executable) = lookup(MAVEN_PATH_KEY).filter(|value| !value.trim().is_empty()) {
+        info!("Using system maven: {}", executable);
+        return Ok(MavenToolchain::System {
+            executable: PathBuf::from(executable.trim()),
+        });
+    }
+
+    let version = lookup(MAVEN_VERSION_KEY)
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| MAVEN_VERSION.to_string());
+    if !version
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+    {
+        return Err(MavenError::InvalidVersion(version));
+    }
+
+    let dist_name = format!("apache-maven-{version}");
+    let home = path.join(&dist_name);
+    let toolchain = MavenToolchain::Managed {
+        version,
+        home: home.clone(),
+    };
+    if home.exists() {
+        return Ok(toolchain);
+    }
+
+    let zip_name = format!("{dist_name}-bin.zip");
+    let zip_path = path.join(&zip_name);
+    let url = format!("{}{}", endpoints().maven_download_url, &zip_name);
+
+    let checksum = match lookup(MAVEN_SHA512_KEY).filter(|value| !value.trim().is_empty()) {
+        Some(checksum) => checksum.trim().to_string(),
+        None => get_checksum(&url).await?,
+    };
+    let checksum =
+        parse_checksum(&checksum).ok_or_else(|| MavenError::InvalidChecksum(url.clone()))?;
 
-        info!("Unzipping downloaded maven zip");
-        unzip(&maven_path, &path.to_path_buf()).await?;
+    info!("Starting download for maven: {}", &url);
+    download(&url, &zip_path, Some((HashType::SHA512, checksum))).await?;
+    info!("Finished downloading maven");
 
-        if maven_path.exists() {
-            debug!("Deleting downloaded maven install zip");
-            remove_file(&maven_path).await?;
+    // Extract to a temporary directory first so that a partially
+    // extracted distribution is never used
+    info!("Unzipping downloaded maven zip");
+    let extract_path = path.join(format!("{dist_name}.tmp"));
+    delete_existing(&extract_path).await?;
+    unzip(&zip_path, &extract_path).await?;
+    move_directory(extract_path.join(&dist_name), &home).await?;
+    delete_existing(&extract_path).await?;
+
+    if zip_path.exists() {
+        debug!("Deleting downloaded maven install zip");
+        remove_file(&zip_path).await?;
+    }
+
+    let executable = toolchain.executable();
+    if !executable.is_file() {
+        return Err(MavenError::MissingExecutable(executable));
+    }
+
+    Ok(toolchain)
+}
+
+/// Downloads the published SHA-512 checksum for the distribution url
+async fn get_checksum(url: &str) -> Result<String, MavenError> {
+    let url = format!("{url}.sha512");
+    let response = create_reqwest()?
+        .get(&url)
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(NetworkError::Status(url, status).into());
+    }
+    Ok(response.text().await?)
+}
+
+/// Parses a SHA-512 checksum file which contains the hex digest
+/// optionally followed by the file name
+fn parse_checksum(contents: &str) -> Option<&str> {
+    let checksum = contents
+        .split_whitespace()
+        .next()?;
+    if checksum.len() != 128
+        || !checksum
+            .bytes()
+            .all(|byte| byte.is_ascii_hexdigit())
+    {
+        return None;
+    }
+    Some(checksum)
+}
+
+/// Mirror of one or more maven repositories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MavenMirror {
+    pub id: String,
+    /// The repositories being mirrored (e.g. central or *)
+    pub mirror_of: String,
+    pub url: String,
+}
+
+/// Maven local repository and settings used by a single build workspace
+/// so that builds don't install into or read from the global ~/.m2
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MavenWorkspace {
+    /// The local repository artifacts are installed into
+    pub local_repo: PathBuf,
+    /// The generated settings.xml passed to maven
+    pub settings: PathBuf,
+    pub mirrors: Vec<MavenMirror>,
+}
+
+impl MavenWorkspace {
+    /// Sets up the workspace within the provided build path using
+    /// the environment. See `setup_with`
+    pub async fn setup(path: &Path) -> Result<Self, MavenError> {
+        Self::setup_with(path, |key| env::var(key).ok()).await
+    }
+
+    /// Sets up the workspace within the provided build path using the
+    /// provided `lookup` for environment variables. The mirrors are loaded
+    /// from `MAVEN_MIRRORS` and new local repositories are seeded from
+    /// the repository at `MAVEN_SEED_REPO` when it is set
+    pub async fn setup_with<F: Fn(&str) -> Option<String>>(
+        path: &Path,
+        lookup: F,
+    ) -> Result<Self, MavenError> {
+        let mirrors = match lookup(MAVEN_MIRRORS_KEY) {
+            Some(value) => parse_mirrors(&value)?,
+            None => Vec::new(),
+        };
+
+        // Maven is run from different working directories so the
+        // paths must be absolute
+        let root = current_dir()?
+            .join(path)
+            .join(WORKSPACE_DIR);
+        let workspace = Self {
+            local_repo: root.join("repository"),
+            settings: root.join("settings.xml"),
+            mirrors,
+        };
+
+        // The marker is only written once the repository is complete so
+        // an interrupted seed is discarded and copied again next time
+        let marker = root.join(SEEDED_MARKER);
+        if !marker.is_file() {
+            match lookup(MAVEN_SEED_REPO_KEY).filter(|value| !value.trim().is_empty()) {
+                Some(seed) => {
+                    info!("Seeding maven repository from {}", seed);
+                    delete_existing(&workspace.local_repo).await?;
+                    copy_contents(seed.trim(), &workspace.local_repo).await?;
+                }
+                None => ensure_dir_exists(&workspace.local_repo).await?,
+            }
+            write_atomic(&marker, b"").await?;
         }
+
+        write_atomic(&workspace.settings, workspace.settings_xml()).await?;
+        Ok(workspace)
     }
 
-    let bin_path = extracted_path.join("bin");
+    /// The arguments passed to maven to use this workspace
+    pub fn args(&self) -> [String; 3] {
+        [
+            "-s".to_string(),
+            self.settings
+                .to_string_lossy()
+                .to_string(),
+            format!(
+                "-Dmaven.repo.local={}",
+                self.local_repo
+                    .to_string_lossy()
+            ),
+        ]
+    }
 
-    #[cfg(target_family = "windows")]
-    let script_path = bin_path.join("mvn.cmd");
-    #[cfg(target_family = "unix")]
-    let script_path = bin_path.join("mvn");
+    /// Creates the contents of the settings.xml for this workspace
+    fn settings_xml(&self) -> String {
+        let mut output = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <settings xmlns=\"http://maven.apache.org/SETTINGS/1.0.0\" \
+            xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+            xsi:schemaLocation=\"http://maven.apache.org/SETTINGS/1.0.0 \
+            https://maven.apache.org/xsd/settings-1.0.0.xsd\">\n",
+        );
+        let _ = writeln!(
+            output,
+            "  <localRepository>{}</localRepository>",
+            escape_xml(
+                &self
+                    .local_repo
+                    .to_string_lossy()
+            )
+        );
+        if !self.mirrors.is_empty() {
+            output.push_str("  <mirrors>\n");
+            for mirror in &self.mirrors {
+                let _ = writeln!(
+                    output,
+                    "    <mirror>\n      <id>{}</id>\n      <mirrorOf>{}</mirrorOf>\n      <url>{}</url>\n    </mirror>",
+                    escape_xml(&mirror.id),
+                    escape_xml(&mirror.mirror_of),
+                    escape_xml(&mirror.url)
+                );
+            }
+            output.push_str("  </mirrors>\n");
+        }
+        output.push_str("</settings>\n");
+        output
+    }
+}
 
-    Ok(script_path)
+/// Parses the mirrors from the `MIRROR_OF=URL` values separated by `;`
+fn parse_mirrors(value: &str) -> Result<Vec<MavenMirror>, MavenError> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .enumerate()
+        .map(|(index, value)| {
+            let (mirror_of, url) = value
+                .split_once('=')
+                .map(|(mirror_of, url)| (mirror_of.trim(), url.trim()))
+                .filter(|(mirror_of, url)| !mirror_of.is_empty() && !url.is_empty())
+                .ok_or_else(|| MavenError::InvalidMirror(value.to_string()))?;
+            Ok(MavenMirror {
+                id: format!("mirror-{}", index + 1),
+                mirror_of: mirror_of.to_string(),
+                url: url.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Escapes the special XML characters in the provided value
+fn escape_xml(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            c => output.push(c),
+        }
+    }
+    output
 }
 
 /// Context for storing information used by maven
@@ -75,9 +402,10 @@ pub async fn setup(path: &Path) -> Result<PathBuf, MavenError> {
 pub struct MavenContext<'a> {
     pub spigot_version: &'a SpigotVersion,
     pub build_info: &'a BuildDataInfo,
-    /// The path to the maven scripts that are used to run
-    /// maven commands
-    pub script_path: PathBuf,
+    /// The maven installation used to run maven commands
+    pub toolchain: MavenToolchain,
+    /// The local repository and settings used by maven commands
+    pub workspace: MavenWorkspace,
 }
 
 impl<'a> MavenContext<'a> {
@@ -87,27 +415,11 @@ impl<'a> MavenContext<'a> {
         working_dir: impl AsRef<Path>,
         args: &[&str],
     ) -> Result<ExitStatus, MavenError> {
-        let path = self
-            .script_path
-            .to_string_lossy();
-
-        let unix = false;
-        let mut new_args = Vec::new();
-
-        if unix {
-            new_args.push(path.as_ref());
-        }
-
         let dbt = format!("-Dbt.name={}", self.spigot_version.name);
-        new_args.push(dbt.as_str());
-        new_args.extend_from_slice(args);
-
-        #[cfg(target_family = "windows")]
-        let cmd: &str = path.as_ref();
-        #[cfg(target_family = "unix")]
-        let cmd: &str = "sh";
-
-        let mut command = Command::new(cmd);
+        let mut command = self.toolchain.command();
+        command.args(self.workspace.args());
+        command.arg(dbt);
+        command.args(args);
 
         const MAVEN_KEY: &str = "MAVEN_OPTS";
 
@@ -118,13 +430,30 @@ impl<'a> MavenContext<'a> {
         );
         command.env_remove("M2_HOME");
         command.current_dir(working_dir);
-        command.args(new_args);
-        let status = piped_command(command).await?;
+
+        let parser = Mutex::new(MavenOutputParser::default());
+        let status = piped_command_with(command, |line| {
+            let mut parser = parser
+                .lock()
+                .expect("Maven output parser lock poisoned");
+            if let Some(event) = parser.parse_line(line) {
+                match event {
+                    MavenEvent::ModuleStarted { name, version } => {
+                        info!("Building maven module {name} ({version})")
+                    }
+                    event => debug!("Maven event: {:?}", event),
+                }
+            }
+        })
+        .await?;
 
         debug!("Execute status: {:?}", status);
 
         if !status.success() {
-            return Err(MavenError::ExecutionFailed);
+            let parser = parser
+                .into_inner()
+                .expect("Maven output parser lock poisoned");
+            return Err(MavenError::BuildFailed(parser.failure()));
         }
 
         Ok(status)
@@ -188,3 +517,180 @@ impl<'a> MavenContext<'a> {
             .await
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::build_tools::maven::{
+        parse_checksum, setup_with, MavenError, MavenToolchain, MavenWorkspace, MAVEN_MIRRORS_KEY,
+        MAVEN_PATH_KEY, MAVEN_SEED_REPO_KEY, MAVEN_VERSION_KEY, SEEDED_MARKER, WORKSPACE_DIR,
+    };
+    use crate::utils::testing::test_dir;
+    use std::path::{Path, PathBuf};
+
+    /// Tests parsing the published checksum files with and
+    /// without the file name
+    #[test]
+    fn test_parse_checksum() {
+        let hash = "ab".repeat(64);
+        assert_eq!(parse_checksum(&hash), Some(hash.as_str()));
+        assert_eq!(
+            parse_checksum(&format!("{hash}  apache-maven-3.9.6-bin.zip\n")),
+            Some(hash.as_str())
+        );
+        assert_eq!(parse_checksum("not a checksum"), None);
+        assert_eq!(parse_checksum(""), None);
+    }
+
+    /// Tests that a configured system maven is used without downloading
+    /// and that invalid versions are rejected
+    #[tokio::test]
+    async fn test_setup_toolchain() {
+        let path = Path::new("build");
+        let toolchain = setup_with(path, |key| {
+            (key == MAVEN_PATH_KEY).then(|| "/usr/bin/mvn".to_string())
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            toolchain,
+            MavenToolchain::System {
+                executable: PathBuf::from("/usr/bin/mvn")
+            }
+        );
+        let command = toolchain.command();
+        #[cfg(target_family = "unix")]
+        assert_eq!(command.as_std().get_program(), "/usr/bin/mvn");
+
+        let result = setup_with(path, |key| {
+            (key == MAVEN_VERSION_KEY).then(|| "../3.9.6".to_string())
+        })
+        .await;
+        assert!(result.is_err());
+    }
+
+    /// Tests that the managed maven script is run through sh on unix
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_managed_command() {
+        let toolchain = MavenToolchain::Managed {
+            version: "3.6.0".to_string(),
+            home: PathBuf::from("build/apache-maven-3.6.0"),
+        };
+        let command = toolchain.command();
+        let command = command.as_std();
+        assert_eq!(command.get_program(), "sh");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["build/apache-maven-3.6.0/bin/mvn"]);
+    }
+
+    /// Tests that the system maven resolves the mvn.cmd script on windows
+    #[cfg(target_family = "windows")]
+    #[test]
+    fn test_system_command() {
+        let toolchain = MavenToolchain::System {
+            executable: PathBuf::from("mvn"),
+        };
+        assert_eq!(toolchain.executable(), PathBuf::from("mvn.cmd"));
+        let toolchain = MavenToolchain::System {
+            executable: PathBuf::from("C:\\maven\\bin\\mvn.cmd"),
+        };
+        assert_eq!(
+            toolchain.executable(),
+            PathBuf::from("C:\\maven\\bin\\mvn.cmd")
+        );
+    }
+
+    /// Tests that workspaces are seeded from the shared repository and
+    /// that the generated settings contain the repository and mirrors
+    #[tokio::test]
+    async fn test_workspace() {
+        let root = test_dir("maven-workspace");
+        let seed = root.join("seed");
+        let artifact = "org/spigotmc/minecraft-server/1.0/minecraft-server-1.0.jar";
+        let seed_artifact = seed.join(artifact);
+        tokio::fs::create_dir_all(
+            seed_artifact
+                .parent()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        tokio::fs::write(&seed_artifact, b"jar")
+            .await
+            .unwrap();
+
+        let build = root.join("build");
+        let workspace = MavenWorkspace::setup_with(&build, |key| match key {
+            MAVEN_MIRRORS_KEY => Some("central=https://mirror.example/maven2?a=1&b=2".to_string()),
+            MAVEN_SEED_REPO_KEY => Some(
+                seed.to_string_lossy()
+                    .to_string(),
+            ),
+            _ => None,
+        })
+        .await
+        .unwrap();
+
+        assert!(workspace
+            .local_repo
+            .is_absolute());
+        assert!(workspace
+            .local_repo
+            .join(artifact)
+            .is_file());
+        let settings = tokio::fs::read_to_string(&workspace.settings)
+            .await
+            .unwrap();
+        assert!(settings.contains(&format!(
+            "<localRepository>{}</localRepository>",
+            workspace
+                .local_repo
+                .to_string_lossy()
+        )));
+        assert!(settings.contains("<mirrorOf>central</mirrorOf>"));
+        assert!(settings.contains("<url>https://mirror.example/maven2?a=1&amp;b=2</url>"));
+
+        let result = MavenWorkspace::setup_with(&build, |key| {
+            (key == MAVEN_MIRRORS_KEY).then(|| "https://mirror.example".to_string())
+        })
+        .await;
+        assert!(matches!(result, Err(MavenError::InvalidMirror(_))));
+
+        // Simulate a seed that was interrupted before completing
+        let marker = build
+            .join(WORKSPACE_DIR)
+            .join(SEEDED_MARKER);
+        assert!(marker.is_file());
+        tokio::fs::remove_file(&marker)
+            .await
+            .unwrap();
+        tokio::fs::remove_file(
+            workspace
+                .local_repo
+                .join(artifact),
+        )
+        .await
+        .unwrap();
+        let partial = workspace
+            .local_repo
+            .join("partial.jar");
+        tokio::fs::write(&partial, b"jar")
+            .await
+            .unwrap();
+
+        let workspace = MavenWorkspace::setup_with(&build, |key| {
+            (key == MAVEN_SEED_REPO_KEY).then(|| {
+                seed.to_string_lossy()
+                    .to_string()
+            })
+        })
+        .await
+        .unwrap();
+        assert!(workspace
+            .local_repo
+            .join(artifact)
+            .is_file());
+        assert!(!partial.exists());
+        assert!(marker.is_file());
+    }
+}
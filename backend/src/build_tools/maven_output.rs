@@ -0,0 +1,512 @@
+use std::fmt::{self, Display, Formatter};
+
+/// The maximum number of compilation errors included in the
+/// failure summary
+const MAX_SUMMARY_ERRORS: usize = 5;
+
+/// Result of a module from the reactor summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleStatus {
+    Success,
+    Failure,
+    Skipped,
+}
+
+/// Compilation error reported by the maven compiler plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompilationError {
+    pub file: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl Display for CompilationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Totals from a surefire test results summary
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TestSummary {
+    pub run: u32,
+    pub failures: u32,
+    pub errors: u32,
+    pub skipped: u32,
+}
+
+/// Structured event parsed from the maven output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MavenEvent {
+    /// A reactor module started building
+    ModuleStarted {
+        name: String,
+        version: String,
+    },
+    /// A module result from the reactor summary
+    ModuleFinished {
+        name: String,
+        status: ModuleStatus,
+    },
+    /// Summary of the tests run for a module
+    Tests(TestSummary),
+    CompilationError(CompilationError),
+    /// Failure to resolve or download a dependency
+    DependencyFailure(String),
+    /// A goal failed for the named project
+    GoalFailed {
+        project: Option<String>,
+        message: String,
+    },
+    /// The overall result of the build
+    BuildFinished {
+        success: bool,
+    },
+}
+
+/// Parser for the output of maven which turns the output lines into
+/// structured events and collects the details of failed builds
+#[derive(Debug, Default)]
+pub struct MavenOutputParser {
+    events: Vec<MavenEvent>,
+    /// Whether the reactor summary is currently being parsed
+    in_summary: bool,
+    /// The module that is currently building
+    current_module: Option<String>,
+}
+
+impl MavenOutputParser {
+    /// Parses the provided output line returning the event for the
+    /// line if it contained one. Every event is kept even when maven
+    /// repeats it (e.g. compilation errors in the error summary)
+    pub fn parse_line(&mut self, line: &str) -> Option<&MavenEvent> {
+        let line = strip_ansi(line);
+        let (level, text) = split_level(&line);
+        let text = text.trim_end();
+        let event = self.parse_event(level, text)?;
+        self.events.push(event);
+        self.events.last()
+    }
+
+    fn parse_event(&mut self, level: Option<&str>, text: &str) -> Option<MavenEvent> {
+        if text.starts_with("Reactor Summary") {
+            self.in_summary = true;
+            return None;
+        }
+        if let Some(result) = text.strip_prefix("BUILD ") {
+            self.in_summary = false;
+            return match result.trim() {
+                "SUCCESS" => Some(MavenEvent::BuildFinished { success: true }),
+                "FAILURE" => Some(MavenEvent::BuildFinished { success: false }),
+                _ => None,
+            };
+        }
+        if self.in_summary {
+            if let Some(event) = parse_summary_line(text) {
+                return Some(event);
+            }
+        }
+        if let Some((name, version)) = parse_module_start(text) {
+            self.current_module = Some(name.to_string());
+            return Some(MavenEvent::ModuleStarted {
+                name: name.to_string(),
+                version: version.to_string(),
+            });
+        }
+        if let Some(summary) = parse_test_summary(text) {
+            return Some(MavenEvent::Tests(summary));
+        }
+
+        let is_error = matches!(level, Some("ERROR" | "FATAL"));
+        if !is_error {
+            return None;
+        }
+        if let Some(error) = parse_compilation_error(text) {
+            return Some(MavenEvent::CompilationError(error));
+        }
+        if let Some(rest) = text.strip_prefix("Failed to execute goal") {
+            let project = rest
+                .split_once("on project ")
+                .and_then(|(_, project)| project.split(':').next())
+                .map(|project| project.trim().to_string())
+                .filter(|project| !project.is_empty());
+            return Some(MavenEvent::GoalFailed {
+                project,
+                message: text.to_string(),
+            });
+        }
+        if is_dependency_failure(text) {
+            return Some(MavenEvent::DependencyFailure(text.to_string()));
+        }
+        None
+    }
+
+    /// The events parsed so far
+    pub fn events(&self) -> &[MavenEvent] {
+        &self.events
+    }
+
+    /// Creates a summary of why the build failed from the parsed events.
+    /// Errors that maven reported more than once are only included once
+    pub fn failure(&self) -> MavenFailure {
+        let mut failure = MavenFailure::default();
+        for event in &self.events {
+            match event {
+                MavenEvent::ModuleFinished {
+                    name,
+                    status: ModuleStatus::Failure,
+                } => failure.module = Some(name.clone()),
+                MavenEvent::Tests(summary) if summary.failures > 0 || summary.errors > 0 => {
+                    failure.tests = Some(*summary)
+                }
+                MavenEvent::CompilationError(error)
+                    if !failure
+                        .compilation_errors
+                        .contains(error) =>
+                {
+                    failure
+                        .compilation_errors
+                        .push(error.clone())
+                }
+                MavenEvent::DependencyFailure(message)
+                    if !failure
+                        .dependency_failures
+                        .contains(message) =>
+                {
+                    failure
+                        .dependency_failures
+                        .push(message.clone())
+                }
+                MavenEvent::GoalFailed { project, message } => {
+                    if failure.module.is_none() {
+                        failure.module = project.clone();
+                    }
+                    // Dependency failures are reported as goal failures
+                    if is_dependency_failure(message) {
+                        if !failure
+                            .dependency_failures
+                            .contains(message)
+                        {
+                            failure
+                                .dependency_failures
+                                .push(message.clone());
+                        }
+                    } else {
+                        failure.goal = Some(message.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        if failure.module.is_none() {
+            failure.module = self.current_module.clone();
+        }
+        failure
+    }
+}
+
+/// Summary of a failed maven build
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MavenFailure {
+    /// The module that failed
+    pub module: Option<String>,
+    pub compilation_errors: Vec<CompilationError>,
+    pub dependency_failures: Vec<String>,
+    /// Test results when tests failed
+    pub tests: Option<TestSummary>,
+    /// The failed goal message when the failure isn't
+    /// one of the other kinds
+    pub goal: Option<String>,
+}
+
+impl Display for MavenFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.module {
+            Some(module) => write!(f, "module {module} failed")?,
+            None => f.write_str("build failed")?,
+        }
+        if !self
+            .compilation_errors
+            .is_empty()
+        {
+            write!(
+                f,
+                "; {} compilation error(s): ",
+                self.compilation_errors.len()
+            )?;
+            for (index, error) in self
+                .compilation_errors
+                .iter()
+                .take(MAX_SUMMARY_ERRORS)
+                .enumerate()
+            {
+                if index > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{error}")?;
+            }
+            if self.compilation_errors.len() > MAX_SUMMARY_ERRORS {
+                f.write_str(", ...")?;
+            }
+        }
+        if let Some(tests) = &self.tests {
+            write!(
+                f,
+                "; tests failed ({} run, {} failures, {} errors)",
+                tests.run, tests.failures, tests.errors
+            )?;
+        }
+        for failure in &self.dependency_failures {
+            write!(f, "; {failure}")?;
+        }
+        if let Some(goal) = &self.goal {
+            write!(f, "; {goal}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes ANSI escape sequences (e.g. colors) from the line
+fn strip_ansi(line: &str) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip until the final byte of the escape sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+/// Splits the `[LEVEL] TEXT` line into its level and text
+fn split_level(line: &str) -> (Option<&str>, &str) {
+    if let Some(rest) = line.strip_prefix('[') {
+        if let Some((level, text)) = rest.split_once(']') {
+            if !level.is_empty()
+                && level
+                    .bytes()
+                    .all(|byte| byte.is_ascii_uppercase())
+            {
+                return (
+                    Some(level),
+                    text.strip_prefix(' ')
+                        .unwrap_or(text),
+                );
+            }
+        }
+    }
+    (None, line)
+}
+
+/// Parses the module start line (e.g. `Building Spigot-API
+/// 1.19-R0.1-SNAPSHOT [1/4]`) returning the name and version
+fn parse_module_start(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix("Building ")?;
+    // Plugin output such as "Building jar: /path/to.jar"
+    if rest.contains(": ") || rest.ends_with(':') {
+        return None;
+    }
+    let rest = rest.trim_end();
+    let rest = match rest.rsplit_once(' ') {
+        Some((rest, progress)) if progress.starts_with('[') && progress.ends_with(']') => {
+            rest.trim_end()
+        }
+        _ => rest,
+    };
+    let (name, version) = rest.rsplit_once(' ')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some((name, version))
+}
+
+/// Parses a reactor summary line (e.g. `Spigot-API ..... SUCCESS [ 1.2 s]`)
+fn parse_summary_line(text: &str) -> Option<MavenEvent> {
+    [
+        (" SUCCESS", ModuleStatus::Success),
+        (" FAILURE", ModuleStatus::Failure),
+        (" SKIPPED", ModuleStatus::Skipped),
+    ]
+    .into_iter()
+    .find_map(|(marker, status)| {
+        let (name, _) = text.split_once(marker)?;
+        let name = name.trim_end_matches(['.', ' ']);
+        if name.is_empty() {
+            return None;
+        }
+        Some(MavenEvent::ModuleFinished {
+            name: name.to_string(),
+            status,
+        })
+    })
+}
+
+/// Parses the surefire results summary line (e.g. `Tests run: 5, Failures:
+/// 0, Errors: 0, Skipped: 0`). Per-class lines which include the elapsed
+/// time are ignored
+fn parse_test_summary(text: &str) -> Option<TestSummary> {
+    let text = text.strip_prefix("Tests run: ")?;
+    if text.contains("Time elapsed") {
+        return None;
+    }
+    let mut summary = TestSummary::default();
+    let run = text.split(',').next()?.trim();
+    summary.run = run.parse().ok()?;
+    for part in text.split(',').skip(1) {
+        let (name, value) = part.split_once(':')?;
+        let value = value.trim().parse().ok()?;
+        match name.trim() {
+            "Failures" => summary.failures = value,
+            "Errors" => summary.errors = value,
+            "Skipped" => summary.skipped = value,
+            _ => {}
+        }
+    }
+    Some(summary)
+}
+
+/// Parses a compiler error line (e.g. `/src/Main.java:[12,5] cannot
+/// find symbol`) into its file, line, column and message
+fn parse_compilation_error(text: &str) -> Option<CompilationError> {
+    let (file, rest) = text.split_once(":[")?;
+    let (position, message) = rest.split_once(']')?;
+    let file = file.trim();
+    if !file.ends_with(".java") {
+        return None;
+    }
+    let (line, column) = match position.split_once(',') {
+        Some((line, column)) => (line.trim().parse().ok(), column.trim().parse().ok()),
+        None => (position.trim().parse().ok(), None),
+    };
+    Some(CompilationError {
+        file: file.to_string(),
+        line,
+        column,
+        message: message.trim().to_string(),
+    })
+}
+
+/// Whether the error message is for a dependency resolution failure
+fn is_dependency_failure(text: &str) -> bool {
+    [
+        "Could not resolve dependencies",
+        "Could not find artifact",
+        "Could not transfer artifact",
+        "Failed to read artifact descriptor",
+    ]
+    .iter()
+    .any(|pattern| text.contains(pattern))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::build_tools::maven_output::{
+        CompilationError, MavenEvent, MavenOutputParser, ModuleStatus, TestSummary,
+    };
+
+    /// Tests parsing the events and failure summary from the
+    /// output of a failed multi-module build
+    #[test]
+    fn test_parse_failed_build() {
+        let output = "\
+[INFO] Reactor Build Order:
+[INFO] ------------------< org.spigotmc:spigot-api >-------------------
+[INFO] Building Spigot-API 1.19-R0.1-SNAPSHOT                       [1/2]
+[INFO] Building jar: /build/Spigot/Spigot-API/target/spigot-api.jar
+[INFO] Tests run: 3, Failures: 0, Errors: 0, Skipped: 0, Time elapsed: 0.1 s - in org.bukkit.Test
+[INFO] Tests run: 12, Failures: 0, Errors: 0, Skipped: 1
+[INFO] Building Spigot 1.19-R0.1-SNAPSHOT                           [2/2]
+[INFO] -------------------------------------------------------------
+\x1b[1;31m[ERROR] COMPILATION ERROR : \x1b[m
+[ERROR] /build/Spigot/Spigot-Server/src/main/java/Main.java:[12,5] cannot find symbol
+[INFO] Reactor Summary for Spigot-Parent dev-SNAPSHOT:
+[INFO] Spigot-API ......................................... SUCCESS [ 10.1 s]
+[INFO] Spigot ............................................. FAILURE [  2.0 s]
+[INFO] BUILD FAILURE
+[ERROR] Failed to execute goal org.apache.maven.plugins:maven-compiler-plugin:3.8.0:compile (default-compile) on project spigot: Compilation failure
+[ERROR] /build/Spigot/Spigot-Server/src/main/java/Main.java:[12,5] cannot find symbol
+";
+        let mut parser = MavenOutputParser::default();
+        for line in output.lines() {
+            parser.parse_line(line);
+        }
+
+        let events = parser.events();
+        assert_eq!(
+            events[0],
+            MavenEvent::ModuleStarted {
+                name: "Spigot-API".to_string(),
+                version: "1.19-R0.1-SNAPSHOT".to_string(),
+            }
+        );
+        assert!(events.contains(&MavenEvent::Tests(TestSummary {
+            run: 12,
+            failures: 0,
+            errors: 0,
+            skipped: 1,
+        })));
+        assert!(events.contains(&MavenEvent::ModuleFinished {
+            name: "Spigot".to_string(),
+            status: ModuleStatus::Failure,
+        }));
+        assert!(events.contains(&MavenEvent::BuildFinished { success: false }));
+        // The repeated compilation error is kept in the event stream
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| matches!(event, MavenEvent::CompilationError(_)))
+                .count(),
+            2
+        );
+
+        let failure = parser.failure();
+        assert_eq!(failure.module.as_deref(), Some("Spigot"));
+        assert_eq!(
+            failure.compilation_errors,
+            [CompilationError {
+                file: "/build/Spigot/Spigot-Server/src/main/java/Main.java".to_string(),
+                line: Some(12),
+                column: Some(5),
+                message: "cannot find symbol".to_string(),
+            }]
+        );
+        assert!(failure.goal.is_some());
+        assert!(failure
+            .to_string()
+            .starts_with("module Spigot failed; 1 compilation error(s): "));
+    }
+
+    /// Tests that dependency resolution failures are reported
+    #[test]
+    fn test_parse_dependency_failure() {
+        let mut parser = MavenOutputParser::default();
+        parser.parse_line("[INFO] Building CraftBukkit 1.19-R0.1-SNAPSHOT");
+        parser.parse_line(
+            "[ERROR] Failed to execute goal on project craftbukkit: Could not resolve \
+            dependencies for project org.bukkit:craftbukkit:jar:1.19-R0.1-SNAPSHOT: Could not \
+            find artifact org.spigotmc:minecraft-server:jar:1.19-R0.1-SNAPSHOT",
+        );
+        let failure = parser.failure();
+        assert_eq!(failure.module.as_deref(), Some("craftbukkit"));
+        assert_eq!(
+            failure
+                .dependency_failures
+                .len(),
+            1
+        );
+        assert_eq!(failure.goal, None);
+    }
+}
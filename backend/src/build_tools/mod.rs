@@ -1,15 +1,20 @@
+use crate::build_tools::bundler::{BundlerError, BundlerInfo};
+use crate::build_tools::commands::{format_command_line, BuildStep, CommandResolver};
 use crate::build_tools::mapping::Mapper;
-use crate::build_tools::maven::{MavenContext, MavenError};
+use crate::build_tools::maven::{MavenContext, MavenError, MavenWorkspace};
+use crate::build_tools::remapper::{remap_jar, Mappings, RemapError, RemapOptions};
 use crate::build_tools::spigot::SpigotError;
 use crate::models::build_tools::BuildDataInfo;
-use crate::utils::cmd::{execute_command, CommandError};
+use crate::utils::cmd::{execute_template, CommandError};
 use crate::utils::constants::PARODY_BUILD_TOOLS_VERSION;
 use crate::utils::files::{copy_contents, delete_existing, ensure_dir_exists, ensure_is_file};
-use crate::utils::git::{setup_repositories, Repo, RepoError, Repositories};
+use crate::utils::git::{setup_repositories, ConflictMode, Repo, RepoError, Repositories};
 use crate::utils::hash::HashType;
-use crate::utils::net::{download_file, NetworkError};
-use crate::utils::zip::{extract_file, remove_from_zip, unzip_filtered, ZipError};
+use crate::utils::net::{download, NetworkError};
+use crate::utils::versions::{get_version_package, get_versions, VersionsError};
+use crate::utils::zip::{extract_file, strip_signatures, unzip_filtered, ZipError, ZipWriteMode};
 use futures::future::{try_join_all, TryFutureExt};
+use git2::Repository;
 use log::{debug, info, warn};
 use std::env::current_dir;
 use std::io;
@@ -18,9 +23,14 @@ use thiserror::Error;
 use tokio::fs::{create_dir_all, read, remove_dir, remove_dir_all, symlink_dir, write};
 use tokio::try_join;
 
+mod bundler;
+mod class_file;
+pub(crate) mod commands;
 mod mapping;
 mod maven;
+mod maven_output;
 mod patches;
+mod remapper;
 pub(crate) mod spigot;
 
 type BuildResult<T> = Result<T, BuildToolsError>;
@@ -51,6 +61,12 @@ pub enum BuildToolsError {
     StripPrefix(#[from] StripPrefixError),
     #[error("Failed to patch: {0}")]
     Patch(#[from] patches::PatchError),
+    #[error("Failed bundler op: {0}")]
+    Bundler(#[from] BundlerError),
+    #[error("Failed to remap jar: {0}")]
+    Remap(#[from] RemapError),
+    #[error("Failed to load version: {0}")]
+    Versions(#[from] VersionsError),
 }
 pub struct Context<'a> {
     build_info: &'a BuildDataInfo,
@@ -61,34 +77,60 @@ pub struct Context<'a> {
     vanilla_jar: &'a Path,
     fm_jar: &'a Path,
     mappings_hash: &'a str,
+    commands: &'a CommandResolver,
 }
 
 pub async fn run_build_tools(version: &str) -> BuildResult<()> {
-    debug!("Retrieving spigot version...");
+    debug!("Resolving spigot version...");
 
-    let spigot_version = spigot::get_version(version).await?;
+    let resolved = spigot::resolve_version(version).await?;
+    let spigot_version = &resolved.spigot_version;
 
+    info!(
+        "Resolved {} to Minecraft {} (Spigot build {})",
+        resolved.requested, resolved.minecraft_version, resolved.build.name
+    );
     debug!("Loaded spigot version: {:#?}", spigot_version);
     debug!("Setting up build directory");
 
-    let build_path = Path::new("build");
+    // Each build has its own workspace so builds of different versions
+    // can run at the same time. The mirrors and maven are shared
+    let root_path = Path::new("build");
+    let build_path = &root_path.join(&resolved.minecraft_version);
     ensure_dir_exists(build_path).await?;
 
-    let (repositories, maven_path) = try_join!(
-        setup_repositories(build_path, &spigot_version).map_err(|err| BuildToolsError::Repo(err)),
-        maven::setup(build_path).map_err(|err| BuildToolsError::Maven(err))
+    let mirrors_path = root_path.join("mirrors");
+
+    let (repositories, maven_toolchain, maven_workspace) = try_join!(
+        setup_repositories(build_path, &mirrors_path, spigot_version)
+            .map_err(BuildToolsError::Repo),
+        maven::setup(root_path).map_err(BuildToolsError::Maven),
+        MavenWorkspace::setup(build_path).map_err(BuildToolsError::Maven)
     )?;
 
     let repositories: Repositories = repositories;
+
+    let build_info = get_build_info(build_path).await?;
+    // The checked out BuildData decides the Minecraft version built
+    if build_info.minecraft_version != resolved.minecraft_version {
+        warn!(
+            "BuildData is for Minecraft {} but {} resolved to {}, building {}",
+            build_info.minecraft_version,
+            resolved.requested,
+            resolved.minecraft_version,
+            build_info.minecraft_version
+        );
+    }
+    build_info.validate_commands()?;
+
     info!("Determining mappings hash");
-    let reference = Repo::get_mappings_reference(&repositories.build_data)?;
+    let reference =
+        Repo::get_mappings_reference(&repositories.build_data, &build_info.get_mappings_paths())?;
     let md = md5::compute(reference);
     let mappings_hash = &format!("{md:x}")[24..];
 
     info!("Mappings hash: {mappings_hash}");
 
-    let build_info = get_build_info(build_path).await?;
-
     // Check if required version is higher than parody version
     if let Some(tools_version) = build_info.tools_version {
         if tools_version > PARODY_BUILD_TOOLS_VERSION {
@@ -103,8 +145,7 @@ pub async fn run_build_tools(version: &str) -> BuildResult<()> {
     info!("Preparing vanilla jar");
     let jar_path = prepare_vanilla_jar(build_path, &build_info).await?;
 
-    // TODO: Remove jar signature. Possible to do later?
-    remove_embed_signature(build_path, &jar_path).await?;
+    let jar_path = remove_embed_signature(&jar_path).await?;
 
     let work_path = build_path.join("work");
     ensure_dir_exists(&work_path).await?;
@@ -113,19 +154,23 @@ pub async fn run_build_tools(version: &str) -> BuildResult<()> {
     let fm_jar = format!("mapping.{mappings_hash}.jar");
     let fm_jar = work_path.join(fm_jar);
 
+    let commands = CommandResolver::new(build_path);
+
     let context = Context {
         build_info: &build_info,
         build_path,
         work_path: &work_path,
         maven: MavenContext {
-            spigot_version: &spigot_version,
+            spigot_version,
             build_info: &build_info,
-            script_path: maven_path,
+            toolchain: maven_toolchain,
+            workspace: maven_workspace,
         },
         repositories: &repositories,
         vanilla_jar: &jar_path,
         fm_jar: &fm_jar,
         mappings_hash,
+        commands: &commands,
     };
 
     if ensure_is_file(&fm_jar).await? {
@@ -133,7 +178,7 @@ pub async fn run_build_tools(version: &str) -> BuildResult<()> {
     } else {
         let m_paths = create_mappings(&context).await?;
         if let Some(m_paths) = m_paths {
-            apply_special_source(&context, m_paths).await?;
+            apply_mappings(&context, m_paths).await?;
         }
     }
 
@@ -199,7 +244,8 @@ async fn prepare_vanilla_jar(root: &Path, info: &BuildDataInfo) -> BuildResult<P
         root.join(embedded_name)
     };
 
-    let embedded = extract_embedded(&jar_path, &embedded_path, info).await?;
+    let libraries_path = root.join(format!("libraries.{}", info.minecraft_version));
+    let embedded = extract_embedded(&jar_path, &embedded_path, &libraries_path, info).await?;
 
     let path = match embedded {
         ExtractType::Cached => {
@@ -227,49 +273,46 @@ enum ExtractType {
 }
 
 /// Attempts to extract the embedded jar from `path` to `embedded_path` but will
-/// return whether or not one existed.
+/// return whether or not one existed. The bundled libraries are extracted to
+/// `libraries_path` and all extracted files are verified against the bundler
+/// hashes
 async fn extract_embedded(
-    jar_path: &PathBuf,
-    embedded_path: &PathBuf,
+    jar_path: &Path,
+    embedded_path: &Path,
+    libraries_path: &Path,
     info: &BuildDataInfo,
 ) -> BuildResult<ExtractType> {
-    let embedded_path = embedded_path.clone();
-
-    let embedded_zip_path = format!(
-        "META-INF/versions/{0}/server-{0}.jar",
-        info.minecraft_version
-    );
+    let Some(bundler) = BundlerInfo::read(jar_path).await? else {
+        return Ok(ExtractType::None);
+    };
 
-    if ensure_is_file(&embedded_path).await? {
-        if let Some(mc_hash) = &info.minecraft_hash {
-            let existing = read(&embedded_path).await?;
-            if HashType::SHA256.is_match(mc_hash, existing) {
-                info!("Already extracted embedded jar with matching hash. Skipping.");
-                return Ok(ExtractType::Cached);
-            }
-        }
+    if let Some(main_class) = &bundler.main_class {
+        debug!("Bundled server main class: {main_class}");
     }
-    let existed = extract_file(jar_path, &embedded_path, &embedded_zip_path).await?;
-    Ok(if existed {
+    bundler
+        .extract_libraries(jar_path, libraries_path)
+        .await?;
+
+    let server = bundler.server(&info.minecraft_version)?;
+    let extracted = server
+        .extract(jar_path, embedded_path)
+        .await?;
+    Ok(if extracted {
         ExtractType::Done
     } else {
-        ExtractType::None
+        ExtractType::Cached
     })
 }
 
-/// Removes the MOJANGCS.RSA and MOJANGCS.SF from the jar file or
-/// else they wont function.
-async fn remove_embed_signature(path: &Path, jar_path: &Path) -> BuildResult<()> {
+/// Creates a copy of the jar without the signature files and manifest
+/// digests or else they wont function. The original jar is kept so its
+/// hash still matches on later builds. Returns the path of the copy
+async fn remove_embed_signature(jar_path: &Path) -> BuildResult<PathBuf> {
     info!("Removing signature from jar");
-    let tmp = path.join("tmp-extract.jar");
-    delete_existing(&tmp).await?;
-    remove_from_zip(
-        jar_path,
-        &tmp,
-        &["META-INF/MOJANGCS.RSA", "META-INF/MOJANGCS.SF"],
-    )
-    .await?;
-    Ok(())
+    let unsigned_path = jar_path.with_extension("unsigned.jar");
+    delete_existing(&unsigned_path).await?;
+    strip_signatures(jar_path, &unsigned_path, ZipWriteMode::deterministic()).await?;
+    Ok(unsigned_path)
 }
 
 /// Checks whether the locally stored server jar hash matches the one
@@ -281,11 +324,10 @@ async fn check_vanilla_jar(path: &Path, info: &BuildDataInfo) -> bool {
             return false;
         }
 
-        if let Ok(jar_bytes) = read(path).await {
-            hash_type.is_match(hash, jar_bytes)
-        } else {
-            false
-        }
+        hash_type
+            .is_file_match(hash, path)
+            .await
+            .unwrap_or(false)
     } else {
         path.exists()
     }
@@ -295,41 +337,67 @@ async fn check_vanilla_jar(path: &Path, info: &BuildDataInfo) -> bool {
 /// the provided path
 async fn download_vanilla_jar(path: &Path, info: &BuildDataInfo) -> BuildResult<()> {
     let url = info.get_download_url();
-    let bytes = reqwest::get(url)
-        .await?
-        .bytes()
-        .await?;
-    write(path, bytes).await?;
+    download(&url, path, info.get_server_hash()).await?;
     Ok(())
 }
 
-/// Replaces directory names that are normally for the Spigot build tools
-/// app with the names for this projects directory structure
-fn replace_dir_names(value: &str) -> String {
-    let mut out: String = value.to_string();
+/// Runs the command for the provided build step with the provided arguments
+async fn run_step(context: &Context<'_>, step: BuildStep, args: &[&str]) -> BuildResult<()> {
+    let template = context
+        .commands
+        .resolve(step, context.build_info)?;
+    let command_line = format_command_line(&template.render(args, &[])?);
+    info!("Running {step} command: {command_line}");
+    execute_template(current_dir()?, &template, args, &[]).await?;
+    Ok(())
+}
 
-    if out.contains("BuildData") {
-        out = out.replace("BuildData", "build/build_data")
-    }
-    if out.contains("Bukkit") {
-        out = out.replace("CraftBukkit", "build/craftbukkit")
-    }
-    if out.contains("Spigot") {
-        out = out.replace("Spigot", "build/spigot")
+/// Applies the mappings for the provided step to the `input` jar writing
+/// the mapped jar to `output`. The jar is remapped natively using the
+/// options from the SpecialSource command of the step unless running
+/// SpecialSource itself was opted into
+async fn map_jar(
+    context: &Context<'_>,
+    step: BuildStep,
+    input: &Path,
+    access_transforms: Option<&Path>,
+    mappings: &Path,
+    output: &Path,
+) -> BuildResult<()> {
+    let input_arg = input.to_string_lossy();
+    let access_arg = access_transforms.map(|path| path.to_string_lossy());
+    let mappings_arg = mappings.to_string_lossy();
+    let output_arg = output.to_string_lossy();
+    let mut args = vec![input_arg.as_ref()];
+    if let Some(access_arg) = &access_arg {
+        args.push(access_arg.as_ref());
     }
-    if out.contains("Bukkit") {
-        out = out.replace("Bukkit", "build/bukkit")
+    args.push(mappings_arg.as_ref());
+    args.push(output_arg.as_ref());
+    if context
+        .commands
+        .use_special_source()
+    {
+        return run_step(context, step, &args).await;
     }
 
-    out
+    let command = context
+        .commands
+        .resolve(step, context.build_info)?
+        .render(&args, &[])?;
+    info!("Remapping jar for {step}");
+    debug!("Using options from {}", format_command_line(&command));
+    let options = RemapOptions::from_special_source(&command).await?;
+    let mappings = Mappings::load(mappings).await?;
+    remap_jar(input, output, mappings, options).await?;
+    Ok(())
 }
 
-/// Applies the special source renaming to the jars
-async fn apply_special_source(context: &Context<'_>, m_paths: MappingsPaths) -> BuildResult<()> {
-    info!("Applying special source");
+/// Applies the class, member and final mappings to the vanilla jar
+async fn apply_mappings(context: &Context<'_>, m_paths: MappingsPaths) -> BuildResult<()> {
+    info!("Applying mappings");
 
     let mappings_hash = context.mappings_hash;
-    let current_dir = current_dir()?;
     let work_path = context.work_path;
 
     let clm_jar = format!("mappings.{mappings_hash}.jar-cl");
@@ -340,85 +408,45 @@ async fn apply_special_source(context: &Context<'_>, m_paths: MappingsPaths) ->
 
     let bd_info = context.build_info;
 
-    let cm_command = bd_info
-        .class_map_command
-        .as_ref()
-        .map(|value| replace_dir_names(value))
-        .unwrap_or_else(|| {
-            String::from(
-                "java -jar build/build_data/bin/SpecialSource-2.jar map -i {0} -m {1} -o {2}",
-            )
-        });
     info!("Applying class mappings");
-    execute_command(
-        &current_dir,
-        &cm_command,
-        &[
-            &context
-                .vanilla_jar
-                .to_string_lossy(),
-            &m_paths
-                .cm_path
-                .to_string_lossy(),
-            &clm_jar.to_string_lossy(),
-        ],
+    map_jar(
+        context,
+        BuildStep::ClassMap,
+        context.vanilla_jar,
+        None,
+        &m_paths.cm_path,
+        &clm_jar,
     )
     .await?;
 
     if let Some(mm_path) = &m_paths.mm_path {
-        let mm_command = bd_info
-            .class_map_command
-            .as_ref()
-            .map(|value| replace_dir_names(value))
-            .unwrap_or_else(|| {
-                String::from(
-                    "java -jar build/build_data/bin/SpecialSource-2.jar map -i {0} -m {1} -o {2}",
-                )
-            });
-
         info!("Applying member mappings");
-        execute_command(
-            &current_dir,
-            &mm_command,
-            &[
-                &clm_jar.to_string_lossy(),
-                &mm_path.to_string_lossy(),
-                &mm_jar.to_string_lossy(),
-            ],
+        map_jar(
+            context,
+            BuildStep::MemberMap,
+            &clm_jar,
+            None,
+            mm_path,
+            &mm_jar,
         )
         .await?;
     }
 
-    let fm_command = bd_info
-        .final_map_command
-        .as_ref()
-        .map(|value| replace_dir_names(value))
-        .unwrap_or_else(|| {
-            String::from(
-                "java -jar build/build_data/bin/SpecialSource.jar --kill-lvt -i {0} --access-transformer {1} -m {2} -o {3}",
-            )
-        });
-
-    let final_mappings = if let Some(package_mappings) = &bd_info.package_mappings {
-        format!("build/build_data/mappings/{}", package_mappings)
-    } else {
-        m_paths
-            .fm_path
-            .to_string_lossy()
-            .to_string()
+    let mappings_path = context
+        .build_path
+        .join("build_data/mappings");
+    let final_mappings = match &bd_info.package_mappings {
+        Some(package_mappings) => mappings_path.join(package_mappings),
+        None => m_paths.fm_path,
     };
     info!("Applying final mappings");
-    execute_command(
-        &current_dir,
-        &fm_command,
-        &[
-            &mm_jar.to_string_lossy(),
-            &format!("build/build_data/mappings/{}", bd_info.access_transforms),
-            &final_mappings,
-            &context
-                .fm_jar
-                .to_string_lossy(),
-        ],
+    map_jar(
+        context,
+        BuildStep::FinalMap,
+        &mm_jar,
+        Some(&mappings_path.join(&bd_info.access_transforms)),
+        &final_mappings,
+        context.fm_jar,
     )
     .await?;
 
@@ -436,6 +464,30 @@ struct MappingsPaths {
     fm_path: PathBuf,
 }
 
+/// Finds the download url for the Mojang server mappings along with their
+/// SHA-1 hash when its known. BuildData only includes the url for some
+/// versions so the server mappings from the version package are used
+/// when its missing. Versions older than the official mappings have none
+async fn get_mojang_mappings(
+    info: &BuildDataInfo,
+) -> BuildResult<Option<(String, Option<String>)>> {
+    if let Some(url) = info.get_mappings_url() {
+        return Ok(Some((url, None)));
+    }
+    let manifest = get_versions().await?;
+    let Some(version) = manifest.get(&info.minecraft_version) else {
+        debug!(
+            "Minecraft version {} is not in the version manifest",
+            info.minecraft_version
+        );
+        return Ok(None);
+    };
+    let package = get_version_package(version).await?;
+    Ok(package
+        .server_mappings()
+        .map(|download| (download.get_url(), Some(download.sha1.clone()))))
+}
+
 async fn create_mappings(context: &Context<'_>) -> BuildResult<Option<MappingsPaths>> {
     info!("Setting up mappings");
     let work_path = context.work_path;
@@ -469,12 +521,24 @@ async fn create_mappings(context: &Context<'_>) -> BuildResult<Option<MappingsPa
     let fm_path = format!("bukkit-{}-fields.csrg", mappings_hash);
     let fm_path = work_path.join(fm_path);
 
-    if let Some(mappings_url) = &bd_info.mappings_url {
+    if let Some((mappings_url, sha1)) = get_mojang_mappings(bd_info).await? {
         let mc_version = &bd_info.minecraft_version;
         let mojang_path = format!("server.{mc_version}.txt");
         let mojang_path = work_path.join(mojang_path);
-        if !ensure_is_file(&mojang_path).await? {
-            download_file(mappings_url, &mojang_path).await?;
+        let expected = sha1
+            .as_deref()
+            .map(|hash| (HashType::SHA1, hash));
+        let cached = match expected {
+            Some((hash_type, hash)) if ensure_is_file(&mojang_path).await? => {
+                hash_type
+                    .is_file_match(hash, &mojang_path)
+                    .await?
+            }
+            Some(_) => false,
+            None => ensure_is_file(&mojang_path).await?,
+        };
+        if !cached {
+            download(&mappings_url, &mojang_path, expected).await?;
         }
 
         // Bukkit mappings (Class mappings)
@@ -560,20 +624,9 @@ async fn decompile(context: &Context<'_>) -> BuildResult<PathBuf> {
             name.starts_with("net/minecraft")
         })
         .await?;
-        let bd_info = context.build_info;
-        let current_dir = current_dir()?;
-        let decomp_command = bd_info
-            .decompile_command
-            .as_ref()
-            .map(|value| replace_dir_names(value))
-            .unwrap_or_else(|| {
-                String::from(
-                    "java -jar build/build_data/bin/fernflower.jar -dgs=1 -hdc=0 -rbr=0 -asc=1 -udv=0 {0} {1}",
-                )
-            });
-        execute_command(
-            &current_dir,
-            &decomp_command,
+        run_step(
+            context,
+            BuildStep::Decompile,
             &[&class_dir.to_string_lossy(), &decomp_path.to_string_lossy()],
         )
         .await?;
@@ -590,6 +643,9 @@ async fn decompile(context: &Context<'_>) -> BuildResult<PathBuf> {
     Ok(decomp_path)
 }
 
+/// Assembles the Spigot-Server and Spigot-API repositories by resetting them
+/// to clean copies of the patched CraftBukkit and Bukkit repositories and then
+/// applying the Spigot patches on top (like Spigot's applyPatches.sh)
 async fn apply_spigot_patches(context: &Context<'_>) -> BuildResult<()> {
     let build_path = context.build_path;
     let sp_path = build_path.join("spigot");
@@ -597,29 +653,53 @@ async fn apply_spigot_patches(context: &Context<'_>) -> BuildResult<()> {
     let cb_path = build_path.join("craftbukkit");
     let bk_path = build_path.join("bukkit");
 
-    let ss_path = sp_path.join("Spigot-Server");
-    let sa_path = sp_path.join("Spigot-API");
-
-    try_join!(
-        copy_contents(cb_path, &ss_path),
-        copy_contents(bk_path, &sa_path)
-    )?;
+    let (a, b) = reset_spigot_upstreams(
+        &context
+            .repositories
+            .craft_bukkit,
+        &cb_path,
+        &bk_path,
+        &sp_path,
+    )
+    .await?;
 
     let cb_patches = sp_path.join("CraftBukkit-Patches");
     let bk_patches = sp_path.join("Bukkit-Patches");
 
-    let a = Repo::open(&ss_path)?;
-    let b = Repo::open(&sa_path)?;
-
     info!("Applying Spigot Craft Bukkit Patches");
+    let report = Repo::apply_patches(&a, &cb_patches, ConflictMode::Stop).await?;
+    info!("Spigot Craft Bukkit Patches: {report}");
 
-    Repo::apply_patches(&a, &cb_patches).await?;
     info!("Applying Spigot Bukkit Patches");
-    Repo::apply_patches(&b, &bk_patches).await?;
+    let report = Repo::apply_patches(&b, &bk_patches, ConflictMode::Stop).await?;
+    info!("Spigot Bukkit Patches: {report}");
 
     Ok(())
 }
 
+/// Commits the patched CraftBukkit sources onto its patched branch and resets
+/// the Spigot-Server and Spigot-API repositories within `sp_path` to clean
+/// copies of that branch and the Bukkit HEAD
+async fn reset_spigot_upstreams(
+    craft_bukkit: &Repository,
+    cb_path: &Path,
+    bk_path: &Path,
+    sp_path: &Path,
+) -> BuildResult<(Repository, Repository)> {
+    let ss_path = sp_path.join("Spigot-Server");
+    let sa_path = sp_path.join("Spigot-API");
+
+    // The branch name is specific to the worktree CraftBukkit is checked out in
+    let patched = Repo::create_patched_branch(craft_bukkit)?;
+
+    info!("Resetting Spigot-Server and Spigot-API to upstream");
+    let repos = try_join!(
+        Repo::reset_to_upstream(cb_path, &patched, ss_path),
+        Repo::reset_to_upstream(bk_path, "HEAD", sa_path)
+    )?;
+    Ok(repos)
+}
+
 /// Applies the CraftBukkit patches from craftbukkit/nms-patches to the
 /// decompiled sources
 async fn apply_cb_patches(context: &Context<'_>, decomp_path: &PathBuf) -> BuildResult<()> {
@@ -709,14 +789,96 @@ async fn compile_spigot(context: &Context<'_>) -> BuildResult<()> {
 
 #[cfg(test)]
 mod test {
+    use crate::build_tools::reset_spigot_upstreams;
     use crate::build_tools::run_build_tools;
     use crate::build_tools::spigot::get_version_test;
     use crate::build_tools::spigot::test::TEST_VERSIONS;
     use crate::models::build_tools::BuildDataInfo;
     use crate::utils::git::setup_repositories;
+    use crate::utils::testing::test_dir;
+    use git2::{IndexAddOption, Repository, Signature};
+    use std::fs::{create_dir_all, write};
     use std::path::Path;
     use tokio::fs::read;
 
+    /// Stages and commits everything in the working tree of `repo`
+    fn commit_all(repo: &Repository) {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo
+            .find_tree(index.write_tree().unwrap())
+            .unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Commit",
+            &tree,
+            &parents,
+        )
+        .unwrap();
+    }
+
+    /// Tests that Spigot-Server is reset to the patched branch of the
+    /// worktree CraftBukkit is checked out in rather than a stale branch
+    /// left behind in the shared mirror
+    #[tokio::test]
+    async fn test_reset_spigot_upstreams() {
+        let root = test_dir("spigot-upstreams");
+        let mirror_path = root.join("mirror");
+        let cb_path = root.join("craftbukkit");
+        let bk_path = root.join("bukkit");
+        let sp_path = root.join("spigot");
+        create_dir_all(&mirror_path).unwrap();
+        create_dir_all(&bk_path).unwrap();
+
+        let mirror = Repository::init(&mirror_path).unwrap();
+        write(mirror_path.join("README.md"), "CraftBukkit").unwrap();
+        commit_all(&mirror);
+        // Stale branch from a build that didn't use worktrees
+        let head = mirror
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        mirror
+            .branch("patched", &head, true)
+            .unwrap();
+
+        mirror
+            .worktree("build", &cb_path, None)
+            .unwrap();
+        let craft_bukkit = Repository::open(&cb_path).unwrap();
+        assert!(craft_bukkit.is_worktree());
+        let decompiled = cb_path.join("src/main/java/net");
+        create_dir_all(&decompiled).unwrap();
+        write(decompiled.join("Decompiled.java"), "class Decompiled {}").unwrap();
+
+        let bukkit = Repository::init(&bk_path).unwrap();
+        write(bk_path.join("Bukkit.java"), "class Bukkit {}").unwrap();
+        commit_all(&bukkit);
+
+        reset_spigot_upstreams(&craft_bukkit, &cb_path, &bk_path, &sp_path)
+            .await
+            .unwrap();
+
+        assert!(sp_path
+            .join("Spigot-Server/src/main/java/net/Decompiled.java")
+            .exists());
+        assert!(sp_path
+            .join("Spigot-API/Bukkit.java")
+            .exists());
+    }
+
     /// Sets up the local repositories with data from all the
     /// versions listed in `TEST_VERSIONS`
     #[tokio::test]
@@ -749,7 +911,7 @@ mod test {
             .await
             .unwrap();
         let test_path = Path::new("test/build");
-        setup_repositories(test_path, &spigot_version)
+        setup_repositories(test_path, &test_path.join("mirrors"), &spigot_version)
             .await
             .unwrap();
         let build_data = Path::new("test/build/build_data");
@@ -770,6 +932,8 @@ mod test {
         if version != "latest" {
             assert_eq!(&info.minecraft_version, version);
         }
+        info.validate_commands()
+            .unwrap();
 
         let mappings_path = path.join("mappings");
         let access_transforms = mappings_path.join(info.access_transforms);
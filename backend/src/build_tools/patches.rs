@@ -1,10 +1,10 @@
 use async_walkdir::WalkDir;
-use cached::instant::SystemTime;
 use futures::StreamExt;
 use log::{debug, info, warn};
 use patch::{Line, Patch};
 use std::io;
 use std::path::PathBuf;
+use std::time::SystemTime;
 use thiserror::Error;
 use tokio::fs::{create_dir_all, read, write};
 
@@ -0,0 +1,1980 @@
+use crate::build_tools::class_file::{
+    instructions, write_u16, Attribute, ClassFile, ClassFileError, ClassFileResult, ClassReader,
+    CodeAttribute, Constant, ConstantPool, Member,
+};
+use crate::build_tools::mapping::{CowMapping, Mapper};
+use crate::utils::zip::{read_files, rewrite_zip, EntryAction, ZipError, ZipWriteMode};
+use log::{debug, warn};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+use tokio::fs::read;
+use tokio::task::{spawn_blocking, JoinError};
+
+/// Access flags used by the access transformer
+const ACC_PUBLIC: u16 = 0x0001;
+const ACC_PRIVATE: u16 = 0x0002;
+const ACC_PROTECTED: u16 = 0x0004;
+const ACC_STATIC: u16 = 0x0008;
+const ACC_FINAL: u16 = 0x0010;
+const VISIBILITY_MASK: u16 = ACC_PUBLIC | ACC_PRIVATE | ACC_PROTECTED;
+
+/// Access flags used when generating member names
+const ACC_BRIDGE: u16 = 0x0040;
+const ACC_SYNTHETIC: u16 = 0x1000;
+const ACC_ENUM: u16 = 0x4000;
+
+/// Descriptors of the logger fields renamed by `AutoMember::Logger`
+const LOGGER_DESCRIPTORS: [&str; 2] = ["Lorg/apache/logging/log4j/Logger;", "Lorg/slf4j/Logger;"];
+
+/// Reserved words which can't be used as local variable names
+const RESERVED_NAMES: [&str; 16] = [
+    "boolean",
+    "byte",
+    "char",
+    "class",
+    "double",
+    "enum",
+    "float",
+    "int",
+    "interface",
+    "long",
+    "new",
+    "package",
+    "short",
+    "switch",
+    "this",
+    "void",
+];
+
+/// Bootstrap class for lambdas and method references
+const LAMBDA_METAFACTORY: &str = "java/lang/invoke/LambdaMetafactory";
+
+#[derive(Debug, Error)]
+pub enum RemapError {
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    #[error(transparent)]
+    Zip(#[from] ZipError),
+    #[error("Invalid class {0}: {1}")]
+    Class(String, ClassFileError),
+    #[error("Invalid mapping line \"{0}\"")]
+    InvalidMapping(String),
+    #[error("Invalid access transformer line \"{0}\"")]
+    InvalidAccess(String),
+    #[error(transparent)]
+    JoinError(#[from] JoinError),
+    #[error("Not a SpecialSource command: {0}")]
+    UnsupportedCommand(String),
+    #[error("Invalid SpecialSource argument {0}")]
+    InvalidArgument(String),
+}
+
+type RemapResult<T> = Result<T, RemapError>;
+
+/// Field and method mappings for a single class
+#[derive(Debug, Default)]
+struct ClassMembers {
+    /// Field name -> mapped name
+    fields: HashMap<String, String>,
+    /// Method name -> descriptor -> mapped name
+    methods: HashMap<String, HashMap<String, String>>,
+}
+
+/// Class, package and member mappings loaded from CSRG files. Member
+/// mappings are keyed by the input names of their class
+pub struct Mappings {
+    classes: CowMapping<'static>,
+    /// Package mappings ordered with the longest first. The
+    /// default package is represented by `./`
+    packages: Vec<(String, String)>,
+    members: HashMap<String, ClassMembers>,
+    /// Class and package prefixes which are never mapped
+    excluded: Vec<String>,
+}
+
+impl Mappings {
+    /// Parses CSRG mappings. Lines are either `package/ new/package/`,
+    /// `class new`, `class field new` or `class method descriptor new`
+    pub fn parse(text: &str) -> RemapResult<Self> {
+        let mut classes = CowMapping::new();
+        let mut packages = Vec::new();
+        let mut members: HashMap<String, ClassMembers> = HashMap::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts = line
+                .split_whitespace()
+                .collect::<Vec<&str>>();
+            match parts[..] {
+                [package, target] if package.ends_with('/') => {
+                    packages.push((package.to_string(), target.to_string()));
+                }
+                [class, target] => {
+                    classes.insert_owned(class.to_string(), target.to_string());
+                }
+                [class, field, target] => {
+                    members
+                        .entry(class.to_string())
+                        .or_default()
+                        .fields
+                        .insert(field.to_string(), target.to_string());
+                }
+                [class, method, descriptor, target] => {
+                    members
+                        .entry(class.to_string())
+                        .or_default()
+                        .methods
+                        .entry(method.to_string())
+                        .or_default()
+                        .insert(descriptor.to_string(), target.to_string());
+                }
+                _ => return Err(RemapError::InvalidMapping(line.to_string())),
+            }
+        }
+        packages.sort_by_key(|(package, _)| Reverse(package.len()));
+        Ok(Self {
+            classes,
+            packages,
+            members,
+            excluded: Vec::new(),
+        })
+    }
+
+    /// Loads the CSRG mappings file at the provided path
+    pub async fn load(path: impl AsRef<Path>) -> RemapResult<Self> {
+        let contents = read(path).await?;
+        Self::parse(&String::from_utf8_lossy(&contents))
+    }
+
+    /// Maps the provided class name. Nested classes are mapped using
+    /// their outer class when they have no mapping of their own and
+    /// package mappings are used for classes without a mapping
+    pub fn map_class(&self, name: &str) -> Option<String> {
+        if self.is_excluded(name) {
+            return None;
+        }
+        Mapper::mapped_value(name, &self.classes).or_else(|| self.map_package(name))
+    }
+
+    /// Maps the provided class name returning the name
+    /// unchanged if there is no mapping
+    pub fn class(&self, name: &str) -> String {
+        self.map_class(name)
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// Maps the package of the provided class name
+    fn map_package(&self, name: &str) -> Option<String> {
+        let (package, target) = self
+            .packages
+            .iter()
+            .find(|(package, _)| {
+                if package == "./" {
+                    !name.contains('/')
+                } else {
+                    name.starts_with(package.as_str())
+                }
+            })?;
+        let simple = if package == "./" {
+            name
+        } else {
+            &name[package.len()..]
+        };
+        if target == "./" {
+            Some(simple.to_string())
+        } else {
+            Some(format!("{target}{simple}"))
+        }
+    }
+
+    /// Excludes the classes within the provided class or package
+    /// prefixes from being mapped
+    pub fn exclude(&mut self, prefixes: &[String]) {
+        self.excluded
+            .extend_from_slice(prefixes);
+    }
+
+    fn is_excluded(&self, name: &str) -> bool {
+        self.excluded
+            .iter()
+            .any(|prefix| matches_prefix(name, prefix))
+    }
+
+    /// The member mappings of the provided class if it isn't excluded
+    fn class_members(&self, class: &str) -> Option<&ClassMembers> {
+        if self.is_excluded(class) {
+            return None;
+        }
+        self.members.get(class)
+    }
+
+    /// Adds a generated member name unless the member is already mapped
+    fn add_generated(&mut self, generated: GeneratedName) {
+        let members = self
+            .members
+            .entry(generated.class)
+            .or_default();
+        match generated.descriptor {
+            Some(descriptor) => members
+                .methods
+                .entry(generated.name)
+                .or_default()
+                .entry(descriptor)
+                .or_insert(generated.mapped),
+            None => members
+                .fields
+                .entry(generated.name)
+                .or_insert(generated.mapped),
+        };
+    }
+
+    /// The mapped name of the field declared in the provided class
+    pub fn field(&self, class: &str, name: &str) -> Option<&str> {
+        self.class_members(class)?
+            .fields
+            .get(name)
+            .map(String::as_str)
+    }
+
+    /// The mapped name of the method declared in the provided class
+    pub fn method(&self, class: &str, name: &str, descriptor: &str) -> Option<&str> {
+        self.class_members(class)?
+            .methods
+            .get(name)?
+            .get(descriptor)
+            .map(String::as_str)
+    }
+
+    /// Maps the class names within the provided descriptor or generic
+    /// signature. Malformed signatures are returned unchanged
+    pub fn map_signature(&self, signature: &str) -> String {
+        if !signature.contains('L') {
+            return signature.to_string();
+        }
+        SignatureMapper {
+            mappings: self,
+            input: signature,
+            position: 0,
+            output: String::with_capacity(signature.len()),
+        }
+        .map()
+        .unwrap_or_else(|| signature.to_string())
+    }
+}
+
+/// Parser for descriptors and generic signatures which copies the
+/// input to the output mapping any class names it encounters
+struct SignatureMapper<'a> {
+    mappings: &'a Mappings,
+    input: &'a str,
+    position: usize,
+    output: String,
+}
+
+impl<'a> SignatureMapper<'a> {
+    fn map(mut self) -> Option<String> {
+        if self.peek() == Some(b'<') {
+            self.formal_parameters()?;
+        }
+        while let Some(value) = self.peek() {
+            match value {
+                b'(' | b')' => self.push(),
+                b'^' => {
+                    self.push();
+                    self.value_type()?;
+                }
+                _ => self.value_type()?,
+            }
+        }
+        Some(self.output)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input
+            .as_bytes()
+            .get(self.position)
+            .copied()
+    }
+
+    /// Copies the current ASCII character to the output
+    fn push(&mut self) {
+        self.output
+            .push(self.input.as_bytes()[self.position] as char);
+        self.position += 1;
+    }
+
+    /// Takes the text up to (but not including) the first of
+    /// the provided delimiters
+    fn take_until(&mut self, delimiters: &[u8]) -> Option<&'a str> {
+        let rest = &self.input[self.position..];
+        let end = rest
+            .bytes()
+            .position(|value| delimiters.contains(&value))?;
+        self.position += end;
+        Some(&rest[..end])
+    }
+
+    /// Formal type parameters e.g. `<T:Ljava/lang/Object;U::La;>`
+    fn formal_parameters(&mut self) -> Option<()> {
+        self.push();
+        loop {
+            if self.peek()? == b'>' {
+                self.push();
+                return Some(());
+            }
+            let name = self.take_until(b":")?;
+            self.output.push_str(name);
+            while self.peek() == Some(b':') {
+                self.push();
+                if matches!(self.peek()?, b'L' | b'[' | b'T') {
+                    self.value_type()?;
+                }
+            }
+        }
+    }
+
+    fn value_type(&mut self) -> Option<()> {
+        match self.peek()? {
+            b'L' => self.class_type(),
+            b'T' => {
+                let variable = self.take_until(b";")?;
+                self.output.push_str(variable);
+                self.push();
+                Some(())
+            }
+            b'[' | b'+' | b'-' => {
+                self.push();
+                self.value_type()
+            }
+            b'*' | b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b'V' => {
+                self.push();
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    /// Class type including any type arguments and inner
+    /// class suffixes e.g. `La<TT;>.b;`
+    fn class_type(&mut self) -> Option<()> {
+        self.push();
+        let name = self.take_until(b"<;.")?;
+        let mut current = name.to_string();
+        self.output
+            .push_str(&self.mappings.class(name));
+        loop {
+            match self.peek()? {
+                b'<' => {
+                    self.push();
+                    while self.peek()? != b'>' {
+                        self.value_type()?;
+                    }
+                    self.push();
+                }
+                b'.' => {
+                    self.push();
+                    let inner = self.take_until(b"<;.")?;
+                    current = format!("{current}${inner}");
+                    let mapped = self.mappings.class(&current);
+                    let simple = mapped
+                        .rsplit_once('$')
+                        .map(|(_, simple)| simple)
+                        .unwrap_or(inner);
+                    self.output.push_str(simple);
+                }
+                b';' => {
+                    self.push();
+                    return Some(());
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Change to the access of a class or member
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct AccessChange {
+    /// The visibility flag to apply (zero for package private)
+    visibility: Option<u16>,
+    /// Whether the final flag should be added or removed
+    final_flag: Option<bool>,
+}
+
+impl AccessChange {
+    /// Parses a modifier such as `public`, `protected-f` or `private+f`
+    fn parse(value: &str) -> Option<Self> {
+        let (level, final_flag) = if let Some(level) = value.strip_suffix("+f") {
+            (level, Some(true))
+        } else if let Some(level) = value.strip_suffix("-f") {
+            (level, Some(false))
+        } else {
+            (value, None)
+        };
+        let visibility = match level {
+            "public" => Some(ACC_PUBLIC),
+            "protected" => Some(ACC_PROTECTED),
+            "private" => Some(ACC_PRIVATE),
+            "default" => Some(0),
+            "" if final_flag.is_some() => None,
+            _ => return None,
+        };
+        Some(Self {
+            visibility,
+            final_flag,
+        })
+    }
+
+    /// Ranks visibility flags from least to most visible
+    fn rank(access: u16) -> u8 {
+        match access & VISIBILITY_MASK {
+            ACC_PUBLIC => 3,
+            ACC_PROTECTED => 2,
+            ACC_PRIVATE => 0,
+            _ => 1,
+        }
+    }
+
+    /// Combines two changes for the same target keeping the
+    /// most visible access
+    fn merge(self, other: Self) -> Self {
+        let visibility = match (self.visibility, other.visibility) {
+            (Some(a), Some(b)) if Self::rank(b) > Self::rank(a) => Some(b),
+            (Some(a), _) => Some(a),
+            (None, b) => b,
+        };
+        Self {
+            visibility,
+            final_flag: other
+                .final_flag
+                .or(self.final_flag),
+        }
+    }
+
+    /// Applies the change to the provided access flags. Visibility
+    /// is only ever increased
+    fn apply(&self, mut access: u16) -> u16 {
+        if let Some(visibility) = self.visibility {
+            if Self::rank(visibility) > Self::rank(access) {
+                access = (access & !VISIBILITY_MASK) | visibility;
+            }
+        }
+        match self.final_flag {
+            Some(true) => access | ACC_FINAL,
+            Some(false) => access & !ACC_FINAL,
+            None => access,
+        }
+    }
+}
+
+/// Access transformer changing the access of classes and members. Lines
+/// are formatted as `public-f net/minecraft/server/Entity` optionally
+/// followed by a field name, method name and descriptor or the `*` and
+/// `*()` wildcards for all fields and methods. Targets use the input
+/// names of the jar being remapped
+#[derive(Debug, Default)]
+pub struct AccessTransformer {
+    /// Changes keyed by `class`, `class field` or `class method(desc)`
+    changes: HashMap<String, AccessChange>,
+}
+
+impl AccessTransformer {
+    pub fn parse(text: &str) -> RemapResult<Self> {
+        let mut changes: HashMap<String, AccessChange> = HashMap::new();
+        for line in text.lines() {
+            let line = match line.split_once('#') {
+                Some((line, _)) => line,
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || RemapError::InvalidAccess(line.to_string());
+            let parts = line
+                .split_whitespace()
+                .collect::<Vec<&str>>();
+            let (modifier, key) = match parts[..] {
+                [modifier, target] => match target.split_once('.') {
+                    Some((class, member)) => (modifier, format!("{class} {member}")),
+                    None => (modifier, target.to_string()),
+                },
+                [modifier, class, member] => (modifier, format!("{class} {member}")),
+                [modifier, class, method, descriptor] => {
+                    (modifier, format!("{class} {method}{descriptor}"))
+                }
+                _ => return Err(invalid()),
+            };
+            let change = AccessChange::parse(modifier).ok_or_else(invalid)?;
+            let change = match changes.get(&key) {
+                Some(existing) => existing.merge(change),
+                None => change,
+            };
+            changes.insert(key, change);
+        }
+        Ok(Self { changes })
+    }
+
+    /// Loads the access transformer file at the provided path
+    pub async fn load(path: impl AsRef<Path>) -> RemapResult<Self> {
+        let contents = read(path).await?;
+        Self::parse(&String::from_utf8_lossy(&contents))
+    }
+
+    /// Applies the changes under the provided keys in order
+    fn apply(&self, keys: &[String], access: u16) -> u16 {
+        keys.iter()
+            .filter_map(|key| self.changes.get(key))
+            .fold(access, |access, change| change.apply(access))
+    }
+
+    fn apply_class(&self, class: &str, access: u16) -> u16 {
+        self.apply(&[class.to_string()], access)
+    }
+
+    fn apply_field(&self, class: &str, name: &str, access: u16) -> u16 {
+        self.apply(&[format!("{class} *"), format!("{class} {name}")], access)
+    }
+
+    fn apply_method(&self, class: &str, name: &str, descriptor: &str, access: u16) -> u16 {
+        self.apply(
+            &[
+                format!("{class} *()"),
+                format!("{class} {name}{descriptor}"),
+            ],
+            access,
+        )
+    }
+}
+
+/// Local variable naming strategies (SpecialSource `--auto-lvt`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoLvt {
+    /// Names variables after their types (e.g. `i`, `flag` or `blockposition1`)
+    Basic,
+}
+
+/// Member naming strategies (SpecialSource `--auto-member`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoMember {
+    /// Names the static logger field of a class `LOGGER`
+    Logger,
+    /// Names enum constant fields after the names passed to their constructors
+    Tokens,
+    /// Names synthetic bridge methods after the methods they call
+    Synthetic,
+}
+
+/// Options for remapping a jar
+#[derive(Debug, Default)]
+pub struct RemapOptions {
+    /// Access changes to apply to the classes
+    pub access: Option<AccessTransformer>,
+    /// Whether the local variable tables should be removed
+    pub kill_lvt: bool,
+    /// Class or package prefixes of the classes to remap (`.` for the
+    /// default package). All classes are remapped when empty
+    pub only: Vec<String>,
+    /// Class or package prefixes which are neither remapped nor mapped
+    pub excluded: Vec<String>,
+    /// Strategy for renaming local variables
+    pub auto_lvt: Option<AutoLvt>,
+    /// Strategies for naming members without a mapping
+    pub auto_members: Vec<AutoMember>,
+}
+
+impl RemapOptions {
+    /// Creates the options equivalent to the arguments of a SpecialSource
+    /// command. The input, output and mappings arguments are ignored as
+    /// they are provided to `remap_jar` directly. Files referenced by the
+    /// arguments are loaded and unsupported arguments are skipped
+    pub async fn from_special_source(args: &[String]) -> RemapResult<Self> {
+        let start = args
+            .iter()
+            .position(|arg| {
+                arg.to_lowercase()
+                    .contains("specialsource")
+            })
+            .ok_or_else(|| RemapError::UnsupportedCommand(args.join(" ")))?;
+
+        let mut options = Self::default();
+        let mut args = args[start + 1..].iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline
+                    .map(str::to_string)
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| RemapError::InvalidArgument(arg.clone()))
+            };
+            match flag {
+                "map" => {}
+                "-i" | "--in-jar" | "-o" | "--out-jar" | "-m" | "--srg-in" => {
+                    value()?;
+                }
+                "--access-transformer" => {
+                    options.access = Some(AccessTransformer::load(value()?).await?);
+                }
+                "-e" | "--excluded-packages" => {
+                    let path = value()?;
+                    options
+                        .excluded
+                        .extend(load_excluded(&path).await?);
+                }
+                "--only" => options.only.push(value()?),
+                "--kill-lvt" => options.kill_lvt = true,
+                "--auto-lvt" => {
+                    let strategy = value()?;
+                    options.auto_lvt = match strategy.as_str() {
+                        "BASIC" => Some(AutoLvt::Basic),
+                        _ => return Err(RemapError::InvalidArgument(format!("{arg} {strategy}"))),
+                    };
+                }
+                "--auto-member" => {
+                    let strategy = value()?;
+                    let member = match strategy.as_str() {
+                        "LOGGER" => AutoMember::Logger,
+                        "TOKENS" => AutoMember::Tokens,
+                        "SYNTHETIC" => AutoMember::Synthetic,
+                        _ => return Err(RemapError::InvalidArgument(format!("{arg} {strategy}"))),
+                    };
+                    options
+                        .auto_members
+                        .push(member);
+                }
+                _ => warn!("Ignoring unsupported SpecialSource argument {arg}"),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Whether the provided class should be remapped
+    fn is_included(&self, name: &str) -> bool {
+        (self.only.is_empty()
+            || self
+                .only
+                .iter()
+                .any(|prefix| matches_prefix(name, prefix)))
+            && !self
+                .excluded
+                .iter()
+                .any(|prefix| matches_prefix(name, prefix))
+    }
+}
+
+/// Loads the class and package names from a SpecialSource exclusion file
+async fn load_excluded(path: &str) -> RemapResult<Vec<String>> {
+    let contents = read(path).await?;
+    Ok(String::from_utf8_lossy(&contents)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            if line == "." {
+                line.to_string()
+            } else {
+                line.replace('.', "/")
+            }
+        })
+        .collect())
+}
+
+/// Whether the class name is within the provided class or package
+/// prefix. The `.` prefix matches classes in the default package
+fn matches_prefix(name: &str, prefix: &str) -> bool {
+    if prefix == "." || prefix == "./" {
+        return !name.contains('/');
+    }
+    let prefix = prefix.trim_end_matches('/');
+    name.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '$']))
+}
+
+/// Member name generated by one of the `AutoMember` strategies
+struct GeneratedName {
+    /// Input name of the declaring class
+    class: String,
+    name: String,
+    /// Descriptor of methods, None for fields
+    descriptor: Option<String>,
+    mapped: String,
+}
+
+/// Remaps the classes of a jar. Member lookups search the super
+/// classes and interfaces of classes within the jar so inherited
+/// and overriding members are mapped like their declarations
+struct Remapper<'a> {
+    mappings: &'a Mappings,
+    options: &'a RemapOptions,
+    /// The super class and interfaces of each class in the jar
+    parents: HashMap<String, Vec<String>>,
+}
+
+impl<'a> Remapper<'a> {
+    /// Searches the provided class and its parents for a
+    /// mapping using the `lookup` function
+    fn find_member<F>(&self, owner: &str, lookup: F) -> Option<&'a str>
+    where
+        F: Fn(&str) -> Option<&'a str>,
+    {
+        let mut pending = vec![owner];
+        let mut visited = HashSet::new();
+        while let Some(class) = pending.pop() {
+            if !visited.insert(class) {
+                continue;
+            }
+            if let Some(mapped) = lookup(class) {
+                return Some(mapped);
+            }
+            if let Some(parents) = self.parents.get(class) {
+                pending.extend(
+                    parents
+                        .iter()
+                        .rev()
+                        .map(String::as_str),
+                );
+            }
+        }
+        None
+    }
+
+    fn map_field(&self, owner: &str, name: &str) -> Option<&'a str> {
+        let mappings = self.mappings;
+        self.find_member(owner, |class| mappings.field(class, name))
+    }
+
+    fn map_method(&self, owner: &str, name: &str, descriptor: &str) -> Option<&'a str> {
+        if name.starts_with('<') {
+            return None;
+        }
+        let mappings = self.mappings;
+        self.find_member(owner, |class| mappings.method(class, name, descriptor))
+    }
+
+    /// Generates names for the members of the provided class which have
+    /// no mapping using the `AutoMember` strategies of the options
+    fn generate_members(&self, class: &ClassFile) -> ClassFileResult<Vec<GeneratedName>> {
+        let strategies = &self.options.auto_members;
+        let mut generated = Vec::new();
+        let name = class.name()?;
+        if !self.options.is_included(name) {
+            return Ok(generated);
+        }
+        let pool = &class.pool;
+        let mappings = self.mappings;
+
+        if strategies.contains(&AutoMember::Logger) {
+            let mut loggers = Vec::new();
+            for field in &class.fields {
+                if field.access & ACC_STATIC != 0
+                    && LOGGER_DESCRIPTORS.contains(&pool.utf8(field.descriptor)?)
+                {
+                    loggers.push(pool.utf8(field.name)?);
+                }
+            }
+            if let [logger] = loggers[..] {
+                if logger != "LOGGER"
+                    && mappings
+                        .field(name, logger)
+                        .is_none()
+                {
+                    generated.push(GeneratedName {
+                        class: name.to_string(),
+                        name: logger.to_string(),
+                        descriptor: None,
+                        mapped: "LOGGER".to_string(),
+                    });
+                }
+            }
+        }
+
+        if strategies.contains(&AutoMember::Tokens) && class.access & ACC_ENUM != 0 {
+            generated.extend(enum_tokens(class, name, mappings)?);
+        }
+
+        if strategies.contains(&AutoMember::Synthetic) {
+            for method in &class.methods {
+                if method.access & (ACC_SYNTHETIC | ACC_BRIDGE) != ACC_SYNTHETIC | ACC_BRIDGE {
+                    continue;
+                }
+                let method_name = pool.utf8(method.name)?;
+                let descriptor = pool.utf8(method.descriptor)?;
+                if self
+                    .map_method(name, method_name, descriptor)
+                    .is_some()
+                {
+                    continue;
+                }
+                let Some((target, target_descriptor)) = bridge_target(class, name, method)? else {
+                    continue;
+                };
+                let mapped = self
+                    .map_method(name, target, target_descriptor)
+                    .unwrap_or(target);
+                if mapped != method_name {
+                    generated.push(GeneratedName {
+                        class: name.to_string(),
+                        name: method_name.to_string(),
+                        descriptor: Some(descriptor.to_string()),
+                        mapped: mapped.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(generated)
+    }
+
+    /// Adds a UTF-8 entry for the mapped value if it differs from the
+    /// value of the entry at `index` returning the index to use
+    fn replace_utf8(
+        pool: &mut ConstantPool,
+        original: &ConstantPool,
+        index: u16,
+        mapped: &str,
+    ) -> ClassFileResult<u16> {
+        if original.utf8(index)? == mapped {
+            Ok(index)
+        } else {
+            pool.add_utf8(mapped)
+        }
+    }
+
+    /// Remaps the provided class returning the mapped class name
+    /// and the bytes of the remapped class
+    fn remap_class(&self, mut class: ClassFile) -> ClassFileResult<(String, Vec<u8>)> {
+        let original = class.pool.clone();
+        let name = original
+            .class_name(class.this_class)?
+            .to_string();
+        let bootstraps = bootstrap_methods(&class.attributes, &original)?;
+        self.remap_pool(&original, &mut class.pool, &bootstraps)?;
+
+        let access = self.options.access.as_ref();
+        if let Some(access) = access {
+            class.access = access.apply_class(&name, class.access);
+        }
+
+        for field in &mut class.fields {
+            let field_name = original.utf8(field.name)?;
+            let descriptor = original.utf8(field.descriptor)?;
+            if let Some(access) = access {
+                field.access = access.apply_field(&name, field_name, field.access);
+            }
+            if let Some(mapped) = self
+                .mappings
+                .field(&name, field_name)
+            {
+                field.name = class.pool.add_utf8(mapped)?;
+            }
+            let mapped = self
+                .mappings
+                .map_signature(descriptor);
+            field.descriptor =
+                Self::replace_utf8(&mut class.pool, &original, field.descriptor, &mapped)?;
+            self.remap_attributes(&name, &mut field.attributes, &original, &mut class.pool)?;
+        }
+
+        for method in &mut class.methods {
+            let method_name = original.utf8(method.name)?;
+            let descriptor = original.utf8(method.descriptor)?;
+            if let Some(access) = access {
+                method.access = access.apply_method(&name, method_name, descriptor, method.access);
+            }
+            let mapped = if method.access & (ACC_PRIVATE | ACC_STATIC) != 0 {
+                self.mappings
+                    .method(&name, method_name, descriptor)
+            } else {
+                self.map_method(&name, method_name, descriptor)
+            };
+            if let Some(mapped) = mapped {
+                method.name = class.pool.add_utf8(mapped)?;
+            }
+            let mapped = self
+                .mappings
+                .map_signature(descriptor);
+            method.descriptor =
+                Self::replace_utf8(&mut class.pool, &original, method.descriptor, &mapped)?;
+            self.remap_attributes(&name, &mut method.attributes, &original, &mut class.pool)?;
+            if self.options.auto_lvt == Some(AutoLvt::Basic) {
+                let is_static = method.access & ACC_STATIC != 0;
+                name_locals(&mut method.attributes, &mut class.pool, is_static)?;
+            }
+        }
+
+        self.remap_attributes(&name, &mut class.attributes, &original, &mut class.pool)?;
+
+        let mapped_name = class
+            .pool
+            .class_name(class.this_class)?
+            .to_string();
+        Ok((mapped_name, class.to_bytes()))
+    }
+
+    /// Remaps the class, member reference and descriptor entries of the
+    /// pool. Shared UTF-8 entries are never modified instead new entries
+    /// are added for the mapped values
+    fn remap_pool(
+        &self,
+        original: &ConstantPool,
+        pool: &mut ConstantPool,
+        bootstraps: &[BootstrapMethod],
+    ) -> ClassFileResult<()> {
+        for index in 1..original.slots() {
+            let Ok(constant) = original.get(index) else {
+                continue;
+            };
+            match constant {
+                Constant::Class(name) => {
+                    let value = original.utf8(*name)?;
+                    let mapped = if value.starts_with('[') {
+                        self.mappings
+                            .map_signature(value)
+                    } else {
+                        self.mappings.class(value)
+                    };
+                    if mapped != value {
+                        let name = pool.add_utf8(&mapped)?;
+                        pool.set(index, Constant::Class(name))?;
+                    }
+                }
+                Constant::FieldRef {
+                    class,
+                    name_and_type,
+                } => {
+                    let owner = original.class_name(*class)?;
+                    let (name, descriptor) = original.name_and_type(*name_and_type)?;
+                    let mapped = self
+                        .map_field(owner, name)
+                        .unwrap_or(name);
+                    if let Some(name_and_type) =
+                        self.remap_name_and_type(pool, name, mapped, descriptor)?
+                    {
+                        pool.set(
+                            index,
+                            Constant::FieldRef {
+                                class: *class,
+                                name_and_type,
+                            },
+                        )?;
+                    }
+                }
+                Constant::MethodRef {
+                    class,
+                    name_and_type,
+                }
+                | Constant::InterfaceMethodRef {
+                    class,
+                    name_and_type,
+                } => {
+                    let owner = original.class_name(*class)?;
+                    let (name, descriptor) = original.name_and_type(*name_and_type)?;
+                    let mapped = if owner.starts_with('[') {
+                        name
+                    } else {
+                        self.map_method(owner, name, descriptor)
+                            .unwrap_or(name)
+                    };
+                    if let Some(name_and_type) =
+                        self.remap_name_and_type(pool, name, mapped, descriptor)?
+                    {
+                        let (class, name_and_type) = (*class, name_and_type);
+                        let constant = match constant {
+                            Constant::MethodRef { .. } => Constant::MethodRef {
+                                class,
+                                name_and_type,
+                            },
+                            _ => Constant::InterfaceMethodRef {
+                                class,
+                                name_and_type,
+                            },
+                        };
+                        pool.set(index, constant)?;
+                    }
+                }
+                Constant::MethodType(descriptor) => {
+                    let value = original.utf8(*descriptor)?;
+                    let mapped = self
+                        .mappings
+                        .map_signature(value);
+                    if mapped != value {
+                        let descriptor = pool.add_utf8(&mapped)?;
+                        pool.set(index, Constant::MethodType(descriptor))?;
+                    }
+                }
+                Constant::Dynamic {
+                    bootstrap,
+                    name_and_type,
+                }
+                | Constant::InvokeDynamic {
+                    bootstrap,
+                    name_and_type,
+                } => {
+                    let (name, descriptor) = original.name_and_type(*name_and_type)?;
+                    // Lambda call sites are named after the method of the
+                    // functional interface they implement
+                    let mapped =
+                        match Self::lambda_method(original, bootstraps, *bootstrap, descriptor)? {
+                            Some((interface, method_descriptor)) => self
+                                .map_method(interface, name, method_descriptor)
+                                .unwrap_or(name),
+                            None => name,
+                        };
+                    if let Some(name_and_type) =
+                        self.remap_name_and_type(pool, name, mapped, descriptor)?
+                    {
+                        let (bootstrap, name_and_type) = (*bootstrap, name_and_type);
+                        let constant = match constant {
+                            Constant::Dynamic { .. } => Constant::Dynamic {
+                                bootstrap,
+                                name_and_type,
+                            },
+                            _ => Constant::InvokeDynamic {
+                                bootstrap,
+                                name_and_type,
+                            },
+                        };
+                        pool.set(index, constant)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the functional interface and the descriptor of its method
+    /// for call sites bootstrapped by the LambdaMetafactory. Returns
+    /// None for other bootstrap methods
+    fn lambda_method<'b>(
+        original: &'b ConstantPool,
+        bootstraps: &[BootstrapMethod],
+        bootstrap: u16,
+        descriptor: &'b str,
+    ) -> ClassFileResult<Option<(&'b str, &'b str)>> {
+        let Some(bootstrap) = bootstraps.get(bootstrap as usize) else {
+            return Ok(None);
+        };
+        let Constant::MethodHandle { reference, .. } = original.get(bootstrap.method)? else {
+            return Ok(None);
+        };
+        let owner = match original.get(*reference)? {
+            Constant::MethodRef { class, .. } | Constant::InterfaceMethodRef { class, .. } => {
+                original.class_name(*class)?
+            }
+            _ => return Ok(None),
+        };
+        if owner != LAMBDA_METAFACTORY {
+            return Ok(None);
+        }
+        let interface = descriptor
+            .rsplit_once(')')
+            .and_then(|(_, value)| value.strip_prefix('L'))
+            .and_then(|value| value.strip_suffix(';'));
+        let method_type = bootstrap
+            .arguments
+            .first()
+            .map(|argument| original.get(*argument))
+            .transpose()?;
+        match (interface, method_type) {
+            (Some(interface), Some(Constant::MethodType(method_descriptor))) => {
+                Ok(Some((interface, original.utf8(*method_descriptor)?)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Creates a name and type entry for the mapped name and descriptor
+    /// returning None when neither of them changed
+    fn remap_name_and_type(
+        &self,
+        pool: &mut ConstantPool,
+        name: &str,
+        mapped: &str,
+        descriptor: &str,
+    ) -> ClassFileResult<Option<u16>> {
+        let mapped_descriptor = self
+            .mappings
+            .map_signature(descriptor);
+        if name == mapped && descriptor == mapped_descriptor {
+            return Ok(None);
+        }
+        pool.add_name_and_type(mapped, &mapped_descriptor)
+            .map(Some)
+    }
+
+    /// Remaps the descriptors, signatures and names within the provided
+    /// attributes. `class` is the input name of the class being remapped
+    fn remap_attributes(
+        &self,
+        class: &str,
+        attributes: &mut Vec<Attribute>,
+        original: &ConstantPool,
+        pool: &mut ConstantPool,
+    ) -> ClassFileResult<()> {
+        if self.options.kill_lvt {
+            attributes.retain(|attribute| {
+                !matches!(
+                    original.utf8(attribute.name),
+                    Ok("LocalVariableTable" | "LocalVariableTypeTable")
+                )
+            });
+        }
+        for attribute in attributes.iter_mut() {
+            match original.utf8(attribute.name)? {
+                "Signature" => {
+                    let index = ClassReader::new(&attribute.data).u16()?;
+                    let mapped = self
+                        .mappings
+                        .map_signature(original.utf8(index)?);
+                    let index = Self::replace_utf8(pool, original, index, &mapped)?;
+                    attribute.data = index.to_be_bytes().to_vec();
+                }
+                "Code" => {
+                    let mut code = CodeAttribute::parse(&attribute.data)?;
+                    self.remap_attributes(class, &mut code.attributes, original, pool)?;
+                    attribute.data = code.to_bytes();
+                }
+                "LocalVariableTable" | "LocalVariableTypeTable" => {
+                    // Entries are start, length, name, descriptor and index
+                    let mut reader = ClassReader::new(&attribute.data);
+                    let count = reader.u16()?;
+                    let mut data = Vec::with_capacity(attribute.data.len());
+                    write_u16(&mut data, count);
+                    for _ in 0..count {
+                        data.extend_from_slice(reader.bytes(6)?);
+                        let index = reader.u16()?;
+                        let mapped = self
+                            .mappings
+                            .map_signature(original.utf8(index)?);
+                        write_u16(
+                            &mut data,
+                            Self::replace_utf8(pool, original, index, &mapped)?,
+                        );
+                        data.extend_from_slice(reader.bytes(2)?);
+                    }
+                    attribute.data = data;
+                }
+                "InnerClasses" => {
+                    // Entries are inner class, outer class, inner name and access
+                    let mut reader = ClassReader::new(&attribute.data);
+                    let count = reader.u16()?;
+                    let mut data = Vec::with_capacity(attribute.data.len());
+                    write_u16(&mut data, count);
+                    for _ in 0..count {
+                        let inner_class = reader.u16()?;
+                        let outer_class = reader.u16()?;
+                        let mut inner_name = reader.u16()?;
+                        let flags = reader.u16()?;
+                        if inner_name != 0 {
+                            let mapped = pool.class_name(inner_class)?;
+                            let simple = match mapped.rsplit_once('$') {
+                                Some((_, simple)) => simple,
+                                None => mapped
+                                    .rsplit('/')
+                                    .next()
+                                    .unwrap_or(mapped),
+                            }
+                            .to_string();
+                            inner_name = Self::replace_utf8(pool, original, inner_name, &simple)?;
+                        }
+                        write_u16(&mut data, inner_class);
+                        write_u16(&mut data, outer_class);
+                        write_u16(&mut data, inner_name);
+                        write_u16(&mut data, flags);
+                    }
+                    attribute.data = data;
+                }
+                "EnclosingMethod" => {
+                    let mut reader = ClassReader::new(&attribute.data);
+                    let outer_class = reader.u16()?;
+                    let method = reader.u16()?;
+                    if method != 0 {
+                        let owner = original.class_name(outer_class)?;
+                        let (name, descriptor) = original.name_and_type(method)?;
+                        let mapped = self
+                            .map_method(owner, name, descriptor)
+                            .unwrap_or(name);
+                        if let Some(method) =
+                            self.remap_name_and_type(pool, name, mapped, descriptor)?
+                        {
+                            let mut data = Vec::with_capacity(4);
+                            write_u16(&mut data, outer_class);
+                            write_u16(&mut data, method);
+                            attribute.data = data;
+                        }
+                    }
+                }
+                "Record" => {
+                    let mut reader = ClassReader::new(&attribute.data);
+                    let count = reader.u16()?;
+                    let mut data = Vec::with_capacity(attribute.data.len());
+                    write_u16(&mut data, count);
+                    for _ in 0..count {
+                        let mut name = reader.u16()?;
+                        let mut descriptor = reader.u16()?;
+                        let mut attributes = Attribute::read_all(&mut reader)?;
+                        if let Some(mapped) = self
+                            .mappings
+                            .field(class, original.utf8(name)?)
+                        {
+                            name = pool.add_utf8(mapped)?;
+                        }
+                        let mapped = self
+                            .mappings
+                            .map_signature(original.utf8(descriptor)?);
+                        descriptor = Self::replace_utf8(pool, original, descriptor, &mapped)?;
+                        self.remap_attributes(class, &mut attributes, original, pool)?;
+                        write_u16(&mut data, name);
+                        write_u16(&mut data, descriptor);
+                        Attribute::write_all(&attributes, &mut data);
+                    }
+                    attribute.data = data;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Renames the local variables within the Code attribute of a method
+/// after their types. Instance methods keep `this` for their first slot
+fn name_locals(
+    attributes: &mut [Attribute],
+    pool: &mut ConstantPool,
+    is_static: bool,
+) -> ClassFileResult<()> {
+    for attribute in attributes.iter_mut() {
+        if pool.utf8(attribute.name)? != "Code" {
+            continue;
+        }
+        let mut code = CodeAttribute::parse(&attribute.data)?;
+
+        // Start, length and slot of each variable -> name index
+        let mut names = HashMap::new();
+        let mut counts = HashMap::new();
+        for table in code.attributes.iter_mut() {
+            if pool.utf8(table.name)? != "LocalVariableTable" {
+                continue;
+            }
+            let mut entries = read_local_variables(&table.data)?;
+            // Parameters are named first followed by the other variables
+            // in the order their scopes begin
+            let mut order = (0..entries.len()).collect::<Vec<usize>>();
+            order.sort_by_key(|index| (entries[*index][0], entries[*index][4]));
+            for index in order {
+                let [start, length, _, descriptor, slot] = entries[index];
+                let name = if !is_static && slot == 0 {
+                    "this".to_string()
+                } else {
+                    basic_local_name(pool.utf8(descriptor)?, &mut counts)
+                };
+                let name = pool.add_utf8(&name)?;
+                entries[index][2] = name;
+                names.insert((start, length, slot), name);
+            }
+            table.data = write_local_variables(&entries);
+        }
+
+        // Generic types of the variables use the same names
+        for table in code.attributes.iter_mut() {
+            if pool.utf8(table.name)? != "LocalVariableTypeTable" {
+                continue;
+            }
+            let mut entries = read_local_variables(&table.data)?;
+            for entry in &mut entries {
+                if let Some(name) = names.get(&(entry[0], entry[1], entry[4])) {
+                    entry[2] = *name;
+                }
+            }
+            table.data = write_local_variables(&entries);
+        }
+        attribute.data = code.to_bytes();
+    }
+    Ok(())
+}
+
+/// Reads the start, length, name, descriptor and slot of each
+/// local variable table entry
+fn read_local_variables(data: &[u8]) -> ClassFileResult<Vec<[u16; 5]>> {
+    let mut reader = ClassReader::new(data);
+    let count = reader.u16()?;
+    (0..count)
+        .map(|_| {
+            Ok([
+                reader.u16()?,
+                reader.u16()?,
+                reader.u16()?,
+                reader.u16()?,
+                reader.u16()?,
+            ])
+        })
+        .collect()
+}
+
+fn write_local_variables(entries: &[[u16; 5]]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(2 + entries.len() * 10);
+    write_u16(&mut data, entries.len() as u16);
+    for value in entries.iter().flatten() {
+        write_u16(&mut data, *value);
+    }
+    data
+}
+
+/// Creates a name for a local variable of the provided type. Integers
+/// cycle through `i`, `j`, `k` and `l`, booleans are named `flag`,
+/// arrays are prefixed with `a` and classes use their lowercase simple
+/// name. Repeated names are numbered using `counts`
+fn basic_local_name(descriptor: &str, counts: &mut HashMap<String, usize>) -> String {
+    let dimensions = descriptor
+        .chars()
+        .take_while(|c| *c == '[')
+        .count();
+    let element = &descriptor[dimensions..];
+    let type_name = match element {
+        "Z" => "boolean".to_string(),
+        "B" => "byte".to_string(),
+        "C" => "char".to_string(),
+        "S" => "short".to_string(),
+        "I" => "int".to_string(),
+        "J" => "long".to_string(),
+        "F" => "float".to_string(),
+        "D" => "double".to_string(),
+        _ => element
+            .trim_start_matches('L')
+            .trim_end_matches(';')
+            .rsplit('/')
+            .next()
+            .unwrap_or(element)
+            .replace('$', "_")
+            .to_lowercase(),
+    };
+
+    let (base, numbered) = if dimensions > 0 {
+        (format!("a{type_name}"), false)
+    } else {
+        match type_name.as_str() {
+            "int" | "long" => {
+                let count = counts
+                    .entry("int".to_string())
+                    .or_default();
+                let letter = ["i", "j", "k", "l"][*count % 4];
+                let round = *count / 4;
+                *count += 1;
+                return if round == 0 {
+                    letter.to_string()
+                } else {
+                    format!("{letter}{round}")
+                };
+            }
+            "boolean" => ("flag".to_string(), false),
+            "float" => ("f".to_string(), false),
+            "string" => ("s".to_string(), false),
+            "double" => ("d".to_string(), true),
+            "byte" => ("b".to_string(), true),
+            "char" => ("c".to_string(), true),
+            name => (name.to_string(), RESERVED_NAMES.contains(&name)),
+        }
+    };
+    let count = counts
+        .entry(base.clone())
+        .or_default();
+    let name = if numbered || *count > 0 {
+        format!("{base}{count}")
+    } else {
+        base
+    };
+    *count += 1;
+    name
+}
+
+/// Names the constants of an enum after the name passed to their
+/// constructor within the static initializer
+fn enum_tokens(
+    class: &ClassFile,
+    name: &str,
+    mappings: &Mappings,
+) -> ClassFileResult<Vec<GeneratedName>> {
+    let pool = &class.pool;
+    let enum_descriptor = format!("L{name};");
+    let mut generated = Vec::new();
+    for method in &class.methods {
+        if pool.utf8(method.name)? != "<clinit>" {
+            continue;
+        }
+        let Some(code) = method_code(pool, method)? else {
+            continue;
+        };
+        // The first string loaded after each `new` is the constant name
+        let mut token = None;
+        let mut expecting = false;
+        for instruction in instructions(&code.code)? {
+            match (instruction.opcode, instruction.constant()) {
+                // new
+                (0xbb, _) => {
+                    expecting = true;
+                    token = None;
+                }
+                // ldc and ldc_w
+                (0x12 | 0x13, Some(index)) if expecting => {
+                    if let Constant::String(value) = pool.get(index)? {
+                        token = Some(pool.utf8(*value)?);
+                        expecting = false;
+                    }
+                }
+                // putstatic
+                (0xb3, Some(index)) => {
+                    expecting = false;
+                    let Some(token) = token.take() else {
+                        continue;
+                    };
+                    let Constant::FieldRef {
+                        class: owner,
+                        name_and_type,
+                    } = pool.get(index)?
+                    else {
+                        continue;
+                    };
+                    let (field, descriptor) = pool.name_and_type(*name_and_type)?;
+                    if pool.class_name(*owner)? == name
+                        && descriptor == enum_descriptor
+                        && field != token
+                        && is_identifier(token)
+                        && mappings
+                            .field(name, field)
+                            .is_none()
+                    {
+                        generated.push(GeneratedName {
+                            class: name.to_string(),
+                            name: field.to_string(),
+                            descriptor: None,
+                            mapped: token.to_string(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(generated)
+}
+
+/// Finds the method of the class called by the provided bridge method
+fn bridge_target<'a>(
+    class: &'a ClassFile,
+    name: &str,
+    method: &Member,
+) -> ClassFileResult<Option<(&'a str, &'a str)>> {
+    let pool = &class.pool;
+    let Some(code) = method_code(pool, method)? else {
+        return Ok(None);
+    };
+    for instruction in instructions(&code.code)? {
+        // invokevirtual, invokespecial and invokeinterface
+        if !matches!(instruction.opcode, 0xb6 | 0xb7 | 0xb9) {
+            continue;
+        }
+        let Some(index) = instruction.constant() else {
+            continue;
+        };
+        if let Constant::MethodRef {
+            class: owner,
+            name_and_type,
+        }
+        | Constant::InterfaceMethodRef {
+            class: owner,
+            name_and_type,
+        } = pool.get(index)?
+        {
+            if pool.class_name(*owner)? == name {
+                return pool
+                    .name_and_type(*name_and_type)
+                    .map(Some);
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Parses the Code attribute of the provided method
+fn method_code(pool: &ConstantPool, method: &Member) -> ClassFileResult<Option<CodeAttribute>> {
+    for attribute in &method.attributes {
+        if pool.utf8(attribute.name)? == "Code" {
+            return CodeAttribute::parse(&attribute.data).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Whether the value can be used as a Java identifier
+fn is_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Entry of the BootstrapMethods attribute
+struct BootstrapMethod {
+    /// Index of the method handle for the bootstrap method
+    method: u16,
+    /// Indices of the static arguments
+    arguments: Vec<u16>,
+}
+
+/// Reads the BootstrapMethods attribute from the provided class attributes
+fn bootstrap_methods(
+    attributes: &[Attribute],
+    pool: &ConstantPool,
+) -> ClassFileResult<Vec<BootstrapMethod>> {
+    let mut methods = Vec::new();
+    for attribute in attributes {
+        if pool.utf8(attribute.name)? != "BootstrapMethods" {
+            continue;
+        }
+        let mut reader = ClassReader::new(&attribute.data);
+        let count = reader.u16()?;
+        for _ in 0..count {
+            let method = reader.u16()?;
+            let argument_count = reader.u16()?;
+            let arguments = (0..argument_count)
+                .map(|_| reader.u16())
+                .collect::<ClassFileResult<Vec<u16>>>()?;
+            methods.push(BootstrapMethod { method, arguments });
+        }
+    }
+    Ok(methods)
+}
+
+/// Remaps the classes of the jar at `input` using the provided mappings
+/// writing the remapped jar to `output`. Classes are moved to the entry
+/// for their mapped name and all other entries are copied unchanged.
+/// Annotation values are not remapped
+pub async fn remap_jar(
+    input: &Path,
+    output: &Path,
+    mappings: Mappings,
+    options: RemapOptions,
+) -> RemapResult<()> {
+    let entries = read_files(input, |name| name.ends_with(".class")).await?;
+    debug!("Remapping {} classes from {input:?}", entries.len());
+
+    let mut mappings = mappings;
+    mappings.exclude(&options.excluded);
+
+    // Remapping is CPU bound so is kept off the async workers
+    let mut remapped = spawn_blocking(move || remap_classes(entries, mappings, &options)).await??;
+
+    rewrite_zip(
+        input,
+        output,
+        ZipWriteMode::deterministic(),
+        |name| match remapped.remove(name) {
+            Some((mapped, contents)) => EntryAction::Rename(mapped, contents),
+            None => EntryAction::Copy,
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// Remaps the provided class entries returning a map from the original
+/// entry names to the mapped entry names and contents. Classes which
+/// aren't included by the options are left out
+fn remap_classes(
+    entries: Vec<(String, Vec<u8>)>,
+    mut mappings: Mappings,
+    options: &RemapOptions,
+) -> RemapResult<HashMap<String, (String, Vec<u8>)>> {
+    let mut classes = Vec::with_capacity(entries.len());
+    let mut parents = HashMap::with_capacity(entries.len());
+    for (entry, contents) in entries {
+        let class = ClassFile::parse(&contents)
+            .and_then(|class| {
+                let name = class.name()?.to_string();
+                let mut class_parents = Vec::new();
+                if let Some(super_name) = class.super_name()? {
+                    class_parents.push(super_name.to_string());
+                }
+                for interface in class.interface_names()? {
+                    class_parents.push(interface.to_string());
+                }
+                parents.insert(name, class_parents);
+                Ok(class)
+            })
+            .map_err(|err| RemapError::Class(entry.clone(), err))?;
+        classes.push((entry, class));
+    }
+
+    if !options
+        .auto_members
+        .is_empty()
+    {
+        let remapper = Remapper {
+            mappings: &mappings,
+            options,
+            parents,
+        };
+        let mut generated = Vec::new();
+        for (entry, class) in &classes {
+            generated.extend(
+                remapper
+                    .generate_members(class)
+                    .map_err(|err| RemapError::Class(entry.clone(), err))?,
+            );
+        }
+        parents = remapper.parents;
+        for generated in generated {
+            mappings.add_generated(generated);
+        }
+    }
+
+    let remapper = Remapper {
+        mappings: &mappings,
+        options,
+        parents,
+    };
+
+    let mut remapped = HashMap::with_capacity(classes.len());
+    for (entry, class) in classes {
+        let name = class
+            .name()
+            .map_err(|err| RemapError::Class(entry.clone(), err))?
+            .to_string();
+        if !options.is_included(&name) {
+            continue;
+        }
+        let (mapped_name, contents) = remapper
+            .remap_class(class)
+            .map_err(|err| RemapError::Class(entry.clone(), err))?;
+        // Classes stored under a prefix (e.g. multi-release
+        // versions) keep their prefix
+        let mapped_entry = match entry.strip_suffix(&format!("{name}.class")) {
+            Some(prefix) => format!("{prefix}{mapped_name}.class"),
+            None => entry.clone(),
+        };
+        remapped.insert(entry, (mapped_entry, contents));
+    }
+    Ok(remapped)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::build_tools::class_file::{ClassFile, ClassReader, CodeAttribute, Constant, Member};
+    use crate::build_tools::remapper::{remap_jar, AccessTransformer, Mappings, RemapOptions};
+    use crate::utils::testing::test_dir;
+    use crate::utils::zip::read_files;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    /// Finds the member with the provided name
+    fn find_member<'a>(class: &ClassFile, members: &'a [Member], name: &str) -> &'a Member {
+        members
+            .iter()
+            .find(|member| {
+                class
+                    .pool
+                    .utf8(member.name)
+                    .unwrap()
+                    == name
+            })
+            .unwrap_or_else(|| panic!("Missing member {name}"))
+    }
+
+    /// Collects the names of the field or method references and
+    /// call sites in the pool
+    fn reference_names(class: &ClassFile, fields: bool) -> Vec<&str> {
+        (1..class.pool.slots())
+            .filter_map(|index| match class.pool.get(index) {
+                Ok(Constant::FieldRef { name_and_type, .. }) if fields => Some(*name_and_type),
+                Ok(
+                    Constant::MethodRef { name_and_type, .. }
+                    | Constant::InvokeDynamic { name_and_type, .. },
+                ) if !fields => Some(*name_and_type),
+                _ => None,
+            })
+            .map(|index| {
+                class
+                    .pool
+                    .name_and_type(index)
+                    .unwrap()
+                    .0
+            })
+            .collect()
+    }
+
+    /// Tests mapping the class names in descriptors and generic
+    /// signatures including nested and package mapped classes
+    #[test]
+    fn test_map_signature() {
+        let mappings =
+            Mappings::parse("a net/test/Base\nb$c net/test/Other$Inner\n./ net/test/\n").unwrap();
+        assert_eq!(mappings.class("a$d"), "net/test/Base$d");
+        assert_eq!(mappings.class("e"), "net/test/e");
+        assert_eq!(mappings.class("java/lang/Object"), "java/lang/Object");
+        assert_eq!(
+            mappings.map_signature("(La;[La$d;I)Le;"),
+            "(Lnet/test/Base;[Lnet/test/Base$d;I)Lnet/test/e;"
+        );
+        assert_eq!(
+            mappings.map_signature(
+                "<T:La;U::Ljava/util/List<TT;>;>La$d<TT;>;Ljava/util/function/Supplier<+Lb<*>.c;>;"
+            ),
+            "<T:Lnet/test/Base;U::Ljava/util/List<TT;>;>Lnet/test/Base$d<TT;>;\
+            Ljava/util/function/Supplier<+Lnet/test/b<*>.Inner;>;"
+        );
+        assert_eq!(mappings.map_signature("(La"), "(La");
+        assert!(Mappings::parse("a b c d e").is_err());
+        assert!(AccessTransformer::parse("visible a").is_err());
+    }
+
+    /// Tests applying the class, member and final mappings to
+    /// the fixture jar in test/remapper (built from its sources)
+    #[tokio::test]
+    async fn test_remap_jar() {
+        let root = Path::new("test/remapper");
+        let out = test_dir("remapper");
+
+        // Commands in the form BuildData provides them
+        let mut input = root.join("input.jar");
+        for (mappings, command, output) in [
+            (
+                "classes.csrg",
+                "java -jar SpecialSource-2.jar map --only . --only net/test --auto-lvt BASIC \
+                -e test/remapper/excluded.txt -i {0} -m {1} -o {2}",
+                "classes.jar",
+            ),
+            (
+                "members.csrg",
+                "java -jar SpecialSource-2.jar map --only . --only net/test --auto-member LOGGER \
+                --auto-member TOKENS --auto-member SYNTHETIC -i {0} -m {1} -o {2}",
+                "members.jar",
+            ),
+            (
+                "package.csrg",
+                "java -jar SpecialSource.jar --kill-lvt --only . --only net/test -i {0} \
+                --access-transformer test/remapper/access.at -m {2} -o {3} --unknown",
+                "mapped.jar",
+            ),
+        ] {
+            let command = command
+                .split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<String>>();
+            let options = RemapOptions::from_special_source(&command)
+                .await
+                .unwrap();
+            let mappings = Mappings::load(root.join(mappings))
+                .await
+                .unwrap();
+            let output = out.join(output);
+            remap_jar(&input, &output, mappings, options)
+                .await
+                .unwrap();
+            input = output;
+        }
+        assert!(RemapOptions::from_special_source(&["java".to_string()])
+            .await
+            .is_err());
+        assert!(RemapOptions::from_special_source(&[
+            "SpecialSource".to_string(),
+            "--only".to_string()
+        ])
+        .await
+        .is_err());
+
+        // Local variables are named after their types
+        let named = read_files(out.join("classes.jar"), |name| {
+            name == "net/test/Named.class"
+        })
+        .await
+        .unwrap();
+        let named = ClassFile::parse(&named[0].1).unwrap();
+        let method = find_member(&named, &named.methods, "k");
+        let code = method
+            .attributes
+            .iter()
+            .find(|attribute| {
+                named
+                    .pool
+                    .utf8(attribute.name)
+                    .unwrap()
+                    == "Code"
+            })
+            .map(|attribute| CodeAttribute::parse(&attribute.data).unwrap())
+            .unwrap();
+        let table = code
+            .attributes
+            .iter()
+            .find(|attribute| {
+                named
+                    .pool
+                    .utf8(attribute.name)
+                    .unwrap()
+                    == "LocalVariableTable"
+            })
+            .unwrap();
+        let mut reader = ClassReader::new(&table.data);
+        let locals = (0..reader.u16().unwrap())
+            .map(|_| {
+                let name = reader.bytes(10).unwrap()[4..6].to_vec();
+                named
+                    .pool
+                    .utf8(u16::from_be_bytes([name[0], name[1]]))
+                    .unwrap()
+            })
+            .collect::<Vec<&str>>();
+        assert_eq!(locals, ["this", "i", "flag"]);
+
+        let files = read_files(&input, |_| true)
+            .await
+            .unwrap();
+        let mut names = files
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<&str>>();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "META-INF/MANIFEST.MF",
+                "j.class",
+                "net/test/v1/Base$d.class",
+                "net/test/v1/Base.class",
+                "net/test/v1/Child.class",
+                "net/test/v1/Getter.class",
+                "net/test/v1/Kind.class",
+                "net/test/v1/Named.class",
+                "net/test/v1/Pair.class",
+                "org/apache/logging/log4j/Logger.class",
+            ]
+        );
+        let classes = files
+            .iter()
+            .filter(|(name, _)| name.ends_with(".class"))
+            .map(|(_, contents)| {
+                let class = ClassFile::parse(contents).unwrap();
+                (
+                    class
+                        .name()
+                        .unwrap()
+                        .to_string(),
+                    class,
+                )
+            })
+            .collect::<HashMap<String, ClassFile>>();
+
+        // Access changes and member declarations
+        let base = &classes["net/test/v1/Base"];
+        assert_eq!(find_member(base, &base.fields, "value").access & 0x7, 0x4);
+        assert_eq!(
+            find_member(base, &base.methods, "compute").access & 0x7,
+            0x1
+        );
+        let nested = &classes["net/test/v1/Base$d"];
+        let element = find_member(nested, &nested.fields, "element");
+        assert_eq!(
+            nested
+                .pool
+                .utf8(element.descriptor)
+                .unwrap(),
+            "Lnet/test/v1/Base;"
+        );
+        let pair = &classes["net/test/v1/Pair"];
+        assert_eq!(find_member(pair, &pair.fields, "base").access & 0x17, 0x1);
+        find_member(pair, &pair.methods, "base");
+
+        // Inherited references, overrides and signatures
+        let child = &classes["net/test/v1/Child"];
+        assert_eq!(child.super_name().unwrap(), Some("net/test/v1/Base"));
+        find_member(child, &child.methods, "compute");
+        let fields = reference_names(child, true);
+        assert!(fields.contains(&"value") && !fields.contains(&"b"));
+        let methods = reference_names(child, false);
+        assert!(methods.contains(&"compute") && !methods.contains(&"c"));
+        assert!(methods.contains(&"base"));
+        // Lambdas and method references implementing a mapped interface
+        assert!(methods.contains(&"apply") && !methods.contains(&"g"));
+        let getter = &classes["net/test/v1/Getter"];
+        find_member(getter, &getter.methods, "apply");
+
+        // Generated member names and excluded classes
+        let kind = &classes["net/test/v1/Kind"];
+        find_member(kind, &kind.fields, "FIRST");
+        find_member(kind, &kind.fields, "LOGGER");
+        let named = &classes["net/test/v1/Named"];
+        assert_eq!(named.super_name().unwrap(), Some("j"));
+        let names = named
+            .methods
+            .iter()
+            .map(|method| {
+                named
+                    .pool
+                    .utf8(method.name)
+                    .unwrap()
+            })
+            .collect::<Vec<&str>>();
+        assert_eq!(names, ["<init>", "name", "name"]);
+        find_member(&classes["j"], &classes["j"].methods, "k");
+
+        let mut signature = None;
+        for attribute in &child.attributes {
+            if child
+                .pool
+                .utf8(attribute.name)
+                .unwrap()
+                == "Signature"
+            {
+                let index = ClassReader::new(&attribute.data)
+                    .u16()
+                    .unwrap();
+                signature = Some(
+                    child
+                        .pool
+                        .utf8(index)
+                        .unwrap(),
+                );
+            }
+        }
+        assert_eq!(
+            signature,
+            Some(
+                "Lnet/test/v1/Base;Ljava/util/function/Supplier\
+                <Lnet/test/v1/Base$d<Lnet/test/v1/Child;>;>;"
+            )
+        );
+
+        // Local variable tables are removed by the final mappings
+        for class in classes.values() {
+            for method in &class.methods {
+                for attribute in &method.attributes {
+                    if class
+                        .pool
+                        .utf8(attribute.name)
+                        .unwrap()
+                        == "Code"
+                    {
+                        let code = CodeAttribute::parse(&attribute.data).unwrap();
+                        assert!(code
+                            .attributes
+                            .iter()
+                            .all(|attribute| class
+                                .pool
+                                .utf8(attribute.name)
+                                .unwrap()
+                                != "LocalVariableTable"));
+                    }
+                }
+            }
+        }
+    }
+}
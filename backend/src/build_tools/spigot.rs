@@ -1,13 +1,25 @@
-use crate::utils::constants::SPIGOT_VERSIONS_URL;
+use crate::models::build_tools::BuildDataInfo;
+use crate::utils::archive::{metadata_archive, ArchiveEntry, ArchiveError, ArchiveKind};
+use crate::utils::endpoints::endpoints;
+use crate::utils::files::{ensure_is_file, ensure_parent_exists, write_atomic};
+use crate::utils::http_cache::{http_cache, HttpCacheError};
 use crate::utils::net::create_reqwest;
+use crate::utils::versions::{MinecraftVersion, ReleaseStage, VersionKind};
+use futures::{stream, StreamExt};
+use log::{debug, warn};
 use regex::Regex;
 use reqwest::StatusCode;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::io;
 use std::path::Path;
 use thiserror::Error;
 use tokio::fs::{read, write};
 
+/// The maximum number of version JSONs fetched at once when
+/// building the version index
+const INDEX_CONCURRENCY: usize = 8;
+
 /// Structure for version details response from
 /// https://hub.spigotmc.org/versions/{VERSION}.json
 #[derive(Debug, Deserialize)]
@@ -27,7 +39,7 @@ pub struct SpigotVersion {
 
 /// git refs for the different parts of the server
 /// required to build
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct VersionRefs {
     pub build_data: String,
@@ -48,23 +60,83 @@ pub enum SpigotError {
     IO(#[from] io::Error),
     #[error(transparent)]
     SerdeError(#[from] serde_json::Error),
+    #[error(transparent)]
+    Cache(#[from] HttpCacheError),
+    #[error(transparent)]
+    Archive(#[from] ArchiveError),
+    #[error("Unable to determine the Minecraft version of spigot version \"{0}\"")]
+    UnresolvedVersion(String),
 }
 
 type SpigotResult<T> = Result<T, SpigotError>;
 
-/// Retrieves a spigot version JSON from `SPIGOT_VERSION_URL` and parses it
-/// returning the result or a SpigotError
+/// Retrieves a spigot version JSON from the spigot versions url and parses it
+/// returning the result or a SpigotError. Changed JSONs are archived in the
+/// metadata archive
 pub async fn get_version(version: &str) -> SpigotResult<SpigotVersion> {
-    let client = create_reqwest()?;
-    let url = format!("{}{}.json", SPIGOT_VERSIONS_URL, version);
-    let response = client.get(url).send().await?;
-    if response.status() == StatusCode::NOT_FOUND {
-        return Err(SpigotError::UnknownVersion(version.to_string()));
+    let url = format!("{}{}.json", endpoints().spigot_versions_url, version);
+    let contents = match http_cache().get(&url).await {
+        Err(HttpCacheError::Status(_, StatusCode::NOT_FOUND)) => {
+            return Err(SpigotError::UnknownVersion(version.to_string()))
+        }
+        result => result?,
+    };
+    let parsed = serde_json::from_slice::<SpigotVersion>(&contents)?;
+    metadata_archive()
+        .archive(ArchiveKind::SpigotVersion, version, &url, &contents)
+        .await?;
+    Ok(parsed)
+}
+
+/// Retrieves the BuildData info.json at the BuildData ref of the provided
+/// refs. The info is used to find the Minecraft version of builds which
+/// no Minecraft version JSON points at
+pub async fn get_build_data_info(refs: &VersionRefs) -> SpigotResult<BuildDataInfo> {
+    let url = format!(
+        "{}info.json?at={}",
+        endpoints().build_data_raw_url,
+        refs.build_data
+    );
+    let contents = http_cache().get(&url).await?;
+    let parsed = serde_json::from_slice::<BuildDataInfo>(&contents)?;
+    Ok(parsed)
+}
+
+/// Loads every archived version JSON for the provided version name
+/// ordered from oldest to newest
+pub async fn get_archived_versions(
+    version: &str,
+) -> SpigotResult<Vec<(ArchiveEntry, SpigotVersion)>> {
+    let archive = metadata_archive();
+    let history = archive
+        .history(ArchiveKind::SpigotVersion, version)
+        .await?;
+    let mut versions = Vec::with_capacity(history.len());
+    for entry in history {
+        let contents = archive.read(&entry).await?;
+        let parsed = serde_json::from_slice::<SpigotVersion>(&contents)?;
+        versions.push((entry, parsed));
     }
-    let version = response
-        .json::<SpigotVersion>()
+    Ok(versions)
+}
+
+/// Finds the archived version JSONs that contained the provided refs
+/// to explain which versions a build with those refs came from
+pub async fn find_archived_refs(refs: &VersionRefs) -> SpigotResult<Vec<ArchiveEntry>> {
+    let names = metadata_archive()
+        .names(ArchiveKind::SpigotVersion)
         .await?;
-    Ok(version)
+    let mut entries = Vec::new();
+    for name in names {
+        let versions = get_archived_versions(&name).await?;
+        entries.extend(
+            versions
+                .into_iter()
+                .filter(|(_, version)| &version.refs == refs)
+                .map(|(entry, _)| entry),
+        );
+    }
+    Ok(entries)
 }
 
 /// Loads a spigot version stored locally at the provided path
@@ -91,7 +163,7 @@ pub async fn get_version_test(version: &str) -> SpigotResult<SpigotVersion> {
 pub async fn download_version(path: &Path, version: &str) -> SpigotResult<()> {
     let file_name = format!("{}.json", version);
     let file_path = path.join(&file_name);
-    let url = format!("{}{}", SPIGOT_VERSIONS_URL, file_name);
+    let url = format!("{}{}", endpoints().spigot_versions_url, file_name);
     let client = create_reqwest()?;
     let response = client.get(url).send().await?;
     if response.status() == StatusCode::NOT_FOUND {
@@ -114,29 +186,392 @@ pub async fn download_version(path: &Path, version: &str) -> SpigotResult<()> {
 /// in the 1.8.json, 1.9.json files you will see that the name is in
 /// the 1023, 1021 format which are identical files to the other one.
 pub async fn scrape_versions() -> SpigotResult<Vec<String>> {
-    let client = create_reqwest()?;
-    let response = client
-        .get(SPIGOT_VERSIONS_URL)
-        .send()
-        .await?
-        .text()
+    let response = http_cache()
+        .get(&endpoints().spigot_versions_url)
         .await?;
-    let regex = Regex::new(r#"<a href="((\d(.)?)+).json">"#).unwrap();
-    let values: Vec<String> = regex
-        .captures_iter(&response)
-        .map(|m| m.get(1))
-        .filter_map(|m| m)
+    let response = String::from_utf8_lossy(&response);
+    Ok(parse_version_listing(&response))
+}
+
+/// Parses the names of the version JSON files from the HTML directory
+/// listing of the spigot versions url (e.g. 1.8, 1023, latest)
+fn parse_version_listing(listing: &str) -> Vec<String> {
+    let regex = Regex::new(r#"<a href="([^"/]+)\.json">"#).unwrap();
+    regex
+        .captures_iter(listing)
+        .filter_map(|m| m.get(1))
         .map(|m| m.as_str().to_owned())
+        .collect()
+}
+
+/// A single Spigot build. Multiple version JSONs can point at the same
+/// build (e.g. 1.19.json and 3553.json) so the names of all the JSONs
+/// with identical refs are grouped together as aliases
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpigotBuild {
+    /// The build name from the version JSON (e.g. 3553 or 986a)
+    pub name: String,
+    /// The Jenkins build number parsed from the name. None for
+    /// builds that aren't numbered (e.g. 1.8)
+    pub number: Option<u32>,
+    /// Names of the version JSONs that point at this build (sorted)
+    pub aliases: Vec<String>,
+    pub refs: VersionRefs,
+    /// The Minecraft version from the BuildData info of the build. Only
+    /// loaded for builds without a Minecraft version alias
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minecraft_version: Option<String>,
+}
+
+/// Change in the build that a Minecraft version points at between
+/// two version indexes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpigotBuildChange {
+    pub minecraft_version: String,
+    /// The previous build name. None if the version is new
+    pub previous: Option<String>,
+    pub current: String,
+}
+
+/// Index of every version JSON listed by the spigot versions url grouped
+/// into builds along with the latest build for each Minecraft version
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpigotVersionIndex {
+    /// The builds ordered by build number
+    pub builds: Vec<SpigotBuild>,
+    /// Mapping of Minecraft version to the name of its latest build
+    pub versions: BTreeMap<String, String>,
+}
+
+impl SpigotVersionIndex {
+    /// Fetches every version JSON listed by the spigot versions url and
+    /// creates an index from them. Listed versions which can't be found
+    /// are skipped
+    pub async fn fetch() -> SpigotResult<Self> {
+        let names = scrape_versions().await?;
+        Self::fetch_names(names).await
+    }
+
+    /// Fetches the version JSONs with the provided names and creates an
+    /// index from them. Names which can't be found are skipped
+    async fn fetch_names(names: Vec<String>) -> SpigotResult<Self> {
+        debug!("Fetching {} spigot version JSONs", names.len());
+        let results: Vec<SpigotResult<Option<(String, SpigotVersion)>>> = stream::iter(names)
+            .map(|name| async move {
+                match get_version(&name).await {
+                    Ok(version) => Ok(Some((name, version))),
+                    Err(SpigotError::UnknownVersion(name)) => {
+                        warn!("Listed spigot version {name} couldn't be found");
+                        Ok(None)
+                    }
+                    Err(err) => Err(err),
+                }
+            })
+            .buffer_unordered(INDEX_CONCURRENCY)
+            .collect()
+            .await;
+        let versions = results
+            .into_iter()
+            .filter_map(Result::transpose)
+            .collect::<SpigotResult<Vec<(String, SpigotVersion)>>>()?;
+        Ok(Self::from_versions(versions))
+    }
+
+    /// Creates an index from the provided version JSONs and the names
+    /// they were listed under. Versions with identical refs are grouped
+    /// into a single build
+    pub fn from_versions<I>(versions: I) -> Self
+    where
+        I: IntoIterator<Item = (String, SpigotVersion)>,
+    {
+        let mut builds: Vec<SpigotBuild> = Vec::new();
+        let mut by_refs: HashMap<VersionRefs, usize> = HashMap::new();
+        for (alias, version) in versions {
+            let number = parse_build_number(&version.name);
+            match by_refs.get(&version.refs) {
+                Some(&index) => {
+                    let build = &mut builds[index];
+                    build.aliases.push(alias);
+                    // Prefer the highest numbered name for rebuilt refs
+                    if number > build.number {
+                        build.name = version.name;
+                        build.number = number;
+                    }
+                }
+                None => {
+                    by_refs.insert(version.refs.clone(), builds.len());
+                    builds.push(SpigotBuild {
+                        name: version.name,
+                        number,
+                        aliases: vec![alias],
+                        refs: version.refs,
+                        minecraft_version: None,
+                    });
+                }
+            }
+        }
+
+        for build in &mut builds {
+            build.aliases.sort();
+            build.aliases.dedup();
+        }
+        builds.sort_by(|a, b| {
+            a.number
+                .cmp(&b.number)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        let versions = builds
+            .iter()
+            .flat_map(|build| {
+                build
+                    .aliases
+                    .iter()
+                    .filter(|alias| is_minecraft_version(alias))
+                    .map(|alias| (alias.clone(), build.name.clone()))
+            })
+            .collect();
+
+        Self { builds, versions }
+    }
+
+    /// Finds the build with the provided build name or alias
+    pub fn get(&self, name: &str) -> Option<&SpigotBuild> {
+        self.position(name)
+            .map(|index| &self.builds[index])
+    }
+
+    /// Finds the index of the build with the provided build name or alias
+    fn position(&self, name: &str) -> Option<usize> {
+        self.builds
+            .iter()
+            .position(|build| build.name == name)
+            .or_else(|| {
+                self.builds
+                    .iter()
+                    .position(|build| {
+                        build
+                            .aliases
+                            .iter()
+                            .any(|alias| alias == name)
+                    })
+            })
+    }
+
+    /// Finds the latest build for the provided Minecraft version
+    pub fn latest(&self, minecraft_version: &str) -> Option<&SpigotBuild> {
+        let name = self
+            .versions
+            .get(minecraft_version)?;
+        self.get(name)
+    }
+
+    /// Resolves the provided version (a Minecraft version, a partial
+    /// Minecraft version, a build name or an alias such as latest) to
+    /// the concrete Minecraft version and the build it points at. Builds
+    /// without a Minecraft version alias use the version loaded from
+    /// their BuildData info (see `load_minecraft_version`).
+    ///
+    /// Partial versions that aren't listed themselves (e.g. 1.20 when
+    /// only 1.20.1 is listed) resolve to the highest listed version
+    /// starting with them
+    pub fn resolve(&self, version: &str) -> Option<(&str, &SpigotBuild)> {
+        if let Some(build) = self.get(version) {
+            let minecraft_version = build
+                .aliases
+                .iter()
+                .filter(|alias| is_minecraft_version(alias))
+                .map(|alias| MinecraftVersion::parse(alias))
+                .reduce(|a, b| if b > a { b } else { a })
+                .and_then(|minecraft_version| {
+                    self.versions
+                        .get_key_value(&minecraft_version.id)
+                })
+                .map(|(minecraft_version, _)| minecraft_version.as_str())
+                .or(build
+                    .minecraft_version
+                    .as_deref())?;
+            return Some((minecraft_version, build));
+        }
+
+        let VersionKind::Release {
+            numbers: prefix,
+            stage: ReleaseStage::Release,
+        } = MinecraftVersion::parse(version).kind
+        else {
+            return None;
+        };
+        let minecraft_version = self
+            .versions
+            .keys()
+            .map(|alias| MinecraftVersion::parse(alias))
+            .filter(|alias| {
+                matches!(
+                    &alias.kind,
+                    VersionKind::Release { numbers, stage: ReleaseStage::Release }
+                        if numbers.starts_with(&prefix)
+                )
+            })
+            .reduce(|a, b| if b > a { b } else { a })?;
+        self.versions
+            .get_key_value(&minecraft_version.id)
+            .and_then(|(minecraft_version, build)| {
+                Some((minecraft_version.as_str(), self.get(build)?))
+            })
+    }
+
+    /// Loads the Minecraft version of the build with the provided name or
+    /// alias from its BuildData info when it can't otherwise be resolved
+    /// (e.g. build numbers which no Minecraft version JSON points at)
+    pub async fn load_minecraft_version(&mut self, name: &str) -> SpigotResult<()> {
+        if self.resolve(name).is_some() {
+            return Ok(());
+        }
+        let Some(index) = self.position(name) else {
+            return Ok(());
+        };
+        let build = &mut self.builds[index];
+        debug!(
+            "Loading the Minecraft version of build {} from BuildData",
+            build.name
+        );
+        let info = get_build_data_info(&build.refs).await?;
+        build.minecraft_version = Some(info.minecraft_version);
+        Ok(())
+    }
+
+    /// Finds the Minecraft versions whose latest build differs from
+    /// the build in the `previous` index
+    pub fn changes(&self, previous: &SpigotVersionIndex) -> Vec<SpigotBuildChange> {
+        self.versions
+            .iter()
+            .filter_map(|(minecraft_version, current)| {
+                let previous_build = previous.latest(minecraft_version);
+                let current_build = self.latest(minecraft_version)?;
+                if previous_build.is_some_and(|build| build.refs == current_build.refs) {
+                    return None;
+                }
+                Some(SpigotBuildChange {
+                    minecraft_version: minecraft_version.clone(),
+                    previous: previous_build.map(|build| build.name.clone()),
+                    current: current.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Loads an index previously saved at the provided path. Returns
+    /// None if no index has been saved
+    pub async fn load(path: &Path) -> SpigotResult<Option<Self>> {
+        if !ensure_is_file(path).await? {
+            return Ok(None);
+        }
+        let contents = read(path).await?;
+        let index = serde_json::from_slice::<Self>(&contents)?;
+        Ok(Some(index))
+    }
+
+    /// Saves the index to the provided path
+    pub async fn save(&self, path: &Path) -> SpigotResult<()> {
+        ensure_parent_exists(path).await?;
+        let contents = serde_json::to_vec_pretty(self)?;
+        write_atomic(path, contents).await?;
+        Ok(())
+    }
+
+    /// Fetches the current index and replaces the index saved at the
+    /// provided path returning the index and the Minecraft versions
+    /// which have new builds since it was last saved
+    pub async fn update(path: &Path) -> SpigotResult<(Self, Vec<SpigotBuildChange>)> {
+        let previous = Self::load(path)
+            .await?
+            .unwrap_or_default();
+        let index = Self::fetch().await?;
+        let changes = index.changes(&previous);
+        index.save(path).await?;
+        Ok((index, changes))
+    }
+}
+
+/// Spigot version resolved to a concrete Minecraft version and build
+#[derive(Debug)]
+pub struct ResolvedVersion {
+    /// The version that was requested (e.g. latest or 1.19)
+    pub requested: String,
+    /// The concrete Minecraft version (e.g. 1.19.4)
+    pub minecraft_version: String,
+    /// The build the version resolved to
+    pub build: SpigotBuild,
+    /// The version JSON of the build
+    pub spigot_version: SpigotVersion,
+}
+
+/// Resolves the provided version to a concrete Minecraft version and
+/// Spigot build before building. Only the version JSONs of the listed
+/// Minecraft versions and the requested version are fetched
+pub async fn resolve_version(version: &str) -> SpigotResult<ResolvedVersion> {
+    let names = scrape_versions()
+        .await?
+        .into_iter()
+        .filter(|name| name == version || is_minecraft_version(name))
         .collect();
-    Ok(values)
+    let mut index = SpigotVersionIndex::fetch_names(names).await?;
+    index
+        .load_minecraft_version(version)
+        .await?;
+    let (minecraft_version, build) = match index.resolve(version) {
+        Some(resolved) => resolved,
+        None if index.get(version).is_some() => {
+            return Err(SpigotError::UnresolvedVersion(version.to_string()))
+        }
+        None => return Err(SpigotError::UnknownVersion(version.to_string())),
+    };
+    let alias = build
+        .aliases
+        .first()
+        .ok_or_else(|| SpigotError::UnknownVersion(version.to_string()))?;
+    let spigot_version = get_version(alias).await?;
+    Ok(ResolvedVersion {
+        requested: version.to_string(),
+        minecraft_version: minecraft_version.to_string(),
+        build: build.clone(),
+        spigot_version,
+    })
+}
+
+/// Parses the build number from a build name which is either a number
+/// or a number with a letter suffix (e.g. 3553 or 986a)
+fn parse_build_number(name: &str) -> Option<u32> {
+    let end = name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len());
+    let (number, suffix) = name.split_at(end);
+    if !suffix
+        .chars()
+        .all(|c| c.is_ascii_alphabetic())
+    {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Whether the version JSON name is a Minecraft version rather
+/// than a build number or alias such as latest
+fn is_minecraft_version(name: &str) -> bool {
+    MinecraftVersion::parse(name).kind != VersionKind::Unknown
 }
 
 #[cfg(test)]
 pub(crate) mod test {
-    use crate::build_tools::spigot::{download_version, get_version_local, scrape_versions};
+    use crate::build_tools::spigot::{
+        download_version, get_version_local, parse_version_listing, scrape_versions,
+        SpigotBuildChange, SpigotVersionIndex,
+    };
+    use crate::models::build_tools::BuildDataInfo;
+    use crate::utils::testing::test_dir;
     use futures::future::try_join_all;
     use std::path::Path;
-    use tokio::fs::create_dir;
+    use tokio::fs::{create_dir, read};
 
     pub const TEST_VERSIONS: [&str; 12] = [
         "1.8", "1.9", "1.10.2", "1.11", "1.12", "1.13", "1.14", "1.16.1", "1.17", "1.18", "1.19",
@@ -217,4 +652,135 @@ pub(crate) mod test {
             .await
             .unwrap();
     }
+
+    /// Tests parsing the names from the versions directory listing
+    #[test]
+    fn test_parse_version_listing() {
+        let listing = r#"<a href="../">../</a>
+<a href="1.8.json">1.8.json</a>
+<a href="1023.json">1023.json</a>
+<a href="1.19-pre1.json">1.19-pre1.json</a>
+<a href="latest.json">latest.json</a>"#;
+        assert_eq!(
+            parse_version_listing(listing),
+            ["1.8", "1023", "1.19-pre1", "latest"]
+        );
+    }
+
+    /// Tests grouping version JSONs with identical refs into builds and
+    /// detecting Minecraft versions whose latest build changed
+    #[tokio::test]
+    async fn test_version_index() {
+        let load = |name: &'static str| async move {
+            get_version_local(format!("test/spigot/{name}.json"))
+                .await
+                .unwrap()
+        };
+
+        let previous = SpigotVersionIndex::from_versions([
+            ("1.19".to_string(), load("1.19").await),
+            ("3553".to_string(), load("1.19").await),
+            ("1.18".to_string(), load("1.18").await),
+        ]);
+        let index = SpigotVersionIndex::from_versions([
+            ("1.18".to_string(), load("1.18").await),
+            ("3553".to_string(), load("1.19").await),
+            ("1.19".to_string(), load("latest").await),
+            ("3593".to_string(), load("latest").await),
+            ("latest".to_string(), load("latest").await),
+            ("1.8".to_string(), load("1.8").await),
+        ]);
+
+        assert_eq!(index.builds.len(), 4);
+        // Unnumbered builds are ordered first
+        assert_eq!(index.builds[0].name, "1.8");
+        assert_eq!(index.builds[0].number, None);
+        let latest = index.get("latest").unwrap();
+        assert_eq!(latest.number, Some(3593));
+        assert_eq!(latest.aliases, ["1.19", "3593", "latest"]);
+        assert_eq!(
+            index
+                .latest("1.19")
+                .map(|build| build.name.as_str()),
+            Some("3593")
+        );
+        assert!(!index
+            .versions
+            .contains_key("latest"));
+
+        assert_eq!(
+            index.changes(&previous),
+            [
+                SpigotBuildChange {
+                    minecraft_version: "1.19".to_string(),
+                    previous: Some("3553".to_string()),
+                    current: "3593".to_string(),
+                },
+                SpigotBuildChange {
+                    minecraft_version: "1.8".to_string(),
+                    previous: None,
+                    current: "1.8".to_string(),
+                },
+            ]
+        );
+        assert!(index
+            .changes(&index)
+            .is_empty());
+
+        let path = test_dir("spigot-index").join("index.json");
+        index
+            .save(&path)
+            .await
+            .unwrap();
+        let loaded = SpigotVersionIndex::load(&path)
+            .await
+            .unwrap();
+        assert_eq!(loaded, Some(index));
+    }
+
+    /// Tests resolving aliases, build names and partial versions to
+    /// concrete Minecraft versions and builds
+    #[tokio::test]
+    async fn test_resolve_version() {
+        let load = |name: &'static str| async move {
+            get_version_local(format!("test/spigot/{name}.json"))
+                .await
+                .unwrap()
+        };
+        let mut index = SpigotVersionIndex::from_versions([
+            ("1.18".to_string(), load("1.18").await),
+            ("1.19.2".to_string(), load("latest").await),
+            ("latest".to_string(), load("latest").await),
+            ("3553".to_string(), load("1.19").await),
+        ]);
+        assert_eq!(
+            index
+                .resolve("3553")
+                .map(|(minecraft_version, _)| minecraft_version),
+            None
+        );
+        // Builds without a Minecraft version alias use their BuildData info
+        let info = read("test/spigot/build_data/info.json")
+            .await
+            .unwrap();
+        let info = serde_json::from_slice::<BuildDataInfo>(&info).unwrap();
+        index
+            .builds
+            .iter_mut()
+            .find(|build| build.name == "3553")
+            .unwrap()
+            .minecraft_version = Some(info.minecraft_version);
+
+        let resolve = |version: &str| {
+            index
+                .resolve(version)
+                .map(|(minecraft_version, build)| (minecraft_version, build.name.as_str()))
+        };
+        assert_eq!(resolve("latest"), Some(("1.19.2", "3593")));
+        assert_eq!(resolve("1.19"), Some(("1.19.2", "3593")));
+        assert_eq!(resolve("1.18"), Some(("1.18", "3359")));
+        assert_eq!(resolve("3359"), Some(("1.18", "3359")));
+        assert_eq!(resolve("3553"), Some(("1.19", "3553")));
+        assert_eq!(resolve("1.20"), None);
+    }
 }
@@ -3,16 +3,20 @@
 
 use crate::build_tools::run_build_tools;
 use crate::utils::constants::{APP_VERSION, PARODY_BUILD_TOOLS_VERSION};
+use crate::utils::endpoints::{init_endpoints, EndpointsError};
 
 mod build_tools;
 mod models;
 mod utils;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> Result<(), EndpointsError> {
     dotenv::dotenv().ok();
     env_logger::init();
 
+    // Load the endpoints early so configuration errors are reported on startup
+    init_endpoints()?;
+
     println!(
         "Jars (Version: {}, Parody: {})",
         APP_VERSION, PARODY_BUILD_TOOLS_VERSION
@@ -21,4 +25,5 @@ async fn main() {
     run_build_tools("latest")
         .await
         .unwrap();
+    Ok(())
 }
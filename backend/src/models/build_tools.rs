@@ -1,3 +1,6 @@
+use crate::build_tools::commands::BuildStep;
+use crate::utils::cmd::{CommandError, CommandTemplate};
+use crate::utils::endpoints::endpoints;
 use crate::utils::hash::HashType;
 use regex::Regex;
 use serde::Deserialize;
@@ -61,18 +64,52 @@ impl Default for BuildDataInfo {
 
 impl BuildDataInfo {
     /// Finds the download url for the vanilla server jar based on whether
-    /// the server url exists or not.
+    /// the server url exists or not. The configured url rewrites are applied.
     pub fn get_download_url(&self) -> String {
+        let endpoints = endpoints();
         if let Some(url) = &self.server_url {
-            url.clone()
+            endpoints.rewrite(url)
         } else {
             format!(
-                "https://s3.amazonaws.com/Minecraft.Download/versions/{0}/minecraft_server.{0}.jar",
-                self.minecraft_version,
+                "{0}{1}/minecraft_server.{1}.jar",
+                endpoints.minecraft_download_url, self.minecraft_version,
             )
         }
     }
 
+    /// Finds the download url for the mojang mappings if they are
+    /// present. The configured url rewrites are applied.
+    pub fn get_mappings_url(&self) -> Option<String> {
+        self.mappings_url
+            .as_ref()
+            .map(|url| endpoints().rewrite(url))
+    }
+
+    /// Retrieves the paths of the mappings files within the BuildData
+    /// repository. The last commit to change these files is used for
+    /// the mappings hash
+    pub fn get_mappings_paths(&self) -> Vec<String> {
+        [
+            Some(&self.access_transforms),
+            Some(&self.class_mappings),
+            self.member_mappings.as_ref(),
+            self.package_mappings.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|name| format!("mappings/{name}"))
+        .collect()
+    }
+
+    /// Parses each of the custom commands checking that they only
+    /// reference the arguments provided to their step
+    pub fn validate_commands(&self) -> Result<(), CommandError> {
+        BuildStep::ALL
+            .into_iter()
+            .filter_map(|step| Some((step.custom_command(self)?, step.arg_count())))
+            .try_for_each(|(command, args)| CommandTemplate::parse(command)?.validate(args, &[]))
+    }
+
     /// Retrieves the server hash value o
     pub fn get_server_hash(&self) -> Option<(HashType, &str)> {
         if let Some(server_url) = &self.server_url {
@@ -81,10 +118,12 @@ impl BuildDataInfo {
                 return Some((HashType::SHA1, hash));
             }
         }
-        if let Some(hash) = &self.minecraft_hash {
-            return Some((HashType::MD5, hash));
+        // Newer versions use the minecraft hash for the SHA-256 of
+        // the embedded server jar rather than the downloaded jar
+        match &self.minecraft_hash {
+            Some(hash) if hash.len() == 32 => Some((HashType::MD5, hash)),
+            _ => None,
         }
-        None
     }
 
     /// Retrieves the hash portion of a provided url or None if its
@@ -0,0 +1,306 @@
+use crate::utils::files::{ensure_dir_exists, ensure_is_file, write_atomic};
+use crate::utils::hash::HashType;
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+use thiserror::Error;
+use tokio::fs::{read, read_dir};
+use tokio::sync::Mutex;
+
+/// The metadata archive configured from the environment
+static METADATA_ARCHIVE: OnceLock<MetadataArchive> = OnceLock::new();
+
+/// Environment variable for the directory the archive is stored in
+pub const METADATA_ARCHIVE_PATH_KEY: &str = "METADATA_ARCHIVE_PATH";
+
+/// Default directory the archive is stored in
+const DEFAULT_ARCHIVE_PATH: &str = "build/archive";
+/// Directory within the archive containing the archived contents
+/// stored by their SHA-1 hash
+const OBJECTS_DIR: &str = "objects";
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    #[error(transparent)]
+    Parse(#[from] serde_json::Error),
+    #[error("Invalid archive name \"{0}\"")]
+    InvalidName(String),
+    #[error("Archived contents {0} are missing")]
+    MissingObject(String),
+}
+
+type ArchiveResult<T> = Result<T, ArchiveError>;
+
+/// Kind of upstream metadata that is archived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// Spigot version JSONs archived by the name they were fetched as
+    SpigotVersion,
+    /// Snapshots of Mojang's version manifest
+    VersionManifest,
+}
+
+impl ArchiveKind {
+    /// The directory within the archive for this kind
+    fn directory(&self) -> &'static str {
+        match self {
+            Self::SpigotVersion => "spigot",
+            Self::VersionManifest => "manifest",
+        }
+    }
+}
+
+/// Record of a single archived version of some metadata
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    /// The name the metadata was fetched as (e.g. 1.19 or latest)
+    pub name: String,
+    /// The url the metadata was fetched from
+    pub url: String,
+    /// The SHA-1 hash of the contents
+    pub sha1: String,
+    /// When these contents were first fetched
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Archive of every distinct version of upstream metadata that has been
+/// fetched. Contents are stored once by hash and each name has a history
+/// of the contents it pointed at so historic builds can be reproduced
+#[derive(Debug)]
+pub struct MetadataArchive {
+    path: PathBuf,
+    /// Lock held while updating histories
+    lock: Mutex<()>,
+}
+
+impl MetadataArchive {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Creates the archive using METADATA_ARCHIVE_PATH falling
+    /// back to the default path when it isn't set
+    pub fn from_env() -> Self {
+        let path = env::var(METADATA_ARCHIVE_PATH_KEY)
+            .unwrap_or_else(|_| DEFAULT_ARCHIVE_PATH.to_string());
+        Self::new(path)
+    }
+
+    /// Archives the contents fetched for the provided name. Nothing is
+    /// added when the contents match the latest archived contents for
+    /// the name. Returns the entry for the contents
+    pub async fn archive(
+        &self,
+        kind: ArchiveKind,
+        name: &str,
+        url: &str,
+        contents: &[u8],
+    ) -> ArchiveResult<ArchiveEntry> {
+        let history_path = self.history_path(kind, name)?;
+        let sha1 = HashType::SHA1.digest(contents);
+
+        let _guard = self.lock.lock().await;
+        let mut history = read_history(&history_path).await?;
+        if let Some(latest) = history.last() {
+            if latest.sha1 == sha1 {
+                return Ok(latest.clone());
+            }
+        }
+
+        let objects = self.path.join(OBJECTS_DIR);
+        ensure_dir_exists(&objects).await?;
+        let object_path = objects.join(&sha1);
+        if !ensure_is_file(&object_path).await? {
+            write_atomic(&object_path, contents).await?;
+        }
+
+        debug!("Archiving new contents for {name} ({sha1})");
+        let entry = ArchiveEntry {
+            name: name.to_string(),
+            url: url.to_string(),
+            sha1,
+            fetched_at: Utc::now(),
+        };
+        history.push(entry.clone());
+        let contents = serde_json::to_vec_pretty(&history)?;
+        ensure_dir_exists(
+            self.path
+                .join(kind.directory()),
+        )
+        .await?;
+        write_atomic(&history_path, &contents).await?;
+        Ok(entry)
+    }
+
+    /// The archived entries for the provided name ordered
+    /// from oldest to newest
+    pub async fn history(&self, kind: ArchiveKind, name: &str) -> ArchiveResult<Vec<ArchiveEntry>> {
+        let path = self.history_path(kind, name)?;
+        read_history(&path).await
+    }
+
+    /// Finds the entry that the provided name pointed at during the
+    /// provided time (the newest entry fetched at or before it)
+    pub async fn at(
+        &self,
+        kind: ArchiveKind,
+        name: &str,
+        time: DateTime<Utc>,
+    ) -> ArchiveResult<Option<ArchiveEntry>> {
+        let history = self
+            .history(kind, name)
+            .await?;
+        Ok(history
+            .into_iter()
+            .rev()
+            .find(|entry| entry.fetched_at <= time))
+    }
+
+    /// The names that have been archived for the provided kind
+    pub async fn names(&self, kind: ArchiveKind) -> ArchiveResult<Vec<String>> {
+        let path = self
+            .path
+            .join(kind.directory());
+        if !path.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut names = Vec::new();
+        let mut entries = read_dir(path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name();
+            if let Some(name) = file_name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+            {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Reads the archived contents of the provided entry
+    pub async fn read(&self, entry: &ArchiveEntry) -> ArchiveResult<Vec<u8>> {
+        let path = self
+            .path
+            .join(OBJECTS_DIR)
+            .join(&entry.sha1);
+        if !ensure_is_file(&path).await? {
+            return Err(ArchiveError::MissingObject(entry.sha1.clone()));
+        }
+        Ok(read(path).await?)
+    }
+
+    /// Gets the path of the history file for the provided name
+    fn history_path(&self, kind: ArchiveKind, name: &str) -> ArchiveResult<PathBuf> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\', ':', '\0']) {
+            return Err(ArchiveError::InvalidName(name.to_string()));
+        }
+        Ok(self
+            .path
+            .join(kind.directory())
+            .join(format!("{name}.json")))
+    }
+}
+
+/// Reads the history file at the provided path. Missing
+/// histories are treated as empty
+async fn read_history(path: &Path) -> ArchiveResult<Vec<ArchiveEntry>> {
+    if !ensure_is_file(path).await? {
+        return Ok(Vec::new());
+    }
+    let contents = read(path).await?;
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+/// Retrieves the global metadata archive which is configured from
+/// the environment the first time it's accessed
+pub fn metadata_archive() -> &'static MetadataArchive {
+    METADATA_ARCHIVE.get_or_init(MetadataArchive::from_env)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::archive::{ArchiveError, ArchiveKind, MetadataArchive};
+    use crate::utils::testing::test_dir;
+    use chrono::Utc;
+
+    /// Tests that only changed contents are archived and that the
+    /// history can be queried by name and time
+    #[tokio::test]
+    async fn test_archive_history() {
+        let path = test_dir("archive");
+        let archive = MetadataArchive::new(&path);
+        let kind = ArchiveKind::SpigotVersion;
+        let url = "https://hub.spigotmc.org/versions/latest.json";
+
+        let first = archive
+            .archive(kind, "latest", url, b"first")
+            .await
+            .unwrap();
+        let middle = Utc::now();
+        let repeated = archive
+            .archive(kind, "latest", url, b"first")
+            .await
+            .unwrap();
+        assert_eq!(first, repeated);
+        let second = archive
+            .archive(kind, "latest", url, b"second")
+            .await
+            .unwrap();
+
+        let history = archive
+            .history(kind, "latest")
+            .await
+            .unwrap();
+        assert_eq!(history, [first.clone(), second.clone()]);
+        assert_eq!(
+            archive
+                .at(kind, "latest", middle)
+                .await
+                .unwrap(),
+            Some(first.clone())
+        );
+        assert_eq!(
+            archive
+                .read(&first)
+                .await
+                .unwrap(),
+            b"first"
+        );
+        assert_eq!(
+            archive
+                .read(&second)
+                .await
+                .unwrap(),
+            b"second"
+        );
+        assert_eq!(
+            archive
+                .names(kind)
+                .await
+                .unwrap(),
+            ["latest"]
+        );
+        assert!(archive
+            .names(ArchiveKind::VersionManifest)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let result = archive
+            .archive(kind, "../latest", url, b"first")
+            .await;
+        assert!(matches!(result, Err(ArchiveError::InvalidName(_))));
+    }
+}
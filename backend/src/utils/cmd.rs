@@ -13,20 +13,41 @@ pub enum CommandError {
     IO(#[from] io::Error),
     #[error("Missing command")]
     MissingCommand,
+    #[error("Invalid command template: {0}")]
+    InvalidTemplate(String),
+    #[error("Command template references missing argument {{{0}}}")]
+    MissingArgument(String),
 }
 
-/// Executes the provided `command` formatting it with the provided arguments `args_in`
-/// and returns the ExitStatus of the program on success
+/// Executes the provided `command` template formatting it with the provided
+/// arguments `args_in` and returns the ExitStatus of the program on success
 pub async fn execute_command(
     working_dir: impl AsRef<Path>,
     command: &str,
     args_in: &[&str],
 ) -> Result<ExitStatus, CommandError> {
-    let (command, args) = parse_command(command).ok_or(CommandError::MissingCommand)?;
-    let new_args = transform_args(args, args_in);
+    let template = CommandTemplate::parse(command)?;
+    execute_template(working_dir, &template, args_in, &[]).await
+}
+
+/// Executes the provided command template formatting it with the positional
+/// arguments `args_in` and the `named` arguments and returns the ExitStatus
+/// of the program on success
+pub async fn execute_template(
+    working_dir: impl AsRef<Path>,
+    template: &CommandTemplate,
+    args_in: &[&str],
+    named: &[(&str, &str)],
+) -> Result<ExitStatus, CommandError> {
+    let mut args = template
+        .render(args_in, named)?
+        .into_iter();
+    let command = args
+        .next()
+        .ok_or(CommandError::MissingCommand)?;
 
     let mut command = Command::new(command);
-    command.args(&new_args);
+    command.args(args);
     command.current_dir(working_dir);
     if std::env::var("MAVEN_OPTS").is_err() {
         command.env("MAVEN_OPTS", "-Xmx1024M");
@@ -41,50 +62,223 @@ pub async fn execute_command(
     Ok(status)
 }
 
-/// Parses the provided command into the command itself and
-/// an array of arguments.
-fn parse_command(value: &str) -> Option<(&str, Vec<&str>)> {
-    let mut parts = value.split_whitespace();
-    let command = parts.next()?;
-    let mut args = Vec::new();
-    for part in parts {
-        args.push(part);
-    }
-    Some((command, args))
+/// Part of a command template argument
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    /// Positional placeholder (e.g. {0})
+    Index(usize),
+    /// Named placeholder (e.g. {output})
+    Named(String),
 }
 
-/// Transforms the provided array of arguments by replacing
-/// the format args (e.g. {0}, {1}) with the values stored
-/// in the `args_in` slice.
-fn transform_args<'a: 'b, 'b>(args: Vec<&'a str>, args_in: &'b [&str]) -> Vec<&'b str> {
-    /// Attempts to parse the argument selector (e.g. {0}) from the
-    /// provided string slice returning None if there is none or if
-    /// the number could not be parsed.
-    fn parse_arg(value: &str) -> Option<usize> {
-        let start = value.find('{')?;
-        let end = value.find('}')?;
-        if end <= start {
-            return None;
+/// Parsed command template (e.g. the BuildData `decompile_command`).
+///
+/// Arguments are separated by whitespace unless quoted. Double quoted
+/// text may contain `\"` and `\\` escapes and placeholders while single
+/// quoted text is taken literally. Placeholders are either positional
+/// (`{0}`) or named (`{output}`) and can be embedded within an argument
+/// (e.g. `-o={2}`). Placeholder values are never split into multiple
+/// arguments. `{{` and `}}` are literal braces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandTemplate {
+    args: Vec<Vec<Segment>>,
+}
+
+impl CommandTemplate {
+    /// Parses the provided command template
+    pub fn parse(value: &str) -> Result<Self, CommandError> {
+        let mut args: Vec<Vec<Segment>> = Vec::new();
+        // Segments of the current argument and whether an argument
+        // has been started (quotes start possibly empty arguments)
+        let mut current: Vec<Segment> = Vec::new();
+        let mut literal = String::new();
+        let mut started = false;
+        let mut quote: Option<char> = None;
+
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (Some('\''), '\'') => quote = None,
+                (Some('\''), c) => literal.push(c),
+                (Some('"'), '"') => quote = None,
+                (Some('"'), '\\') if matches!(chars.peek(), Some('"' | '\\')) => {
+                    literal.extend(chars.next());
+                }
+                (None, '"' | '\'') => {
+                    quote = Some(c);
+                    started = true;
+                }
+                (None, c) if c.is_whitespace() => {
+                    if started {
+                        if !literal.is_empty() {
+                            current.push(Segment::Literal(std::mem::take(&mut literal)));
+                        }
+                        args.push(std::mem::take(&mut current));
+                        started = false;
+                    }
+                }
+                (_, '{') if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                    started = true;
+                }
+                (_, '}') if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                    started = true;
+                }
+                (_, '{') => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(CommandError::InvalidTemplate(format!(
+                                    "unclosed placeholder {{{name}"
+                                )))
+                            }
+                        }
+                    }
+                    if !literal.is_empty() {
+                        current.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    current.push(parse_placeholder(&name)?);
+                    started = true;
+                }
+                (_, '}') => return Err(CommandError::InvalidTemplate("unmatched }".to_string())),
+                (_, c) => {
+                    literal.push(c);
+                    started = true;
+                }
+            }
+        }
+
+        if quote.is_some() {
+            return Err(CommandError::InvalidTemplate("unclosed quote".to_string()));
+        }
+        if started {
+            if !literal.is_empty() {
+                current.push(Segment::Literal(literal));
+            }
+            args.push(current);
+        }
+        if args.is_empty() {
+            return Err(CommandError::MissingCommand);
         }
-        let inner = &value[start + 1..end];
-        inner.parse::<usize>().ok()
+        Ok(Self { args })
+    }
+
+    /// Checks that every placeholder in the template refers to one of
+    /// the `positional` number of arguments or to one of the `named`
+    /// arguments
+    pub fn validate(&self, positional: usize, named: &[&str]) -> Result<(), CommandError> {
+        self.args
+            .iter()
+            .flatten()
+            .try_for_each(|segment| match segment {
+                Segment::Index(index) if *index >= positional => {
+                    Err(CommandError::MissingArgument(index.to_string()))
+                }
+                Segment::Named(name) if !named.contains(&name.as_str()) => {
+                    Err(CommandError::MissingArgument(name.clone()))
+                }
+                _ => Ok(()),
+            })
     }
 
-    let mut out = Vec::with_capacity(args.len());
-    for arg in args {
-        if let Some(index) = parse_arg(arg) {
-            if index < args_in.len() {
-                let value = args_in[index];
-                out.push(value);
-                continue;
+    /// Replaces each of the literal parts of the template with the value
+    /// returned by `map`. The second argument of `map` is whether the
+    /// literal is at the start of its argument
+    pub fn map_literals<F: FnMut(&str, bool) -> String>(&mut self, mut map: F) {
+        for segments in &mut self.args {
+            for (index, segment) in segments
+                .iter_mut()
+                .enumerate()
+            {
+                if let Segment::Literal(value) = segment {
+                    *value = map(value, index == 0);
+                }
             }
         }
-        out.push(arg);
     }
-    out
+
+    /// Renders the template into the program and its arguments using
+    /// the provided positional and named argument values
+    pub fn render(
+        &self,
+        args: &[&str],
+        named: &[(&str, &str)],
+    ) -> Result<Vec<String>, CommandError> {
+        let names: Vec<&str> = named
+            .iter()
+            .map(|(name, _)| *name)
+            .collect();
+        self.validate(args.len(), &names)?;
+        let rendered = self
+            .args
+            .iter()
+            .map(|segments| {
+                segments
+                    .iter()
+                    .map(|segment| match segment {
+                        Segment::Literal(value) => value.as_str(),
+                        Segment::Index(index) => args[*index],
+                        Segment::Named(name) => named
+                            .iter()
+                            .find(|(key, _)| key == name)
+                            .map(|(_, value)| *value)
+                            .unwrap_or_default(),
+                    })
+                    .collect::<String>()
+            })
+            .collect();
+        Ok(rendered)
+    }
 }
 
-pub async fn piped_command(mut command: Command) -> io::Result<ExitStatus> {
+/// Parses the contents of a placeholder which is either an
+/// argument index or an argument name
+fn parse_placeholder(value: &str) -> Result<Segment, CommandError> {
+    let value = value.trim();
+    if !value.is_empty()
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_digit())
+    {
+        return value
+            .parse()
+            .map(Segment::Index)
+            .map_err(|_| {
+                CommandError::InvalidTemplate(format!("invalid placeholder {{{value}}}"))
+            });
+    }
+    let valid_name = value
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_name {
+        return Err(CommandError::InvalidTemplate(format!(
+            "invalid placeholder {{{value}}}"
+        )));
+    }
+    Ok(Segment::Named(value.to_string()))
+}
+
+pub async fn piped_command(command: Command) -> io::Result<ExitStatus> {
+    piped_command_with(command, |_| {}).await
+}
+
+/// Executes the command logging its output like `piped_command` while
+/// also passing each line of stdout and stderr to `on_line`
+pub async fn piped_command_with<F: Fn(&str)>(
+    mut command: Command,
+    on_line: F,
+) -> io::Result<ExitStatus> {
     command.stdout(Stdio::piped());
     command.stderr(Stdio::piped());
 
@@ -93,8 +287,8 @@ pub async fn piped_command(mut command: Command) -> io::Result<ExitStatus> {
     let mut stdout_pipe = child.stdout.take();
     let mut stderr_pipe = child.stderr.take();
 
-    let a_fut = pipe_lines(false, &mut stdout_pipe);
-    let b_fut = pipe_lines(true, &mut stderr_pipe);
+    let a_fut = pipe_lines(false, &mut stdout_pipe, &on_line);
+    let b_fut = pipe_lines(true, &mut stderr_pipe, &on_line);
 
     let (status, _, _) = try_join!(child.wait(), a_fut, b_fut)?;
 
@@ -104,7 +298,11 @@ pub async fn piped_command(mut command: Command) -> io::Result<ExitStatus> {
     Ok(status)
 }
 
-async fn pipe_lines<A: AsyncRead + Unpin>(error: bool, io: &mut Option<A>) -> io::Result<()> {
+async fn pipe_lines<A: AsyncRead + Unpin, F: Fn(&str)>(
+    error: bool,
+    io: &mut Option<A>,
+    on_line: &F,
+) -> io::Result<()> {
     let io = match io {
         Some(value) => value,
         None => return Ok(()),
@@ -115,6 +313,7 @@ async fn pipe_lines<A: AsyncRead + Unpin>(error: bool, io: &mut Option<A>) -> io
     let mut error_output = error;
 
     while let Ok(Some(line)) = lines.next_line().await {
+        on_line(&line);
         match get_line_parts(&line) {
             Some((level, text)) => match level {
                 "WARN" | "WARNING" => warn!("{text}"),
@@ -159,7 +358,7 @@ fn get_line_parts(line: &str) -> Option<(&str, &str)> {
 
 #[cfg(test)]
 mod test {
-    use crate::utils::cmd::{parse_command, transform_args};
+    use crate::utils::cmd::{CommandError, CommandTemplate};
     use log::info;
 
     #[test]
@@ -170,9 +369,68 @@ mod test {
         let value = "Hello {0} {0} {1}";
         let args_in = ["false", "true"];
 
-        let (command, args) = parse_command(value).unwrap();
+        let template = CommandTemplate::parse(value).unwrap();
 
-        let new_args = transform_args(args, &args_in);
-        info!("{command} {new_args:?}")
+        let new_args = template
+            .render(&args_in, &[])
+            .unwrap();
+        info!("{new_args:?}");
+        assert_eq!(new_args, ["Hello", "false", "false", "true"]);
+    }
+
+    /// Tests quoting, embedded and named placeholders and that
+    /// placeholder values containing spaces stay a single argument
+    #[test]
+    fn test_template_quoting() {
+        let template = CommandTemplate::parse(
+            r#"java -jar "Build Data/bin/ss.jar" -o={2} --name '{literal}' "say \"{out}\"" "" {{x}}"#,
+        )
+        .unwrap();
+        let args = template
+            .render(&["a", "b", "/tmp/my jar.jar"], &[("out", "done")])
+            .unwrap();
+        assert_eq!(
+            args,
+            [
+                "java",
+                "-jar",
+                "Build Data/bin/ss.jar",
+                "-o=/tmp/my jar.jar",
+                "--name",
+                "{literal}",
+                "say \"done\"",
+                "",
+                "{x}",
+            ]
+        );
+    }
+
+    /// Tests that invalid templates and missing arguments are rejected
+    #[test]
+    fn test_template_validation() {
+        let template =
+            CommandTemplate::parse("java -jar ss.jar -i {0} -o {3} -m {mappings}").unwrap();
+        assert!(matches!(
+            template.validate(3, &["mappings"]),
+            Err(CommandError::MissingArgument(index)) if index == "3"
+        ));
+        assert!(matches!(
+            template.validate(4, &[]),
+            Err(CommandError::MissingArgument(name)) if name == "mappings"
+        ));
+        assert!(template
+            .validate(4, &["mappings"])
+            .is_ok());
+
+        for invalid in ["java \"unclosed", "java {0", "java }", "java {a-b}"] {
+            assert!(matches!(
+                CommandTemplate::parse(invalid),
+                Err(CommandError::InvalidTemplate(_))
+            ));
+        }
+        assert!(matches!(
+            CommandTemplate::parse("  "),
+            Err(CommandError::MissingCommand)
+        ));
     }
 }
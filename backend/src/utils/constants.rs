@@ -2,13 +2,26 @@
 pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 /// The User-Agent header passed when making requests (Jars/{VERSION})
 pub const USER_AGENT: &str = concat!("Jars/", env!("CARGO_PKG_VERSION"));
-/// The url containing the spigot versions.
+/// The default url containing the spigot versions.
 pub const SPIGOT_VERSIONS_URL: &str = "https://hub.spigotmc.org/versions/";
 /// The spigot build tools version that we have feature parody with
 pub const PARODY_BUILD_TOOLS_VERSION: u16 = 149;
-/// The maven version number
-pub const MAVEN_VERSION: &str = "apache-maven-3.6.0";
-/// The download url for the current maven version
+/// The default version of the managed maven distribution
+pub const MAVEN_VERSION: &str = "3.6.0";
+/// The default download url for the current maven version
 pub const MAVEN_DOWNLOAD_URL: &str = "https://static.spigotmc.org/maven/";
-/// The url for Minecraft's version manifest which contains the list of Minecraft versions
+/// The default url for Minecraft's version manifest which contains the list of Minecraft versions
 pub const MANIFEST_URL: &str = "https://launchermeta.mojang.com/mc/game/version_manifest.json";
+/// The default url for the directory containing legacy Minecraft server jars
+pub const MINECRAFT_DOWNLOAD_URL: &str = "https://s3.amazonaws.com/Minecraft.Download/versions/";
+/// The default git url for the BuildData repository
+pub const BUILD_DATA_REPO_URL: &str = "https://hub.spigotmc.org/stash/scm/spigot/builddata.git";
+/// The default url of the raw file browser for the BuildData repository
+pub const BUILD_DATA_RAW_URL: &str =
+    "https://hub.spigotmc.org/stash/projects/SPIGOT/repos/builddata/raw/";
+/// The default git url for the Spigot repository
+pub const SPIGOT_REPO_URL: &str = "https://hub.spigotmc.org/stash/scm/spigot/spigot.git";
+/// The default git url for the Bukkit repository
+pub const BUKKIT_REPO_URL: &str = "https://hub.spigotmc.org/stash/scm/spigot/bukkit.git";
+/// The default git url for the CraftBukkit repository
+pub const CRAFT_BUKKIT_REPO_URL: &str = "https://hub.spigotmc.org/stash/scm/spigot/craftbukkit.git";
@@ -0,0 +1,282 @@
+use crate::utils::constants::{
+    BUILD_DATA_RAW_URL, BUILD_DATA_REPO_URL, BUKKIT_REPO_URL, CRAFT_BUKKIT_REPO_URL, MANIFEST_URL,
+    MAVEN_DOWNLOAD_URL, MINECRAFT_DOWNLOAD_URL, SPIGOT_REPO_URL, SPIGOT_VERSIONS_URL,
+};
+use log::info;
+use serde::Deserialize;
+use std::{env, fs::read, io, sync::OnceLock};
+use thiserror::Error;
+
+/// The endpoints loaded from the environment / configuration file
+static ENDPOINTS: OnceLock<Endpoints> = OnceLock::new();
+
+/// Environment variable containing the path to an optional JSON
+/// file that endpoints are loaded from
+pub const ENDPOINTS_FILE_KEY: &str = "ENDPOINTS_FILE";
+
+#[derive(Debug, Error)]
+pub enum EndpointsError {
+    #[error("Unable to read endpoints file: {0}")]
+    IO(#[from] io::Error),
+    #[error("Unable to parse endpoints file: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("Invalid url rewrite \"{0}\" (expected FROM=TO)")]
+    InvalidRewrite(String),
+}
+
+/// Rewrite rule which replaces the `from` prefix of a url with
+/// the `to` prefix. Used to point upstream urls at mirrors
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct UrlRewrite {
+    pub from: String,
+    pub to: String,
+}
+
+/// The upstream endpoints that are used when building. Each of
+/// these can be replaced to build against mirrors or local copies
+/// (git urls can be `file://` urls or local paths)
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Endpoints {
+    /// Git url for the BuildData repository
+    pub build_data_url: String,
+    /// Url of the raw file browser for the BuildData repository. Files
+    /// are fetched at a ref from `{url}{path}?at={ref}`
+    pub build_data_raw_url: String,
+    /// Git url for the Spigot repository
+    pub spigot_url: String,
+    /// Git url for the Bukkit repository
+    pub bukkit_url: String,
+    /// Git url for the CraftBukkit repository
+    pub craft_bukkit_url: String,
+    /// Url of the directory containing the spigot version JSONs
+    pub spigot_versions_url: String,
+    /// Url of the directory containing the maven distributions
+    pub maven_download_url: String,
+    /// Url of Minecraft's version manifest
+    pub manifest_url: String,
+    /// Url of the directory containing legacy server jars which
+    /// is used when BuildData doesn't provide a server url
+    pub minecraft_download_url: String,
+    /// Rewrites applied to these endpoints and to the urls provided
+    /// by upstream (e.g. the server and mappings urls from BuildData)
+    pub rewrites: Vec<UrlRewrite>,
+}
+
+impl Default for Endpoints {
+    /// Creates the default endpoints which point at the
+    /// official Spigot and Mojang servers
+    fn default() -> Self {
+        Self {
+            build_data_url: BUILD_DATA_REPO_URL.to_string(),
+            build_data_raw_url: BUILD_DATA_RAW_URL.to_string(),
+            spigot_url: SPIGOT_REPO_URL.to_string(),
+            bukkit_url: BUKKIT_REPO_URL.to_string(),
+            craft_bukkit_url: CRAFT_BUKKIT_REPO_URL.to_string(),
+            spigot_versions_url: SPIGOT_VERSIONS_URL.to_string(),
+            maven_download_url: MAVEN_DOWNLOAD_URL.to_string(),
+            manifest_url: MANIFEST_URL.to_string(),
+            minecraft_download_url: MINECRAFT_DOWNLOAD_URL.to_string(),
+            rewrites: Vec::new(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// Loads the endpoints from the process environment. See
+    /// `from_lookup` for the variables used
+    pub fn from_env() -> Result<Self, EndpointsError> {
+        Self::from_lookup(|key| env::var(key).ok())
+    }
+
+    /// Loads the endpoints using the provided `lookup` for environment
+    /// variables. Values are loaded from the JSON file at `ENDPOINTS_FILE`
+    /// if present then replaced by any of the individual variables that
+    /// are set (e.g. `SPIGOT_VERSIONS_URL`, `BUILD_DATA_URL`). Rewrites in
+    /// `URL_REWRITES` are separated by `;` and formatted as `FROM=TO`.
+    pub fn from_lookup<F: Fn(&str) -> Option<String>>(lookup: F) -> Result<Self, EndpointsError> {
+        let mut endpoints = match lookup(ENDPOINTS_FILE_KEY) {
+            Some(path) => {
+                info!("Loading endpoints from {path:?}");
+                let contents = read(path)?;
+                serde_json::from_slice::<Endpoints>(&contents)?
+            }
+            None => Endpoints::default(),
+        };
+
+        let overrides = [
+            ("BUILD_DATA_URL", &mut endpoints.build_data_url),
+            ("BUILD_DATA_RAW_URL", &mut endpoints.build_data_raw_url),
+            ("SPIGOT_URL", &mut endpoints.spigot_url),
+            ("BUKKIT_URL", &mut endpoints.bukkit_url),
+            ("CRAFT_BUKKIT_URL", &mut endpoints.craft_bukkit_url),
+            ("SPIGOT_VERSIONS_URL", &mut endpoints.spigot_versions_url),
+            ("MAVEN_DOWNLOAD_URL", &mut endpoints.maven_download_url),
+            ("MANIFEST_URL", &mut endpoints.manifest_url),
+            (
+                "MINECRAFT_DOWNLOAD_URL",
+                &mut endpoints.minecraft_download_url,
+            ),
+        ];
+        for (key, value) in overrides {
+            if let Some(url) = lookup(key) {
+                *value = url;
+            }
+        }
+
+        if let Some(rewrites) = lookup("URL_REWRITES") {
+            for rewrite in rewrites
+                .split(';')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+            {
+                let (from, to) = rewrite
+                    .split_once('=')
+                    .ok_or_else(|| EndpointsError::InvalidRewrite(rewrite.to_string()))?;
+                endpoints
+                    .rewrites
+                    .push(UrlRewrite {
+                        from: from.to_string(),
+                        to: to.to_string(),
+                    });
+            }
+        }
+
+        endpoints.apply_rewrites();
+        Ok(endpoints)
+    }
+
+    /// Applies the rewrites to each of the configured endpoints
+    fn apply_rewrites(&mut self) {
+        let values = [
+            &mut self.build_data_url,
+            &mut self.build_data_raw_url,
+            &mut self.spigot_url,
+            &mut self.bukkit_url,
+            &mut self.craft_bukkit_url,
+            &mut self.spigot_versions_url,
+            &mut self.maven_download_url,
+            &mut self.manifest_url,
+            &mut self.minecraft_download_url,
+        ];
+        for value in values {
+            *value = rewrite_url(&self.rewrites, value);
+        }
+    }
+
+    /// Rewrites the provided url using the rewrite with the longest
+    /// matching prefix. Urls without a matching rewrite are unchanged
+    pub fn rewrite(&self, url: &str) -> String {
+        rewrite_url(&self.rewrites, url)
+    }
+}
+
+/// Rewrites the provided url using the rewrite from `rewrites`
+/// with the longest matching prefix
+fn rewrite_url(rewrites: &[UrlRewrite], url: &str) -> String {
+    let rewrite = rewrites
+        .iter()
+        .filter(|rewrite| url.starts_with(&rewrite.from))
+        .max_by_key(|rewrite| rewrite.from.len());
+    match rewrite {
+        Some(rewrite) => format!("{}{}", rewrite.to, &url[rewrite.from.len()..]),
+        None => url.to_string(),
+    }
+}
+
+/// Loads the global endpoints from the environment. This is called on
+/// startup so that configuration errors are reported before building.
+/// Endpoints that were already loaded are returned as is
+pub fn init_endpoints() -> Result<&'static Endpoints, EndpointsError> {
+    if let Some(endpoints) = ENDPOINTS.get() {
+        return Ok(endpoints);
+    }
+    let endpoints = Endpoints::from_env()?;
+    Ok(ENDPOINTS.get_or_init(|| endpoints))
+}
+
+/// Retrieves the global endpoints loaded by `init_endpoints`. The
+/// default endpoints are used if they were never loaded
+pub fn endpoints() -> &'static Endpoints {
+    ENDPOINTS.get_or_init(Endpoints::default)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::constants::MANIFEST_URL;
+    use crate::utils::endpoints::{Endpoints, UrlRewrite};
+    use crate::utils::testing::test_dir;
+    use std::collections::HashMap;
+    use std::fs::write;
+
+    /// Tests that individual variables override the defaults and that
+    /// rewrites are applied to the configured endpoints
+    #[test]
+    fn test_env_overrides() {
+        let vars = HashMap::from([
+            ("BUILD_DATA_URL", "file:///srv/git/builddata.git"),
+            (
+                "URL_REWRITES",
+                "https://hub.spigotmc.org/=http://mirror.local/spigot/;https://hub.spigotmc.org/stash/=http://mirror.local/stash/",
+            ),
+        ]);
+        let endpoints = Endpoints::from_lookup(|key| {
+            vars.get(key)
+                .map(|value| value.to_string())
+        })
+        .unwrap();
+
+        assert_eq!(endpoints.build_data_url, "file:///srv/git/builddata.git");
+        assert_eq!(
+            endpoints.spigot_url,
+            "http://mirror.local/stash/scm/spigot/spigot.git"
+        );
+        assert_eq!(
+            endpoints.spigot_versions_url,
+            "http://mirror.local/spigot/versions/"
+        );
+        assert_eq!(endpoints.manifest_url, MANIFEST_URL);
+    }
+
+    /// Tests loading endpoints from a JSON file with environment
+    /// variables taking priority
+    #[test]
+    fn test_file() {
+        let path = test_dir("endpoints").join("endpoints.json");
+        write(
+            &path,
+            r#"{
+                "manifest_url": "http://localhost/manifest.json",
+                "spigot_versions_url": "http://localhost/versions/",
+                "rewrites": [{ "from": "https://piston-data.mojang.com/", "to": "http://localhost/mojang/" }]
+            }"#,
+        )
+        .unwrap();
+        let path = path
+            .to_string_lossy()
+            .to_string();
+        let vars = HashMap::from([
+            ("ENDPOINTS_FILE", path.as_str()),
+            ("SPIGOT_VERSIONS_URL", "http://other/versions/"),
+        ]);
+        let endpoints = Endpoints::from_lookup(|key| {
+            vars.get(key)
+                .map(|value| value.to_string())
+        })
+        .unwrap();
+
+        assert_eq!(endpoints.manifest_url, "http://localhost/manifest.json");
+        assert_eq!(endpoints.spigot_versions_url, "http://other/versions/");
+        assert_eq!(
+            endpoints.rewrites,
+            vec![UrlRewrite {
+                from: "https://piston-data.mojang.com/".to_string(),
+                to: "http://localhost/mojang/".to_string()
+            }]
+        );
+        assert_eq!(
+            endpoints.rewrite("https://piston-data.mojang.com/v1/objects/abc/server.jar"),
+            "http://localhost/mojang/v1/objects/abc/server.jar"
+        );
+    }
+}
@@ -69,6 +69,20 @@ pub async fn move_file(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Resu
     Ok(())
 }
 
+/// Writes the contents to a temporary file next to the path then moves
+/// it to the path so readers never see partially written files
+pub async fn write_atomic(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_os_string();
+    name.push(".tmp");
+    let tmp = path.with_file_name(name);
+    write(&tmp, contents).await?;
+    move_file(&tmp, path).await
+}
+
 /// Moves the directory at the provided path to the other
 /// provided path. Deleting any existing files/directories.
 pub async fn move_directory(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
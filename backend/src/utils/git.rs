@@ -1,11 +1,19 @@
 use crate::build_tools::spigot::{SpigotVersion, VersionRefs};
+use crate::utils::endpoints::endpoints;
 use async_walkdir::WalkDir;
+use chrono::{DateTime, Utc};
 use futures::StreamExt;
-use git2::{BranchType, Diff, ObjectType, Oid, Repository, ResetType, Signature};
-use log::{info, warn};
+use git2::{
+    build::CheckoutBuilder, AutotagOption, BranchType, Delta, Diff, ErrorClass, ErrorCode,
+    FetchOptions, Index, IndexAddOption, IndexEntry, IndexTime, ObjectType, Oid, Repository,
+    ResetType, Signature, Sort, Time, Tree, Worktree, WorktreeAddOptions, WorktreePruneOptions,
+};
+use log::{debug, info, warn};
 use std::{
+    collections::HashMap,
+    env::current_dir,
     fmt::{Display, Formatter},
-    fs::remove_dir_all,
+    fs::{create_dir_all, remove_dir_all, remove_file, File, OpenOptions},
     io,
     path::{Path, PathBuf},
 };
@@ -26,8 +34,81 @@ pub enum RepoError {
     JoinError(#[from] JoinError),
     #[error("Failed expected commit")]
     ExpectedCommit,
+    #[error("Commit {0} could not be found on the remote")]
+    MissingCommit(String),
     #[error("Failed mappings ref")]
     MappingsRef,
+    #[error("Patch {0:?} is missing the blob information required for a three-way merge")]
+    MissingPreImage(String),
+    #[error("Repository has no working directory")]
+    MissingWorkdir,
+    #[error("Failed to apply patches:\n{0}")]
+    PatchConflicts(PatchReport),
+}
+
+/// Name of the remote repositories are fetched from
+const ORIGIN: &str = "origin";
+
+/// Name of the remote repositories reset by `reset_to_upstream`
+/// fetch their upstream commit from
+const UPSTREAM: &str = "upstream";
+
+/// Fetch depth which converts a shallow repository into a
+/// complete one (GIT_FETCH_DEPTH_UNSHALLOW)
+const FETCH_DEPTH_UNSHALLOW: i32 = i32::MAX;
+
+/// How conflicts encountered while falling back to a three-way
+/// merge for a patch are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictMode {
+    /// Stop at the first conflicting patch leaving the conflict
+    /// markers in the working tree
+    #[default]
+    Stop,
+    /// Skip any conflicting patches leaving the repository unchanged
+    /// and continue applying the remaining patches
+    Continue,
+}
+
+/// A patch which conflicted and the files that conflicted
+#[derive(Debug)]
+pub struct PatchConflict {
+    /// The file name of the patch
+    pub patch: String,
+    /// The paths of the files that conflicted
+    pub files: Vec<String>,
+}
+
+/// Report of the result of applying a directory of patches
+#[derive(Debug, Default)]
+pub struct PatchReport {
+    /// The number of patches that were applied (cleanly or merged)
+    pub applied: usize,
+    /// Patches that required a three-way merge
+    pub merged: Vec<String>,
+    /// Patches that had conflicts
+    pub conflicts: Vec<PatchConflict>,
+}
+
+impl Display for PatchReport {
+    /// Formats the report listing each conflicting patch
+    /// and the files that conflicted
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} patches applied ({} merged, {} conflicting)",
+            self.applied,
+            self.merged.len(),
+            self.conflicts.len()
+        )?;
+        for conflict in &self.conflicts {
+            write!(f, "\n  {}:", conflict.patch)?;
+            for file in &conflict.files {
+                write!(f, "\n    {file}")?;
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Enum representing the different know repositories that
@@ -58,13 +139,14 @@ impl Display for Repo {
 
 impl Repo {
     /// Mappings between the different repositories and their
-    /// corresponding git url
+    /// corresponding configured git url
     pub fn get_url(&self) -> &'static str {
+        let endpoints = endpoints();
         match self {
-            Self::BuildData => "https://hub.spigotmc.org/stash/scm/spigot/builddata.git",
-            Self::Spigot => "https://hub.spigotmc.org/stash/scm/spigot/spigot.git",
-            Self::Bukkit => "https://hub.spigotmc.org/stash/scm/spigot/bukkit.git",
-            Self::CraftBukkit => "https://hub.spigotmc.org/stash/scm/spigot/craftbukkit.git",
+            Self::BuildData => &endpoints.build_data_url,
+            Self::Spigot => &endpoints.spigot_url,
+            Self::Bukkit => &endpoints.bukkit_url,
+            Self::CraftBukkit => &endpoints.craft_bukkit_url,
         }
     }
 
@@ -79,38 +161,154 @@ impl Repo {
         }
     }
 
-    /// Retrieves the repository for the provided url and stores
-    /// it at the provided path or simply loads it if it already
-    /// exists. If the existing repository encounters an error
-    /// it will be deleted and cloned again.
-    fn get_repository(url: &'static str, path: &Path) -> Result<Repository, RepoError> {
+    /// The depth to use when fetching the pinned commit for this
+    /// repository. CraftBukkit and Spigot have large histories and only
+    /// the pinned commit is needed so they are fetched shallow. BuildData
+    /// requires its history for the mappings reference.
+    pub fn fetch_depth(&self) -> Option<i32> {
+        match self {
+            Self::CraftBukkit | Self::Spigot => Some(1),
+            Self::BuildData | Self::Bukkit => None,
+        }
+    }
+
+    /// Retrieves the repository for the provided url stored at the
+    /// provided path or creates an empty repository with the url as
+    /// its origin remote if there isn't one. The commits are fetched
+    /// later by `reset_to_commit`. Existing repositories are only
+    /// deleted if they are corrupted, anything else that can't be
+    /// opened as a repository is an error.
+    fn get_repository(url: &str, path: &Path) -> Result<Repository, RepoError> {
         if path.exists() {
-            let git_path = path.join(".git");
-            if git_path.exists() && git_path.is_dir() {
-                if let Ok(repository) = Repository::open(path) {
+            match Repository::open(path) {
+                Ok(repository) => {
+                    Self::ensure_origin(&repository, url)?;
                     return Ok(repository);
                 }
+                Err(err) => {
+                    let err = RepoError::from(err);
+                    if !Self::is_corrupted(&err) {
+                        return Err(err);
+                    }
+                    warn!("Repository at {path:?} is corrupted recloning: {err}");
+                    remove_dir_all(path)?;
+                }
+            }
+        }
+        let repository = Repository::init(path)?;
+        repository.remote(ORIGIN, url)?;
+        Ok(repository)
+    }
+
+    /// Ensures the origin remote of the provided `repo` points
+    /// to the provided `url`
+    fn ensure_origin(repo: &Repository, url: &str) -> Result<(), RepoError> {
+        match repo.find_remote(ORIGIN) {
+            Ok(remote) => {
+                if remote.url() != Some(url) {
+                    repo.remote_set_url(ORIGIN, url)?;
+                }
+            }
+            Err(_) => {
+                repo.remote(ORIGIN, url)?;
             }
-            remove_dir_all(path)?;
         }
-        Ok(Repository::clone(url, path)?)
+        Ok(())
     }
 
     /// Resets the provided `repo` to the commit that the
-    /// `reference` reffers to.
-    fn reset_to_commit(repo: &Repository, reference: &str) -> Result<(), RepoError> {
+    /// `reference` reffers to. The commit is fetched from
+    /// origin if its not present locally.
+    fn reset_to_commit(
+        repo: &Repository,
+        reference: &str,
+        depth: Option<i32>,
+    ) -> Result<(), RepoError> {
         let ref_id = Oid::from_str(reference)?;
+        if repo
+            .find_commit(ref_id)
+            .is_err()
+        {
+            Self::fetch_commit(repo, ref_id, depth)?;
+        }
         let object = repo.find_object(ref_id, Some(ObjectType::Commit))?;
         let commit = object.peel(ObjectType::Commit)?;
         repo.reset(&commit, ResetType::Hard, None)?;
         Ok(())
     }
 
+    /// Fetches the commit with the provided `id` from origin. The commit is
+    /// requested directly first (with the provided `depth`) which requires
+    /// the remote to allow fetching unadvertised commits. If that fails all
+    /// the branches and tags are fetched instead.
+    fn fetch_commit(repo: &Repository, id: Oid, depth: Option<i32>) -> Result<(), RepoError> {
+        let mut remote = repo.find_remote(ORIGIN)?;
+        info!(
+            "Fetching commit {id} from {}",
+            remote
+                .url()
+                .unwrap_or_default()
+        );
+
+        let mut options = FetchOptions::new();
+        if let Some(depth) = depth {
+            options.depth(depth);
+        }
+        match remote.fetch(&[id.to_string()], Some(&mut options), None) {
+            Ok(_) if repo.find_commit(id).is_ok() => return Ok(()),
+            Ok(_) => debug!("Fetching commit {id} directly did not provide the commit"),
+            Err(err) => debug!("Unable to fetch commit {id} directly: {err}"),
+        }
+
+        let mut options = FetchOptions::new();
+        options.download_tags(AutotagOption::All);
+        if repo.is_shallow() {
+            // The commit may be anywhere in the history so the
+            // repository must be unshallowed
+            options.depth(FETCH_DEPTH_UNSHALLOW);
+        }
+        remote.fetch(
+            &["+refs/heads/*:refs/remotes/origin/*"],
+            Some(&mut options),
+            None,
+        )?;
+
+        if repo.find_commit(id).is_err() {
+            return Err(RepoError::MissingCommit(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Checks whether the provided error was caused by a corrupted
+    /// repository (i.e. broken object database or index)
+    fn is_corrupted(err: &RepoError) -> bool {
+        match err {
+            RepoError::GitError(err) => {
+                err.code() != ErrorCode::NotFound
+                    && matches!(
+                        err.class(),
+                        ErrorClass::Odb | ErrorClass::Zlib | ErrorClass::Index
+                    )
+            }
+            _ => false,
+        }
+    }
+
     pub fn open(path: &Path) -> Result<Repository, RepoError> {
         Ok(Repository::open(path)?)
     }
 
-    pub async fn apply_patches(repo: &Repository, patches: &Path) -> Result<(), RepoError> {
+    /// Applies all the `.patch` files in the `patches` directory to the
+    /// provided `repo` in order of their file names committing each patch
+    /// (like `git am`). Patches that no longer apply cleanly fall back to
+    /// a three-way merge using the pre-image blobs referenced by the patch
+    /// (like `git am -3`). Conflicts are handled according to `mode`
+    pub async fn apply_patches(
+        repo: &Repository,
+        patches: &Path,
+        mode: ConflictMode,
+    ) -> Result<PatchReport, RepoError> {
+        let mut patch_paths = Vec::new();
         let mut walk = WalkDir::new(patches);
         while let Some(entry) = walk.next().await {
             let entry = entry?;
@@ -120,110 +318,712 @@ impl Repo {
                 .as_ref()
                 .ends_with(".patch")
             {
-                let patch_path = entry.path();
-                let contents = match read(&patch_path).await {
-                    Ok(value) => value,
-                    Err(err) => {
-                        warn!(
-                            "Unable to apply patch at {patch_path:?} (Unable to read file): {err}"
-                        );
-                        continue;
+                patch_paths.push(entry.path());
+            }
+        }
+        patch_paths.sort_by(|a, b| {
+            a.file_name()
+                .cmp(&b.file_name())
+        });
+
+        let mut report = PatchReport::default();
+        let mut deepened = false;
+        for patch_path in patch_paths {
+            let name = patch_path
+                .file_name()
+                .map(|value| {
+                    value
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .unwrap_or_default();
+            let contents = match read(&patch_path).await {
+                Ok(value) => value,
+                Err(err) => {
+                    warn!("Unable to apply patch at {patch_path:?} (Unable to read file): {err}");
+                    continue;
+                }
+            };
+            let contents = String::from_utf8_lossy(&contents).to_string();
+            let contents = contents.replace("\r\n", "\n");
+
+            let diff = Diff::from_buffer(contents.as_bytes())?;
+            if let Err(err) = repo.apply(&diff, git2::ApplyLocation::Both, None) {
+                warn!(
+                    "Patch {name:?} did not apply cleanly ({err}), falling back to three-way merge"
+                );
+                let files = match Self::apply_three_way(repo, &name, &diff, &contents, mode) {
+                    // The pre-image blobs may be missing from a shallow upstream
+                    Err(err @ RepoError::MissingPreImage(_)) if !deepened => {
+                        deepened = true;
+                        if !Self::deepen_upstream(repo)? {
+                            return Err(err);
+                        }
+                        Self::apply_three_way(repo, &name, &diff, &contents, mode)?
                     }
+                    result => result?,
                 };
-                let contents = String::from_utf8_lossy(&contents).to_string();
-                let contents = contents.replace("\r\n", "\n");
-
-                let diff = Diff::from_buffer(contents.as_bytes())?;
+                if files.is_empty() {
+                    info!("Applied spigot patch at {name:?} using three-way merge");
+                    report
+                        .merged
+                        .push(name.clone());
+                } else {
+                    for file in &files {
+                        warn!("Conflict in {file} while applying patch {name:?}");
+                    }
+                    report
+                        .conflicts
+                        .push(PatchConflict {
+                            patch: name.clone(),
+                            files,
+                        });
+                    if mode == ConflictMode::Stop {
+                        return Err(RepoError::PatchConflicts(report));
+                    }
+                    warn!("Skipping conflicting patch {name:?}");
+                    continue;
+                }
+            } else {
                 info!("Applied spigot patch at {name:?}");
-                repo.apply(&diff, git2::ApplyLocation::Both, None)?;
             }
+            Self::commit_patch(repo, &contents)?;
+            report.applied += 1;
         }
+        Ok(report)
+    }
+
+    /// Commits the current index of the provided `repo` on top of HEAD using
+    /// the author, date and subject from the headers of the provided patch
+    fn commit_patch(repo: &Repository, contents: &str) -> Result<(), RepoError> {
+        let mut author = None;
+        let mut date = None;
+        let mut subject: Option<String> = None;
+        let mut lines = contents.lines();
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("From: ") {
+                author = value
+                    .rsplit_once('<')
+                    .map(|(name, email)| {
+                        (
+                            name.trim().to_string(),
+                            email
+                                .trim_end_matches('>')
+                                .to_string(),
+                        )
+                    });
+            } else if let Some(value) = line.strip_prefix("Date: ") {
+                date = DateTime::parse_from_rfc2822(value.trim())
+                    .ok()
+                    .map(|value| {
+                        Time::new(
+                            value.timestamp(),
+                            value
+                                .offset()
+                                .local_minus_utc()
+                                / 60,
+                        )
+                    });
+            } else if let Some(value) = line.strip_prefix("Subject: ") {
+                let value = match value.strip_prefix("[PATCH") {
+                    Some(value) => value
+                        .split_once(']')
+                        .map(|(_, value)| value)
+                        .unwrap_or(value),
+                    None => value,
+                };
+                subject = Some(value.trim().to_string());
+            } else if line.starts_with(' ') {
+                // Folded continuation of the subject header
+                if let Some(subject) = &mut subject {
+                    subject.push_str(line);
+                }
+            }
+        }
+
+        let mut message = subject.unwrap_or_default();
+        let body = lines
+            .take_while(|line| *line != "---")
+            .collect::<Vec<&str>>()
+            .join("\n");
+        if !body.trim().is_empty() {
+            message.push_str("\n\n");
+            message.push_str(body.trim());
+        }
+
+        let committer = Signature::now("BuildTools", "buildtools@example.com")?;
+        let author = match (&author, &date) {
+            (Some((name, email)), Some(date)) => Signature::new(name, email, date)?,
+            (Some((name, email)), None) => Signature::now(name, email)?,
+            (None, _) => committer.clone(),
+        };
+        let tree = repo.index()?.write_tree()?;
+        let tree = repo.find_tree(tree)?;
+        let parent = repo
+            .head()?
+            .peel_to_commit()?;
+        repo.commit(
+            Some("HEAD"),
+            &author,
+            &committer,
+            &message,
+            &tree,
+            &[&parent],
+        )?;
         Ok(())
     }
 
-    pub fn create_patched_branch(repo: &Repository) -> Result<(), RepoError> {
-        const BRANCH_NAME: &str = "patched";
+    /// Applies the provided `diff` by performing a three-way merge between
+    /// the pre-image blobs named in the patch `index` lines, the current
+    /// index of the repository and the pre-image with the patch applied.
+    /// Returns the paths of any files that conflicted. When conflicts are
+    /// continued past the repository is left unchanged.
+    fn apply_three_way(
+        repo: &Repository,
+        name: &str,
+        diff: &Diff,
+        contents: &str,
+        mode: ConflictMode,
+    ) -> Result<Vec<String>, RepoError> {
+        let pre_images = Self::parse_pre_images(contents);
+
+        // Build the tree the patch was originally created against
+        let mut base_index = Index::new()?;
+        let mut paths = Vec::new();
+        for delta in diff.deltas() {
+            for path in [delta.old_file().path(), delta.new_file().path()]
+                .into_iter()
+                .flatten()
+            {
+                if !paths
+                    .iter()
+                    .any(|value| value == path)
+                {
+                    paths.push(path.to_path_buf());
+                }
+            }
+
+            if delta.status() == Delta::Added {
+                continue;
+            }
+
+            let old_file = delta.old_file();
+            let path = old_file
+                .path()
+                .ok_or_else(|| RepoError::MissingPreImage(name.to_string()))?;
+            let abbrev = pre_images
+                .get(
+                    path.to_string_lossy()
+                        .as_ref(),
+                )
+                .ok_or_else(|| RepoError::MissingPreImage(name.to_string()))?;
+            let object = repo
+                .revparse_single(abbrev)
+                .map_err(|_| RepoError::MissingPreImage(name.to_string()))?;
+            if object.kind() != Some(ObjectType::Blob) {
+                return Err(RepoError::MissingPreImage(name.to_string()));
+            }
+            base_index.add(&IndexEntry {
+                ctime: IndexTime::new(0, 0),
+                mtime: IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: u32::from(old_file.mode()),
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id: object.id(),
+                flags: 0,
+                flags_extended: 0,
+                path: path
+                    .to_string_lossy()
+                    .as_bytes()
+                    .to_vec(),
+            })?;
+        }
+
+        let base_tree = base_index.write_tree_to(repo)?;
+        let base_tree = repo.find_tree(base_tree)?;
+
+        let their_tree = repo
+            .apply_to_tree(&base_tree, diff, None)?
+            .write_tree_to(repo)?;
+        let their_tree = repo.find_tree(their_tree)?;
+
+        let mut repo_index = repo.index()?;
+        let our_tree = repo_index.write_tree()?;
+        let our_tree = repo.find_tree(our_tree)?;
+
+        let mut merged = repo.merge_trees(&base_tree, &our_tree, &their_tree, None)?;
+
+        let mut conflicts = Vec::new();
+        for conflict in merged.conflicts()? {
+            let conflict = conflict?;
+            let entry = conflict
+                .our
+                .as_ref()
+                .or(conflict.their.as_ref())
+                .or(conflict.ancestor.as_ref());
+            if let Some(entry) = entry {
+                let path = String::from_utf8_lossy(&entry.path).to_string();
+                conflicts.push(path);
+            }
+        }
+
+        if mode == ConflictMode::Continue && !conflicts.is_empty() {
+            return Ok(conflicts);
+        }
+
+        let mut checkout = CheckoutBuilder::new();
+        checkout
+            .force()
+            .allow_conflicts(true)
+            .conflict_style_merge(true)
+            .update_index(false);
+        for path in &paths {
+            checkout.path(path);
+        }
+        repo.checkout_index(Some(&mut merged), Some(&mut checkout))?;
+
+        let workdir = repo
+            .workdir()
+            .ok_or(RepoError::MissingWorkdir)?;
+        for path in &paths {
+            let conflicted = conflicts
+                .iter()
+                .any(|value| Path::new(value) == path);
+            if conflicted {
+                continue;
+            }
+            if merged
+                .get_path(path, 0)
+                .is_some()
+            {
+                repo_index.add_path(path)?;
+            } else {
+                let full_path = workdir.join(path);
+                if full_path.exists() {
+                    remove_file(full_path)?;
+                }
+                repo_index.remove_path(path)?;
+            }
+        }
+        repo_index.write()?;
+
+        Ok(conflicts)
+    }
+
+    /// Provides the history needed for the pre-image blobs of patches to the
+    /// provided `repo` that was reset by `reset_to_upstream`. A shallow
+    /// upstream mirror is unshallowed from its origin (under the mirror lock)
+    /// and the objects of the mirror are made readable from the `repo`.
+    /// Returns false if the `repo` doesn't have an upstream
+    fn deepen_upstream(repo: &Repository) -> Result<bool, RepoError> {
+        let Ok(remote) = repo.find_remote(UPSTREAM) else {
+            return Ok(false);
+        };
+        let Some(url) = remote.url() else {
+            return Ok(false);
+        };
+        let upstream_path = PathBuf::from(url);
+        let upstream = Repository::open(&upstream_path)?;
+
+        if upstream.is_shallow() {
+            let _lock = Self::lock_mirror(&upstream_path)?;
+            // Another build may have deepened the mirror while waiting
+            if upstream.is_shallow() {
+                info!("Deepening shallow history of {upstream_path:?}");
+                let id = repo
+                    .find_reference(&format!("refs/remotes/{UPSTREAM}/upstream"))?
+                    .peel_to_commit()?
+                    .id();
+                Self::fetch_commit(&upstream, id, Some(FETCH_DEPTH_UNSHALLOW))?;
+            }
+        }
+
+        let objects = upstream_path.join("objects");
+        repo.odb()?
+            .add_disk_alternate(&objects.to_string_lossy())?;
+        Ok(true)
+    }
 
-        if let Ok(mut branch) = repo.find_branch(BRANCH_NAME, BranchType::Local) {
-            // Delete existing branch
-            branch.delete()?;
+    /// Parses the abbreviated pre-image blob ids from the `index` lines
+    /// of a patch mapped to the path of the file they belong to
+    fn parse_pre_images(contents: &str) -> HashMap<String, String> {
+        let mut pre_images = HashMap::new();
+        let mut current = None;
+        for line in contents.lines() {
+            if let Some(value) = line.strip_prefix("index ") {
+                current = value
+                    .split("..")
+                    .next()
+                    .map(|value| value.to_string());
+            } else if let Some(path) = line.strip_prefix("--- a/") {
+                if let Some(abbrev) = current.take() {
+                    pre_images.insert(path.to_string(), abbrev);
+                }
+            } else if line.starts_with("diff --git ") {
+                current = None;
+            }
         }
+        pre_images
+    }
+
+    /// Commits the patched decompiled sources (src/main/java/net) of the
+    /// provided CraftBukkit `repo` onto the "patched" branch without moving
+    /// HEAD or touching the working tree. This is the branch that the
+    /// Spigot-Server is assembled from. Returns the name of the branch.
+    pub fn create_patched_branch(repo: &Repository) -> Result<String, RepoError> {
+        let branch_name = Self::local_branch_name(repo, "patched")?;
 
         let commit = repo
             .head()?
             .peel_to_commit()?;
 
-        let branch = repo.branch(BRANCH_NAME, &commit, true)?;
+        let mut index = repo.index()?;
+        index.read_tree(&commit.tree()?)?;
+        index.add_all(["src/main/java/net"], IndexAddOption::DEFAULT, None)?;
+        let tree = index.write_tree()?;
+        // Discard the in memory changes to the index
+        index.read(true)?;
+        let tree = repo.find_tree(tree)?;
+
         let signature = Signature::now("BuildTools", "buildtools@example.com")?;
-        let message = "";
-        let tree_builder = repo.treebuilder(None)?;
+        let message = format!(
+            "CraftBukkit $ {}",
+            Utc::now().format("%a %b %d %H:%M:%S UTC %Y")
+        );
+        let new_commit = repo.commit(None, &signature, &signature, &message, &tree, &[&commit])?;
+        let new_commit = repo.find_commit(new_commit)?;
+        repo.branch(&branch_name, &new_commit, true)?;
 
-        let tree = tree_builder.write()?;
-        let tree = repo.find_tree(tree)?;
+        Ok(branch_name)
+    }
 
-        let new_commit = repo.commit(
-            Some(BRANCH_NAME),
-            &signature,
-            &signature,
-            message,
-            &tree,
-            &[&commit],
-        )?;
+    /// Branches are shared between all the worktrees of a mirror so
+    /// branch names created for worktrees are suffixed with the name
+    /// of the worktree to prevent parallel builds from colliding
+    fn local_branch_name(repo: &Repository, name: &str) -> Result<String, RepoError> {
+        if !repo.is_worktree() {
+            return Ok(name.to_string());
+        }
+        let worktree = Worktree::open_from_repository(repo)?;
+        Ok(match worktree.name() {
+            Some(worktree_name) => format!("{name}-{worktree_name}"),
+            None => name.to_string(),
+        })
+    }
+
+    /// Resets the repository at `target` to a clean copy of the `branch` of
+    /// the local `upstream` repository (like Spigot's applyPatches.sh). The
+    /// commit `branch` points to is fetched through an "upstream" remote in
+    /// the target. The target is cloned if it is missing and any untracked
+    /// files are removed so that no files from previous builds remain
+    pub async fn reset_to_upstream(
+        upstream: &Path,
+        branch: &str,
+        target: PathBuf,
+    ) -> Result<Repository, RepoError> {
+        let upstream = std::fs::canonicalize(upstream)?;
+        let branch = branch.to_string();
+        let repo = spawn_blocking(move || {
+            let upstream = Repository::open(&upstream)?;
+            let id = upstream
+                .revparse_single(&branch)?
+                .peel_to_commit()?
+                .id();
+
+            // Worktrees don't share the shallow roots of their mirror so
+            // the commit is fetched from the mirror itself
+            let upstream_path = Self::common_dir(&upstream)?;
+            let upstream_url = upstream_path.to_string_lossy();
+            let repo = Self::get_repository(&upstream_url, &target)?;
+
+            Self::reset_to_remote(&repo, UPSTREAM, &upstream_url, id)?;
+            Ok(repo)
+        } as Result<Repository, RepoError>)
+        .await??;
+        Ok(repo)
+    }
+
+    /// The common directory of the provided `repo` which for worktrees
+    /// is the mirror they were created from (git2 doesn't expose this)
+    fn common_dir(repo: &Repository) -> Result<PathBuf, RepoError> {
+        let path = repo.path();
+        if !repo.is_worktree() {
+            return Ok(path.to_path_buf());
+        }
+        let common = std::fs::read_to_string(path.join("commondir"))?;
+        Ok(path
+            .join(common.trim())
+            .canonicalize()?)
+    }
+
+    /// Replaces the `remote` of the provided `repo` with one pointing at `url`
+    /// then fetches the commit `id` from it storing it as the remotes upstream
+    /// branch and hard resets to the fetched commit removing any untracked
+    /// files
+    fn reset_to_remote(
+        repo: &Repository,
+        remote: &str,
+        url: &str,
+        id: Oid,
+    ) -> Result<(), RepoError> {
+        if repo
+            .find_remote(remote)
+            .is_ok()
+        {
+            repo.remote_delete(remote)?;
+        }
+        repo.remote(remote, url)?
+            .fetch(&[id.to_string()], None, None)?;
 
+        let commit = repo.find_commit(id)?;
+        let remote_ref = format!("refs/remotes/{remote}/upstream");
+        repo.reference(&remote_ref, id, true, "Fetched upstream")?;
+        repo.reset(commit.as_object(), ResetType::Hard, None)?;
+
+        let mut checkout = CheckoutBuilder::new();
+        checkout
+            .force()
+            .remove_untracked(true);
+        repo.checkout_head(Some(&mut checkout))?;
         Ok(())
     }
 
-    /// Does a revwalk on the repository and searches each commit
-    /// returning the SHA1 id of the commit found
-    pub fn get_mappings_reference(repo: &Repository) -> Result<String, RepoError> {
+    /// Does a revwalk on the repository from HEAD returning the SHA1 id of
+    /// the most recent commit that changed any of the provided `paths` (like
+    /// `git log -1 -- <paths>` which is used by BuildTools). A commit changed
+    /// the paths if their tree entries differ from those of every parent.
+    pub fn get_mappings_reference(
+        repo: &Repository,
+        paths: &[String],
+    ) -> Result<String, RepoError> {
         let mut rev_walk = repo.revwalk()?;
+        rev_walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
         rev_walk.push_head()?;
-        let mut count = 0;
-        loop {
-            if count > 20 {
-                break;
+        for id in rev_walk {
+            let commit = repo.find_commit(id?)?;
+            let entries = Self::get_path_entries(&commit.tree()?, paths);
+
+            let mut changed = true;
+            for parent in commit.parents() {
+                if Self::get_path_entries(&parent.tree()?, paths) == entries {
+                    changed = false;
+                    break;
+                }
             }
-            count += 1;
-            let id = match rev_walk.next() {
-                Some(id) => id?,
-                None => break,
-            };
-            let object = repo.find_object(id, Some(ObjectType::Commit))?;
-            let commit = object
-                .as_commit()
-                .ok_or(RepoError::ExpectedCommit)?;
-            let changed_mappings = commit
-                .tree()?
-                .iter()
-                .any(|value| {
-                    if let Some(name) = value.name() {
-                        name.eq("mappings")
-                    } else {
-                        false
-                    }
-                });
-            if changed_mappings {
-                let commit_id = commit.id();
-                let commit_hash = format!("{commit_id}");
-                return Ok(commit_hash);
+
+            // Root commits only change the paths if they add them
+            if changed
+                && (commit.parent_count() > 0
+                    || entries
+                        .iter()
+                        .any(Option::is_some))
+            {
+                return Ok(commit.id().to_string());
             }
         }
         Err(RepoError::MappingsRef)
     }
 
-    /// Sets up this repository by cloning / loading the
-    /// repository and resetting to the commit referenced
-    /// in `refs`
-    pub async fn setup(self, refs: &VersionRefs, path: PathBuf) -> Result<Repository, RepoError> {
+    /// Retrieves the object ids of the entries at each of the
+    /// provided `paths` in the `tree` (None if not present)
+    fn get_path_entries(tree: &Tree, paths: &[String]) -> Vec<Option<Oid>> {
+        paths
+            .iter()
+            .map(|path| {
+                tree.get_path(Path::new(path))
+                    .ok()
+                    .map(|entry| entry.id())
+            })
+            .collect()
+    }
+
+    /// The name of the directory the bare mirror for this
+    /// repository is stored in within the mirrors directory
+    pub fn mirror_name(&self) -> &'static str {
+        match self {
+            Self::BuildData => "builddata.git",
+            Self::Spigot => "spigot.git",
+            Self::Bukkit => "bukkit.git",
+            Self::CraftBukkit => "craftbukkit.git",
+        }
+    }
+
+    /// Retrieves the bare mirror repository for the provided url stored at
+    /// the provided path or creates an empty one with the url as its origin
+    /// if there isn't one. Mirrors that can't be opened are recreated.
+    fn get_mirror(url: &str, path: &Path) -> Result<Repository, RepoError> {
+        if path.exists() {
+            match Repository::open_bare(path) {
+                Ok(repository) => {
+                    Self::ensure_origin(&repository, url)?;
+                    return Ok(repository);
+                }
+                Err(err) => warn!("Unable to open mirror at {path:?} recreating: {err}"),
+            }
+            remove_dir_all(path)?;
+        }
+        let repository = Repository::init_bare(path)?;
+        repository.remote(ORIGIN, url)?;
+        Ok(repository)
+    }
+
+    /// Ensures the mirror at `mirror_path` contains the commit with the
+    /// provided `id` fetching it from the `url` if its missing. Corrupted
+    /// mirrors are deleted and fetched again.
+    fn update_mirror(
+        url: &str,
+        mirror_path: &Path,
+        id: Oid,
+        depth: Option<i32>,
+    ) -> Result<Repository, RepoError> {
+        let mirror = Self::get_mirror(url, mirror_path)?;
+        if mirror.find_commit(id).is_ok() {
+            return Ok(mirror);
+        }
+        match Self::fetch_commit(&mirror, id, depth) {
+            Ok(_) => Ok(mirror),
+            Err(err) if Self::is_corrupted(&err) => {
+                warn!("Mirror at {mirror_path:?} is corrupted recreating: {err}");
+                drop(mirror);
+                remove_dir_all(mirror_path)?;
+                let mirror = Self::get_mirror(url, mirror_path)?;
+                Self::fetch_commit(&mirror, id, depth)?;
+                Ok(mirror)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Retrieves the worktree of the `mirror` at the provided path creating
+    /// it at the commit with the provided `id` if its missing. Anything at
+    /// the path that isn't a worktree of the mirror is replaced.
+    fn get_worktree(mirror: &Repository, path: &Path, id: Oid) -> Result<Repository, RepoError> {
+        let name = Self::worktree_name(path)?;
+        let mirror_path = mirror.path().canonicalize()?;
+
+        if path.exists() {
+            if let Ok(repository) = Repository::open(path) {
+                let owned = repository.is_worktree()
+                    && repository
+                        .path()
+                        .canonicalize()
+                        .map(|value| value.starts_with(&mirror_path))
+                        .unwrap_or(false);
+                if owned {
+                    return Ok(repository);
+                }
+            }
+            remove_dir_all(path)?;
+        }
+
+        // Remove any stale registration for a worktree that was deleted
+        if let Ok(worktree) = mirror.find_worktree(&name) {
+            worktree.prune(Some(
+                WorktreePruneOptions::new()
+                    .valid(true)
+                    .locked(true)
+                    .working_tree(true),
+            ))?;
+        }
+
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        let commit = mirror.find_commit(id)?;
+        let branch = mirror.branch(&name, &commit, true)?;
+        let mut options = WorktreeAddOptions::new();
+        options.reference(Some(branch.get()));
+        let worktree = mirror.worktree(&name, path, Some(&options))?;
+        Ok(Repository::open_from_worktree(&worktree)?)
+    }
+
+    /// Creates a unique name for the worktree at the provided path
+    /// using its directory name and a hash of its absolute path
+    fn worktree_name(path: &Path) -> Result<String, RepoError> {
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            current_dir()?.join(path)
+        };
+        let file_name = path
+            .file_name()
+            .map(|value| value.to_string_lossy())
+            .unwrap_or_default();
+        let digest = md5::compute(
+            absolute
+                .to_string_lossy()
+                .as_bytes(),
+        );
+        let digest = format!("{digest:x}");
+        Ok(format!("{file_name}-{}", &digest[..8]))
+    }
+
+    /// Takes an exclusive lock on the `.lock` file next to the mirror at the
+    /// provided path which prevents multiple builds (including builds in
+    /// other processes) from modifying the mirror at the same time. The
+    /// lock is held until the returned file is dropped
+    fn lock_mirror(path: &Path) -> Result<File, RepoError> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lock_path)?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    /// Sets up a worktree of the mirror at `mirror_path` at the provided `path`
+    /// and resets it to the commit `reference`. The mirror is only fetched
+    /// from the `url` when the commit is missing.
+    fn setup_worktree(
+        url: &str,
+        depth: Option<i32>,
+        reference: &str,
+        mirror_path: &Path,
+        path: &Path,
+    ) -> Result<Repository, RepoError> {
+        let id = Oid::from_str(reference)?;
+        let repository = {
+            let _lock = Self::lock_mirror(mirror_path)?;
+            let mirror = Self::update_mirror(url, mirror_path, id, depth)?;
+            Self::get_worktree(&mirror, path, id)?
+        };
+        Self::reset_to_commit(&repository, reference, depth)?;
+        Ok(repository)
+    }
+
+    /// Sets up this repository as a worktree of the shared bare mirror of
+    /// the repository stored in `mirrors` and resets it to the commit
+    /// referenced in `refs`. The mirror is only fetched from when the
+    /// commit is missing so builds of different versions share objects
+    /// and don't clone the repository again.
+    pub async fn setup(
+        self,
+        refs: &VersionRefs,
+        mirrors: &Path,
+        path: PathBuf,
+    ) -> Result<Repository, RepoError> {
         let url = self.get_url();
+        let depth = self.fetch_depth();
         let reference = self
             .get_commit_ref(refs)
             .to_owned();
+        let mirror_path = mirrors.join(self.mirror_name());
         let repo = spawn_blocking(move || {
-            let repository = Self::get_repository(url, &path)?;
-            Self::reset_to_commit(&repository, &reference)?;
-            Ok(repository)
-        } as Result<Repository, RepoError>)
+            Self::setup_worktree(url, depth, &reference, &mirror_path, &path)
+        })
         .await??;
         Ok(repo)
     }
@@ -236,10 +1036,11 @@ pub struct Repositories {
     pub craft_bukkit: Repository,
 }
 
-/// Sets up the required repositories by downloading them and setting
-/// the correct commit ref this is done Asynchronously
+/// Sets up the required repositories as worktrees of the mirrors stored
+/// in `mirrors` and sets the correct commit ref this is done Asynchronously
 pub async fn setup_repositories(
     path: &Path,
+    mirrors: &Path,
     version: &SpigotVersion,
 ) -> Result<Repositories, RepoError> {
     let refs = &version.refs;
@@ -249,10 +1050,10 @@ pub async fn setup_repositories(
     );
 
     let (build_data_repo, spigot_repo, bukkit_repo, craftbukkit_repo) = try_join!(
-        Repo::BuildData.setup(refs, path.join("build_data")),
-        Repo::Spigot.setup(refs, path.join("spigot")),
-        Repo::Bukkit.setup(refs, path.join("bukkit")),
-        Repo::CraftBukkit.setup(refs, path.join("craftbukkit"))
+        Repo::BuildData.setup(refs, mirrors, path.join("build_data")),
+        Repo::Spigot.setup(refs, mirrors, path.join("spigot")),
+        Repo::Bukkit.setup(refs, mirrors, path.join("bukkit")),
+        Repo::CraftBukkit.setup(refs, mirrors, path.join("craftbukkit"))
     )?;
 
     info!("Repositories successfully setup");
@@ -268,8 +1069,594 @@ pub async fn setup_repositories(
 #[cfg(test)]
 mod test {
     use crate::build_tools::spigot::VersionRefs;
-    use crate::utils::git::Repo;
-    use std::path::Path;
+    use crate::utils::git::{ConflictMode, Repo, RepoError};
+    use crate::utils::testing::test_dir;
+    use git2::{DiffFormat, Repository, Signature};
+    use std::fs::{create_dir_all, read_to_string, write, File};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, Stdio};
+    use std::thread::spawn;
+
+    const ORIGINAL: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+
+    /// Creates a repository at a temporary path named `name` containing
+    /// a committed file.txt with the `ORIGINAL` contents and a patches
+    /// directory containing a patch which replaces "nine" with "NINE"
+    fn setup_patch_repo(name: &str) -> (PathBuf, Repository) {
+        let root = test_dir(name);
+        let repo_path = root.join("repo");
+        let patches_path = root.join("patches");
+        create_dir_all(&repo_path).unwrap();
+        create_dir_all(&patches_path).unwrap();
+
+        let repo = Repository::init(&repo_path).unwrap();
+        write(repo_path.join("file.txt"), ORIGINAL).unwrap();
+        commit_all(&repo);
+
+        write_patch(
+            &repo,
+            &patches_path.join("0001-Test.patch"),
+            &ORIGINAL.replace("nine", "NINE"),
+        );
+        (root, repo)
+    }
+
+    /// Writes a patch to `path` which replaces the file.txt at the HEAD
+    /// of the provided repository with the provided `contents`
+    fn write_patch(repo: &Repository, path: &Path, contents: &str) {
+        let base = repo
+            .head()
+            .unwrap()
+            .peel_to_tree()
+            .unwrap();
+        let blob = repo
+            .blob(contents.as_bytes())
+            .unwrap();
+        let mut builder = repo
+            .treebuilder(Some(&base))
+            .unwrap();
+        builder
+            .insert("file.txt", blob, 0o100644)
+            .unwrap();
+        let patched = builder.write().unwrap();
+        let patched = repo
+            .find_tree(patched)
+            .unwrap();
+
+        let diff = repo
+            .diff_tree_to_tree(Some(&base), Some(&patched), None)
+            .unwrap();
+        let mut patch = String::new();
+        diff.print(DiffFormat::Patch, |_, _, line| {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                patch.push(line.origin());
+            }
+            patch.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })
+        .unwrap();
+        write(path, patch).unwrap();
+    }
+
+    /// Replaces the contents of file.txt and commits the change
+    fn replace_contents(repo: &Repository, contents: &str) {
+        write(
+            repo.workdir()
+                .unwrap()
+                .join("file.txt"),
+            contents,
+        )
+        .unwrap();
+        commit_all(repo);
+    }
+
+    fn commit_all(repo: &Repository) {
+        let mut index = repo.index().unwrap();
+        index
+            .add_path(Path::new("file.txt"))
+            .unwrap();
+        index.write().unwrap();
+        let tree = index.write_tree().unwrap();
+        let tree = repo.find_tree(tree).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let parents = parent
+            .iter()
+            .collect::<Vec<_>>();
+        repo.commit(Some("HEAD"), &signature, &signature, "", &tree, &parents)
+            .unwrap();
+    }
+
+    /// Writes the provided files to the repository and commits them
+    /// returning the id of the commit
+    fn commit_files(repo: &Repository, files: &[(&str, &str)]) -> String {
+        let workdir = repo.workdir().unwrap();
+        let mut index = repo.index().unwrap();
+        for (path, contents) in files {
+            let file_path = workdir.join(path);
+            create_dir_all(file_path.parent().unwrap()).unwrap();
+            write(file_path, contents).unwrap();
+            index
+                .add_path(Path::new(path))
+                .unwrap();
+        }
+        index.write().unwrap();
+        let tree = index.write_tree().unwrap();
+        let tree = repo.find_tree(tree).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let parents = parent
+            .iter()
+            .collect::<Vec<_>>();
+        repo.commit(Some("HEAD"), &signature, &signature, "", &tree, &parents)
+            .unwrap()
+            .to_string()
+    }
+
+    /// Tests that the mappings reference is the last commit which
+    /// changed one of the mappings paths rather than HEAD
+    #[test]
+    fn test_mappings_reference() {
+        let root = test_dir("mappings-reference");
+        let repo = Repository::init(&root).unwrap();
+        let first = commit_files(&repo, &[("mappings/a.csrg", "a"), ("README", "1")]);
+        let second = commit_files(&repo, &[("mappings/b.at", "b")]);
+        commit_files(&repo, &[("README", "2")]);
+
+        let reference = |paths: &[&str]| {
+            let paths = paths
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            Repo::get_mappings_reference(&repo, &paths).unwrap()
+        };
+
+        assert_eq!(reference(&["mappings/a.csrg"]), first);
+        assert_eq!(reference(&["mappings/a.csrg", "mappings/b.at"]), second);
+        assert_eq!(reference(&["mappings"]), second);
+    }
+
+    /// Tests that commits missing from an existing repository are fetched
+    /// from origin without recloning the repository
+    #[test]
+    fn test_fetch_missing_commit() {
+        let (root, upstream) = setup_patch_repo("fetch-missing");
+        let url = upstream
+            .workdir()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let first = upstream
+            .head()
+            .unwrap()
+            .target()
+            .unwrap();
+
+        let path = root.join("clone");
+        let repo = Repo::get_repository(&url, &path).unwrap();
+        Repo::reset_to_commit(&repo, &first.to_string(), Some(1)).unwrap();
+        assert_eq!(read_to_string(path.join("file.txt")).unwrap(), ORIGINAL);
+        drop(repo);
+
+        // Marker to ensure the repository is not recloned
+        let marker = path.join(".git/marker");
+        write(&marker, "").unwrap();
+
+        replace_contents(&upstream, &ORIGINAL.replace("one", "ONE"));
+        let second = upstream
+            .head()
+            .unwrap()
+            .target()
+            .unwrap();
+
+        let repo = Repo::get_repository(&url, &path).unwrap();
+        Repo::reset_to_commit(&repo, &second.to_string(), Some(1)).unwrap();
+        assert_eq!(
+            read_to_string(path.join("file.txt")).unwrap(),
+            ORIGINAL.replace("one", "ONE")
+        );
+        assert!(marker.exists());
+    }
+
+    /// Tests that worktrees of different commits can be created from
+    /// the same mirror and that existing worktrees are reused
+    #[tokio::test]
+    async fn test_mirror_worktrees() {
+        let (root, upstream) = setup_patch_repo("mirror-worktrees");
+        let url = upstream
+            .workdir()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let first = upstream
+            .head()
+            .unwrap()
+            .target()
+            .unwrap();
+        replace_contents(&upstream, &ORIGINAL.replace("one", "ONE"));
+        let second = upstream
+            .head()
+            .unwrap()
+            .target()
+            .unwrap();
+
+        let mirror_path = root.join("mirrors/test.git");
+        let a_path = root.join("a/repo");
+        let b_path = root.join("b/repo");
+
+        let a =
+            Repo::setup_worktree(&url, None, &first.to_string(), &mirror_path, &a_path).unwrap();
+        let b =
+            Repo::setup_worktree(&url, None, &second.to_string(), &mirror_path, &b_path).unwrap();
+        assert!(a.is_worktree() && b.is_worktree());
+        assert!(Repository::open_bare(&mirror_path).is_ok());
+
+        // Mirrors are locked through a file so other processes wait too
+        let lock = Repo::lock_mirror(&mirror_path).unwrap();
+        let other = File::options()
+            .write(true)
+            .open(root.join("mirrors/test.git.lock"))
+            .unwrap();
+        assert!(other.try_lock().is_err());
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+        drop(other);
+        assert_eq!(read_to_string(a_path.join("file.txt")).unwrap(), ORIGINAL);
+        assert_eq!(
+            read_to_string(b_path.join("file.txt")).unwrap(),
+            ORIGINAL.replace("one", "ONE")
+        );
+        drop(a);
+
+        let marker = a_path.join("marker");
+        write(&marker, "").unwrap();
+        Repo::setup_worktree(&url, None, &second.to_string(), &mirror_path, &a_path).unwrap();
+        assert!(marker.exists());
+        assert_eq!(
+            read_to_string(a_path.join("file.txt")).unwrap(),
+            ORIGINAL.replace("one", "ONE")
+        );
+
+        // Worktrees must be usable as upstreams for the Spigot repositories
+        let branch = Repo::create_patched_branch(&b).unwrap();
+        assert_ne!(branch, "patched");
+        let target = root.join("target");
+        Repo::reset_to_upstream(&b_path, &branch, target.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            read_to_string(target.join("file.txt")).unwrap(),
+            ORIGINAL.replace("one", "ONE")
+        );
+    }
+
+    /// Tests that resetting to an upstream repository removes any stale
+    /// files left from a previous build and checks out the upstream branch
+    #[tokio::test]
+    async fn test_reset_to_upstream() {
+        let (root, repo) = setup_patch_repo("reset-upstream");
+        let target = root.join("target");
+
+        Repo::reset_to_upstream(repo.workdir().unwrap(), "HEAD", target.clone())
+            .await
+            .unwrap();
+        assert_eq!(read_to_string(target.join("file.txt")).unwrap(), ORIGINAL);
+
+        write(target.join("stale.txt"), "stale").unwrap();
+        replace_contents(&repo, &ORIGINAL.replace("one", "ONE"));
+
+        Repo::reset_to_upstream(repo.workdir().unwrap(), "HEAD", target.clone())
+            .await
+            .unwrap();
+        assert!(!target
+            .join("stale.txt")
+            .exists());
+        assert_eq!(
+            read_to_string(target.join("file.txt")).unwrap(),
+            ORIGINAL.replace("one", "ONE")
+        );
+    }
+
+    /// Serves the repositories within `root` over the smart HTTP protocol
+    /// using git http-backend (the local transport doesn't support shallow
+    /// fetches). Fetching commits directly is allowed. Returns the url
+    fn serve_git(root: &Path) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let root = root.to_path_buf();
+        spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    break;
+                };
+                let _ = serve_git_request(&root, stream);
+            }
+        });
+        format!("http://{address}")
+    }
+
+    /// Responds to a single HTTP request using the git http-backend CGI
+    fn serve_git_request(root: &Path, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let method = parts
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let target = parts
+            .next()
+            .unwrap_or_default()
+            .to_string();
+
+        let mut content_type = String::new();
+        let mut content_length = 0;
+        let mut chunked = false;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                let value = value.trim();
+                match name.to_lowercase().as_str() {
+                    "content-type" => content_type = value.to_string(),
+                    "content-length" => content_length = value.parse().unwrap_or(0),
+                    "transfer-encoding" => chunked = value.contains("chunked"),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut body = Vec::new();
+        if chunked {
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                let size = usize::from_str_radix(line.trim(), 16).unwrap_or(0);
+                let mut chunk = vec![0; size + 2];
+                reader.read_exact(&mut chunk)?;
+                if size == 0 {
+                    break;
+                }
+                body.extend_from_slice(&chunk[..size]);
+            }
+        } else {
+            body.resize(content_length, 0);
+            reader.read_exact(&mut body)?;
+        }
+
+        let (path, query) = target
+            .split_once('?')
+            .unwrap_or((&target, ""));
+        let mut backend = Command::new("git")
+            .args(["-c", "uploadpack.allowAnySHA1InWant=true", "http-backend"])
+            .env("GIT_PROJECT_ROOT", root)
+            .env("GIT_HTTP_EXPORT_ALL", "1")
+            .env("REQUEST_METHOD", method)
+            .env("PATH_INFO", path)
+            .env("QUERY_STRING", query)
+            .env("CONTENT_TYPE", content_type)
+            .env("CONTENT_LENGTH", body.len().to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = backend.stdin.take() {
+            stdin.write_all(&body)?;
+        }
+        let output = backend.wait_with_output()?;
+
+        // Convert the CGI response into a HTTP response
+        let output = output.stdout;
+        let split = output
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap_or(0);
+        let head = String::from_utf8_lossy(&output[..split]);
+        let body = &output[(split + 4).min(output.len())..];
+        let mut status = "200 OK".to_string();
+        let mut response = String::new();
+        for line in head.lines() {
+            match line.strip_prefix("Status: ") {
+                Some(value) => status = value.to_string(),
+                None => {
+                    response.push_str(line);
+                    response.push_str("\r\n");
+                }
+            }
+        }
+        let response = format!(
+            "HTTP/1.1 {status}\r\n{response}Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(response.as_bytes())?;
+        stream.write_all(body)?;
+        Ok(())
+    }
+
+    /// Tests that existing directories which aren't repositories
+    /// are left untouched rather than replaced
+    #[test]
+    fn test_get_repository_not_repository() {
+        let root = test_dir("get-repository");
+        let path = root.join("repo");
+        create_dir_all(&path).unwrap();
+        write(path.join("file.txt"), ORIGINAL).unwrap();
+
+        assert!(Repo::get_repository("https://example.com/repo.git", &path).is_err());
+        assert_eq!(read_to_string(path.join("file.txt")).unwrap(), ORIGINAL);
+    }
+
+    /// Tests that shallow mirrors are kept and only deepened once the
+    /// three-way merge fallback needs the pre-image blobs of a patch
+    /// created against an older commit
+    #[tokio::test]
+    async fn test_reset_to_shallow_upstream() {
+        let (root, origin) = setup_patch_repo("reset-shallow-upstream");
+        replace_contents(&origin, &ORIGINAL.replace("seven", "SEVEN"));
+        let head = origin
+            .head()
+            .unwrap()
+            .target()
+            .unwrap()
+            .to_string();
+        let url = format!("{}/repo", serve_git(&root));
+
+        let mirror_path = root.join("mirrors/test.git");
+        let upstream_path = root.join("upstream");
+        Repo::setup_worktree(&url, Some(1), &head, &mirror_path, &upstream_path).unwrap();
+        assert!(Repository::open_bare(&mirror_path)
+            .unwrap()
+            .is_shallow());
+
+        // Healthy shallow mirrors are reused rather than fetched again
+        let marker = mirror_path.join("marker");
+        write(&marker, "").unwrap();
+        Repo::setup_worktree(&url, Some(1), &head, &mirror_path, &upstream_path).unwrap();
+        assert!(marker.exists());
+        assert!(Repository::open_bare(&mirror_path)
+            .unwrap()
+            .is_shallow());
+
+        let target = root.join("target");
+        let repo = Repo::reset_to_upstream(&upstream_path, "HEAD", target.clone())
+            .await
+            .unwrap();
+        let report = Repo::apply_patches(&repo, &root.join("patches"), ConflictMode::Stop)
+            .await
+            .unwrap();
+        assert_eq!(report.merged.len(), 1);
+        let contents = read_to_string(target.join("file.txt")).unwrap();
+        assert!(contents.contains("SEVEN"));
+        assert!(contents.contains("NINE"));
+        assert!(!Repository::open_bare(&mirror_path)
+            .unwrap()
+            .is_shallow());
+    }
+
+    /// Tests that patches are committed with the author, date and
+    /// subject from their headers
+    #[tokio::test]
+    async fn test_patch_headers() {
+        let (root, repo) = setup_patch_repo("patch-headers");
+        let patch_path = root.join("patches/0001-Test.patch");
+        let patch = read_to_string(&patch_path).unwrap();
+        write(
+            &patch_path,
+            format!(
+                "From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001\n\
+                From: Test Author <author@example.com>\n\
+                Date: Tue, 13 Sep 2022 20:38:40 +1000\n\
+                Subject: [PATCH] Uppercase nine\n\
+                \n\
+                ---\n\
+                {patch}"
+            ),
+        )
+        .unwrap();
+
+        Repo::apply_patches(&repo, &root.join("patches"), ConflictMode::Stop)
+            .await
+            .unwrap();
+        let commit = repo
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(commit.summary(), Some("Uppercase nine"));
+        let author = commit.author();
+        assert_eq!(author.name(), Some("Test Author"));
+        assert_eq!(author.email(), Some("author@example.com"));
+        assert_eq!(author.when().seconds(), 1663065520);
+        assert_eq!(author.when().offset_minutes(), 600);
+    }
+
+    /// Tests that a patch whose context no longer matches is applied
+    /// using the three-way merge fallback
+    #[tokio::test]
+    async fn test_three_way_merge() {
+        let (root, repo) = setup_patch_repo("three-way-merge");
+        replace_contents(&repo, &ORIGINAL.replace("seven", "SEVEN"));
+
+        let report = Repo::apply_patches(&repo, &root.join("patches"), ConflictMode::Stop)
+            .await
+            .unwrap();
+        assert_eq!(report.applied, 1);
+        assert_eq!(report.merged.len(), 1);
+        assert!(report.conflicts.is_empty());
+
+        let contents = read_to_string(root.join("repo/file.txt")).unwrap();
+        assert!(contents.contains("SEVEN"));
+        assert!(contents.contains("NINE"));
+    }
+
+    /// Tests that conflicting patches are reported and either stop
+    /// or keep the current contents depending on the conflict mode
+    #[tokio::test]
+    async fn test_three_way_conflict() {
+        let (root, repo) = setup_patch_repo("three-way-conflict-stop");
+        replace_contents(&repo, &ORIGINAL.replace("nine", "9"));
+        let result = Repo::apply_patches(&repo, &root.join("patches"), ConflictMode::Stop).await;
+        match result {
+            Err(RepoError::PatchConflicts(report)) => {
+                assert_eq!(report.conflicts.len(), 1);
+                assert_eq!(report.conflicts[0].files, vec!["file.txt"]);
+            }
+            value => panic!("Expected conflict got: {value:?}"),
+        }
+        let contents = read_to_string(root.join("repo/file.txt")).unwrap();
+        assert!(contents.contains("<<<<<<<"));
+
+        // Conflicting patches are skipped without being committed and
+        // the following patches are still applied
+        let (root, repo) = setup_patch_repo("three-way-conflict-continue");
+        let conflicted = ORIGINAL.replace("nine", "9");
+        replace_contents(&repo, &conflicted);
+        write_patch(
+            &repo,
+            &root.join("patches/0002-Other.patch"),
+            &conflicted.replace("two", "TWO"),
+        );
+        let base = repo
+            .head()
+            .unwrap()
+            .target()
+            .unwrap();
+        let report = Repo::apply_patches(&repo, &root.join("patches"), ConflictMode::Continue)
+            .await
+            .unwrap();
+        assert_eq!(report.applied, 1);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].patch, "0001-Test.patch");
+        let contents = read_to_string(root.join("repo/file.txt")).unwrap();
+        assert_eq!(contents, conflicted.replace("two", "TWO"));
+
+        let head = repo
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(
+            head.parent_ids()
+                .collect::<Vec<_>>(),
+            vec![base]
+        );
+        assert!(repo
+            .statuses(None)
+            .unwrap()
+            .is_empty());
+    }
 
     #[tokio::test]
     async fn try_get_refs() {
@@ -285,10 +1672,14 @@ mod test {
 
         let repo_path = Path::new("build");
         let repo = Repo::BuildData
-            .setup(&refs, repo_path.join("build_data"))
+            .setup(
+                &refs,
+                &repo_path.join("mirrors"),
+                repo_path.join("build_data"),
+            )
             .await
             .unwrap();
-        let reference = Repo::get_mappings_reference(&repo).unwrap();
+        let reference = Repo::get_mappings_reference(&repo, &["mappings".to_string()]).unwrap();
         let md = md5::compute(reference);
         let hash = &format!("{md:x}")[24..];
         println!("{hash}")
@@ -1,31 +1,153 @@
 use sha1_smol::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::io;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Size of the buffer used when hashing readers
+const BUFFER_SIZE: usize = 64 * 1024;
 
 /// Different types of hashing methods. Checking against hashes
 /// of these types is done with the `is_match` function.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HashType {
     MD5,
     SHA1,
     SHA256,
+    SHA512,
 }
 
 impl HashType {
     pub fn is_match<D: AsRef<[u8]>>(&self, hash: &str, data: D) -> bool {
+        self.digest(data)
+            .eq_ignore_ascii_case(hash)
+    }
+
+    /// Computes the lowercase hex digest of the provided data
+    pub fn digest<D: AsRef<[u8]>>(&self, data: D) -> String {
+        let mut hasher = Hasher::new(self);
+        hasher.update(data.as_ref());
+        hasher.finish()
+    }
+
+    /// Computes the lowercase hex digest of all the data from the reader
+    pub async fn digest_reader<R: AsyncRead + Unpin>(&self, reader: R) -> io::Result<String> {
+        let mut hasher = Hasher::new(self);
+        hasher
+            .update_reader(reader)
+            .await?;
+        Ok(hasher.finish())
+    }
+
+    /// Computes the lowercase hex digest of the file at the provided
+    /// path without loading the whole file into memory
+    pub async fn digest_file(&self, path: impl AsRef<Path>) -> io::Result<String> {
+        let file = File::open(path).await?;
+        self.digest_reader(file).await
+    }
+
+    /// Checks whether the file at the provided path matches the hash
+    pub async fn is_file_match(&self, hash: &str, path: impl AsRef<Path>) -> io::Result<bool> {
+        let digest = self.digest_file(path).await?;
+        Ok(digest.eq_ignore_ascii_case(hash))
+    }
+}
+
+/// Incremental hasher for computing the digest of data that is
+/// provided in chunks (e.g. while streaming a download)
+pub enum Hasher {
+    MD5(md5::Context),
+    SHA1(Sha1),
+    SHA256(Sha256),
+    SHA512(Sha512),
+}
+
+impl Hasher {
+    /// Creates a new hasher for the provided hash type
+    pub fn new(hash_type: &HashType) -> Self {
+        match hash_type {
+            HashType::MD5 => Self::MD5(md5::Context::new()),
+            HashType::SHA1 => Self::SHA1(Sha1::new()),
+            HashType::SHA256 => Self::SHA256(Sha256::new()),
+            HashType::SHA512 => Self::SHA512(Sha512::new()),
+        }
+    }
+
+    /// Updates the hash with the provided data
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::MD5(context) => context.consume(data),
+            Self::SHA1(hasher) => hasher.update(data),
+            Self::SHA256(hasher) => Digest::update(hasher, data),
+            Self::SHA512(hasher) => Digest::update(hasher, data),
+        }
+    }
+
+    /// Updates the hash with all the data from the reader
+    pub async fn update_reader<R: AsyncRead + Unpin>(&mut self, reader: R) -> io::Result<()> {
+        read_chunks(reader, |chunk| self.update(chunk)).await
+    }
+
+    /// Consumes the hasher returning the lowercase hex digest
+    pub fn finish(self) -> String {
         match self {
-            HashType::MD5 => {
-                let digest = md5::compute(data);
-                let result = format!("{:x}", digest);
-                result.eq(hash)
-            }
-            HashType::SHA1 => {
-                let hasher = Sha1::from(data);
-                let result = hasher.digest().to_string();
-                result.eq(hash)
-            }
-            HashType::SHA256 => {
-                let result = sha256::digest(data.as_ref());
-                result.eq(hash)
-            }
+            Self::MD5(context) => format!("{:x}", context.compute()),
+            Self::SHA1(hasher) => hasher.digest().to_string(),
+            Self::SHA256(hasher) => format!("{:x}", hasher.finalize()),
+            Self::SHA512(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+/// Reads all the data from the reader passing each chunk to `consume`
+async fn read_chunks<R, F>(mut reader: R, mut consume: F) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    F: FnMut(&[u8]),
+{
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    loop {
+        let count = reader
+            .read(&mut buffer)
+            .await?;
+        if count == 0 {
+            break;
+        }
+        consume(&buffer[..count]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::hash::HashType;
+
+    /// Tests the streamed digests of a known value against the
+    /// published test vectors
+    #[tokio::test]
+    async fn test_digests() {
+        let data = b"abc";
+        for (hash_type, expected) in [
+            (HashType::MD5, "900150983cd24fb0d6963f7d28e17f72"),
+            (HashType::SHA1, "a9993e364706816aba3e25717850c26c9cd0d89d"),
+            (
+                HashType::SHA256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                HashType::SHA512,
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            ),
+        ] {
+            let streamed = hash_type
+                .digest_reader(data.as_slice())
+                .await
+                .unwrap();
+            assert_eq!(streamed, expected);
+            assert_eq!(hash_type.digest(data), expected);
+            assert!(hash_type.is_match(&streamed.to_uppercase(), data));
         }
     }
 }
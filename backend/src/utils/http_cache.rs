@@ -0,0 +1,251 @@
+use crate::utils::files::{ensure_dir_exists, write_atomic};
+use crate::utils::net::create_reqwest;
+use log::{debug, warn};
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+use tokio::fs::read;
+
+/// The HTTP cache configured from the environment
+static HTTP_CACHE: OnceLock<HttpCache> = OnceLock::new();
+
+/// Environment variable for the directory the cache is stored in
+pub const HTTP_CACHE_PATH_KEY: &str = "HTTP_CACHE_PATH";
+/// Environment variable for the number of seconds cached responses
+/// are used before they are revalidated with upstream
+pub const HTTP_CACHE_TTL_KEY: &str = "HTTP_CACHE_TTL";
+
+/// Default directory the cache is stored in
+const DEFAULT_CACHE_PATH: &str = "build/cache/http";
+/// Default time cached responses are used without revalidation
+const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Error)]
+pub enum HttpCacheError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    #[error("Request to {0} failed with status {1}")]
+    Status(String, StatusCode),
+}
+
+type CacheResult<T> = Result<T, HttpCacheError>;
+
+/// Metadata stored alongside each cached response body
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Unix time in seconds the response was last fetched or revalidated
+    fetched_at: u64,
+}
+
+/// On-disk cache for GET requests to upstream metadata. Responses are
+/// used for the TTL then revalidated using their ETag / Last-Modified
+/// headers. Cached responses are used when upstream can't be reached
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+impl HttpCache {
+    pub fn new(path: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            path: path.into(),
+            ttl,
+        }
+    }
+
+    /// Creates the cache using HTTP_CACHE_PATH and HTTP_CACHE_TTL
+    /// falling back to the defaults when they aren't set
+    pub fn from_env() -> Self {
+        let path = env::var(HTTP_CACHE_PATH_KEY).unwrap_or_else(|_| DEFAULT_CACHE_PATH.to_string());
+        let ttl = env::var(HTTP_CACHE_TTL_KEY)
+            .ok()
+            .and_then(|value| {
+                value
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TTL);
+        Self::new(path, ttl)
+    }
+
+    /// Retrieves the body of the response from the provided url using
+    /// the cached copy when it's within the TTL or still valid upstream
+    pub async fn get(&self, url: &str) -> CacheResult<Vec<u8>> {
+        let (entry_path, body_path) = self.paths(url);
+        let cached = match read(&entry_path).await {
+            Ok(contents) => serde_json::from_slice::<CacheEntry>(&contents)
+                .ok()
+                .filter(|entry| entry.url == url && body_path.is_file()),
+            Err(_) => None,
+        };
+
+        let now = unix_time();
+        if let Some(entry) = &cached {
+            let age = Duration::from_secs(now.saturating_sub(entry.fetched_at));
+            if age < self.ttl {
+                debug!("Using cached response for {url}");
+                return Ok(read(&body_path).await?);
+            }
+        }
+
+        let client = create_reqwest()?;
+        let mut request = client.get(url);
+        if let Some(entry) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(err) => {
+                if cached.is_some() {
+                    warn!("Failed to reach {url} ({err}) using cached response");
+                    return Ok(read(&body_path).await?);
+                }
+                return Err(err.into());
+            }
+        };
+
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some(mut entry) = cached {
+                debug!("Cached response for {url} is still valid");
+                entry.fetched_at = now;
+                self.write_entry(&entry_path, &entry)
+                    .await?;
+                return Ok(read(&body_path).await?);
+            }
+        }
+
+        if !status.is_success() {
+            if status.is_server_error() && cached.is_some() {
+                warn!("Request to {url} failed with status {status} using cached response");
+                return Ok(read(&body_path).await?);
+            }
+            return Err(HttpCacheError::Status(url.to_string(), status));
+        }
+
+        let header = |name: header::HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        let entry = CacheEntry {
+            url: url.to_string(),
+            etag: header(header::ETAG),
+            last_modified: header(header::LAST_MODIFIED),
+            fetched_at: now,
+        };
+        let body = response.bytes().await?;
+
+        ensure_dir_exists(&self.path).await?;
+        write_atomic(&body_path, &body).await?;
+        self.write_entry(&entry_path, &entry)
+            .await?;
+        Ok(body.to_vec())
+    }
+
+    /// Writes the metadata for a cached response
+    async fn write_entry(&self, path: &Path, entry: &CacheEntry) -> io::Result<()> {
+        let contents = serde_json::to_vec(entry)?;
+        write_atomic(path, &contents).await
+    }
+
+    /// Gets the paths of the metadata and body files for the url
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = format!("{:x}", md5::compute(url));
+        (
+            self.path
+                .join(format!("{key}.json")),
+            self.path
+                .join(format!("{key}.body")),
+        )
+    }
+}
+
+/// The current unix time in seconds
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Retrieves the global HTTP cache which is configured from
+/// the environment the first time it's accessed
+pub fn http_cache() -> &'static HttpCache {
+    HTTP_CACHE.get_or_init(HttpCache::from_env)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::http_cache::HttpCache;
+    use crate::utils::testing::{serve_http, test_dir};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Tests that responses are revalidated using their ETag and that
+    /// the cached response is used when upstream can't be reached
+    #[tokio::test]
+    async fn test_conditional_cache() {
+        let path = test_dir("http-cache");
+
+        // Responses sent by the server (true for not modified)
+        let responses = Arc::new(Mutex::new(Vec::new()));
+        let server_responses = responses.clone();
+        let (address, server) = serve_http(move |request| {
+            let not_modified = request.contains("if-none-match: \"v1\"");
+            server_responses
+                .lock()
+                .unwrap()
+                .push(not_modified);
+            let response = if not_modified {
+                "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n"
+            } else {
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello"
+            };
+            response
+                .as_bytes()
+                .to_vec()
+        })
+        .await;
+
+        let url = format!("http://{address}/versions.json");
+
+        // Cached responses within the TTL don't reach the server
+        let cache = HttpCache::new(&path, Duration::from_secs(60));
+        assert_eq!(cache.get(&url).await.unwrap(), b"hello");
+        assert_eq!(cache.get(&url).await.unwrap(), b"hello");
+        assert_eq!(*responses.lock().unwrap(), [false]);
+
+        // Expired responses are revalidated
+        let cache = HttpCache::new(&path, Duration::ZERO);
+        assert_eq!(cache.get(&url).await.unwrap(), b"hello");
+        assert_eq!(*responses.lock().unwrap(), [false, true]);
+
+        // Cached response is used when upstream is down
+        server.abort();
+        let _ = server.await;
+        assert_eq!(cache.get(&url).await.unwrap(), b"hello");
+    }
+}
@@ -0,0 +1,381 @@
+use std::{fmt::Write, str::from_utf8};
+use thiserror::Error;
+
+/// The maximum length in bytes of a manifest line (excluding the line
+/// ending) longer values are split onto continuation lines
+const MAX_LINE_LENGTH: usize = 72;
+
+pub const MANIFEST_VERSION: &str = "Manifest-Version";
+pub const MAIN_CLASS: &str = "Main-Class";
+pub const IMPLEMENTATION_VERSION: &str = "Implementation-Version";
+pub const MULTI_RELEASE: &str = "Multi-Release";
+/// Attribute that starts each of the per-entry sections
+pub const NAME: &str = "Name";
+
+#[derive(Debug, Error)]
+pub enum ManifestError {
+    #[error("Manifest is not valid UTF-8")]
+    InvalidEncoding,
+    #[error("Invalid manifest line {0}: expected \"Name: Value\"")]
+    InvalidLine(usize),
+    #[error("Manifest continuation line {0} doesn't follow an attribute")]
+    UnexpectedContinuation(usize),
+    #[error("Manifest section starting on line {0} is missing a Name")]
+    MissingName(usize),
+}
+
+type ManifestResult<T> = Result<T, ManifestError>;
+
+/// Ordered collection of manifest attributes. Attribute names
+/// are case-insensitive
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attributes(Vec<(String, String)>);
+
+impl Attributes {
+    /// Gets the value of the attribute with the provided name
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the value of the attribute with the provided name replacing
+    /// the existing value or appending the attribute if it's missing
+    pub fn set(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self
+            .0
+            .iter_mut()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        {
+            Some((_, existing)) => *existing = value,
+            None => self
+                .0
+                .push((name.to_string(), value)),
+        }
+    }
+
+    /// Removes the attribute with the provided name returning its value
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let index = self
+            .0
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(name))?;
+        Some(self.0.remove(index).1)
+    }
+
+    /// Retains only the attributes which the filter returns true for
+    pub fn retain<F: FnMut(&str, &str) -> bool>(&mut self, mut filter: F) {
+        self.0
+            .retain(|(key, value)| filter(key, value))
+    }
+
+    /// Iterator over the attribute names and values in order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Per-entry section of a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// The name of the entry this section applies to
+    pub name: String,
+    /// The attributes of the section (excluding the name)
+    pub attributes: Attributes,
+}
+
+/// Model of a jar META-INF/MANIFEST.MF file consisting of the main
+/// attributes followed by the per-entry sections
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub main: Attributes,
+    pub sections: Vec<Section>,
+}
+
+impl Manifest {
+    /// Parses a manifest from its bytes. Accepts CRLF, LF and CR line
+    /// endings and joins continuation lines (lines starting with a
+    /// single space) onto the previous attribute value
+    pub fn parse(contents: &[u8]) -> ManifestResult<Self> {
+        let text = from_utf8(contents).map_err(|_| ManifestError::InvalidEncoding)?;
+        let text = text
+            .strip_prefix('\u{feff}')
+            .unwrap_or(text);
+
+        // Parsed sections with the line number they start on
+        let mut sections: Vec<(usize, Attributes)> = vec![(1, Attributes::default())];
+        let mut previous_blank = false;
+
+        for (index, line) in split_lines(text).enumerate() {
+            let number = index + 1;
+            let (_, attributes) = sections
+                .last_mut()
+                .expect("Sections always has a section");
+
+            if line.is_empty() {
+                previous_blank = true;
+                continue;
+            }
+
+            if let Some(continuation) = line.strip_prefix(' ') {
+                let (_, value) = match attributes.0.last_mut() {
+                    Some(value) if !previous_blank => value,
+                    _ => return Err(ManifestError::UnexpectedContinuation(number)),
+                };
+                value.push_str(continuation);
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once(':')
+                .ok_or(ManifestError::InvalidLine(number))?;
+            let value = value
+                .strip_prefix(' ')
+                .unwrap_or(value);
+
+            if previous_blank && !attributes.is_empty() {
+                let mut next = Attributes::default();
+                next.0
+                    .push((name.to_string(), value.to_string()));
+                sections.push((number, next));
+            } else {
+                attributes
+                    .0
+                    .push((name.to_string(), value.to_string()));
+            }
+            previous_blank = false;
+        }
+
+        let mut sections = sections.into_iter();
+        let (_, main) = sections
+            .next()
+            .unwrap_or_default();
+        let sections = sections
+            .map(|(line, mut attributes)| {
+                let name = attributes
+                    .remove(NAME)
+                    .ok_or(ManifestError::MissingName(line))?;
+                Ok(Section { name, attributes })
+            })
+            .collect::<ManifestResult<Vec<Section>>>()?;
+
+        Ok(Self { main, sections })
+    }
+
+    /// Writes the manifest to bytes using CRLF line endings and
+    /// splitting lines longer than 72 bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = String::new();
+        for (name, value) in self.main.iter() {
+            write_attribute(&mut output, name, value);
+        }
+        output.push_str("\r\n");
+        for section in &self.sections {
+            write_attribute(&mut output, NAME, &section.name);
+            for (name, value) in section.attributes.iter() {
+                write_attribute(&mut output, name, value);
+            }
+            output.push_str("\r\n");
+        }
+        output.into_bytes()
+    }
+
+    /// Gets the section for the entry with the provided name
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections
+            .iter()
+            .find(|section| section.name == name)
+    }
+
+    /// Gets the section for the entry with the provided name creating
+    /// a new empty section if one doesn't exist
+    pub fn section_mut(&mut self, name: &str) -> &mut Section {
+        let index = match self
+            .sections
+            .iter()
+            .position(|section| section.name == name)
+        {
+            Some(index) => index,
+            None => {
+                self.sections.push(Section {
+                    name: name.to_string(),
+                    attributes: Attributes::default(),
+                });
+                self.sections.len() - 1
+            }
+        };
+        &mut self.sections[index]
+    }
+
+    /// The class that is launched when running the jar
+    pub fn main_class(&self) -> Option<&str> {
+        self.main.get(MAIN_CLASS)
+    }
+
+    /// The implementation version of the jar
+    pub fn implementation_version(&self) -> Option<&str> {
+        self.main
+            .get(IMPLEMENTATION_VERSION)
+    }
+
+    /// Whether the jar is a multi-release jar
+    pub fn multi_release(&self) -> bool {
+        self.main
+            .get(MULTI_RELEASE)
+            .is_some_and(|value| {
+                value
+                    .trim()
+                    .eq_ignore_ascii_case("true")
+            })
+    }
+
+    /// Parses the Spigot and CraftBukkit commit hashes from the Spigot
+    /// implementation version (e.g. `3871-Spigot-9b3d8e0-d6fd8b3` or
+    /// `git-Spigot-9b3d8e0-d6fd8b3`) returning (spigot, craftbukkit)
+    pub fn spigot_commits(&self) -> Option<(&str, &str)> {
+        let version = self.implementation_version()?;
+        let (_, commits) = version.split_once("Spigot-")?;
+        let commits = commits
+            .split_whitespace()
+            .next()?;
+        let (spigot, craft_bukkit) = commits.split_once('-')?;
+        if spigot.is_empty() || craft_bukkit.is_empty() {
+            return None;
+        }
+        Some((spigot, craft_bukkit))
+    }
+
+    /// Removes the digest attributes (e.g. SHA-256-Digest) from the
+    /// per-entry sections. Sections left without any attributes are
+    /// removed entirely
+    pub fn strip_digests(&mut self) {
+        for section in &mut self.sections {
+            section
+                .attributes
+                .retain(|name, _| {
+                    !name
+                        .to_ascii_uppercase()
+                        .ends_with("-DIGEST")
+                });
+        }
+        self.sections
+            .retain(|section| !section.attributes.is_empty());
+    }
+}
+
+/// Splits the text into lines accepting CRLF, LF and CR line endings
+fn split_lines(text: &str) -> impl Iterator<Item = &str> {
+    let mut remaining = text;
+    std::iter::from_fn(move || {
+        if remaining.is_empty() {
+            return None;
+        }
+        let end = remaining
+            .find(['\r', '\n'])
+            .unwrap_or(remaining.len());
+        let line = &remaining[..end];
+        let rest = &remaining[end..];
+        remaining = rest
+            .strip_prefix("\r\n")
+            .or_else(|| rest.strip_prefix('\r'))
+            .or_else(|| rest.strip_prefix('\n'))
+            .unwrap_or(rest);
+        Some(line)
+    })
+}
+
+/// Writes the attribute to the output splitting it onto continuation
+/// lines so that no line exceeds 72 bytes. Lines are never split
+/// within a multi-byte character
+fn write_attribute(output: &mut String, name: &str, value: &str) {
+    let mut line = String::with_capacity(name.len() + value.len() + 2);
+    let _ = write!(line, "{name}: {value}");
+
+    let mut remaining = line.as_str();
+    let mut limit = MAX_LINE_LENGTH;
+    loop {
+        if remaining.len() <= limit {
+            output.push_str(remaining);
+            output.push_str("\r\n");
+            break;
+        }
+        let mut end = limit;
+        while !remaining.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.push_str(&remaining[..end]);
+        output.push_str("\r\n ");
+        remaining = &remaining[end..];
+        // Continuation lines include the leading space
+        limit = MAX_LINE_LENGTH - 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::manifest::{Manifest, MAIN_CLASS};
+
+    /// Tests parsing a manifest with sections, continuation lines
+    /// and mixed line endings
+    #[test]
+    fn test_parse() {
+        let contents = "Manifest-Version: 1.0\r\nMain-Class: org.bukkit.craftbukkit.Main\n\
+            Implementation-Version: 3871-Spigot-9b3d8e0-d6fd8b3 (MC: 1.20.\r\n 1)\r\n\
+            Multi-Release: true\r\n\r\n\
+            Name: net/minecraft/server/Main.class\r\nSHA-256-Digest: abc\r\n\r\n";
+        let manifest = Manifest::parse(contents.as_bytes()).unwrap();
+        assert_eq!(manifest.main_class(), Some("org.bukkit.craftbukkit.Main"));
+        assert_eq!(
+            manifest.implementation_version(),
+            Some("3871-Spigot-9b3d8e0-d6fd8b3 (MC: 1.20.1)")
+        );
+        assert_eq!(manifest.spigot_commits(), Some(("9b3d8e0", "d6fd8b3")));
+        assert!(manifest.multi_release());
+        let section = manifest
+            .section("net/minecraft/server/Main.class")
+            .unwrap();
+        assert_eq!(
+            section
+                .attributes
+                .get("sha-256-digest"),
+            Some("abc")
+        );
+    }
+
+    /// Tests that long values are split at 72 bytes without splitting
+    /// characters and that written manifests parse back identically
+    #[test]
+    fn test_write_round_trip() {
+        let mut manifest = Manifest::default();
+        manifest
+            .main
+            .set("Manifest-Version", "1.0");
+        manifest
+            .main
+            .set(MAIN_CLASS, "a.".repeat(60) + "Main");
+        manifest
+            .section_mut("é".repeat(50).as_str())
+            .attributes
+            .set("Sealed", "true");
+
+        let bytes = manifest.to_bytes();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        for line in text.split("\r\n") {
+            assert!(line.len() <= 72, "Line too long: {line:?}");
+        }
+        assert!(text.ends_with("\r\n\r\n"));
+        assert_eq!(Manifest::parse(&bytes).unwrap(), manifest);
+    }
+}
@@ -1,8 +1,14 @@
+pub(crate) mod archive;
 pub(crate) mod cmd;
 pub(crate) mod constants;
+pub(crate) mod endpoints;
 pub(crate) mod files;
 pub(crate) mod git;
 pub(crate) mod hash;
+pub(crate) mod http_cache;
+pub(crate) mod manifest;
 pub(crate) mod net;
+#[cfg(test)]
+pub(crate) mod testing;
 pub(crate) mod versions;
 pub(crate) mod zip;
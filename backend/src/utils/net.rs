@@ -1,7 +1,26 @@
 use crate::utils::constants::USER_AGENT;
-use std::{io, path::Path};
+use crate::utils::files::{delete_existing, ensure_parent_exists, move_file};
+use crate::utils::hash::{HashType, Hasher};
+use log::{debug, warn};
+use reqwest::{header, StatusCode};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use thiserror::Error;
-use tokio::fs::write;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    time::sleep,
+};
+
+/// The maximum number of attempts made for a download
+const DOWNLOAD_ATTEMPTS: u32 = 5;
+/// The delay before the first retry which doubles for each retry
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// The maximum delay between retries
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Error)]
 pub enum NetworkError {
@@ -9,8 +28,34 @@ pub enum NetworkError {
     Request(#[from] reqwest::Error),
     #[error(transparent)]
     IO(#[from] io::Error),
+    #[error("Request to {0} failed with status {1}")]
+    Status(String, StatusCode),
+    #[error("Download from {0} ended early ({1} of {2} bytes)")]
+    Incomplete(String, u64, u64),
+    #[error("Hash of download from {url} didn't match (expected {expected} got {actual})")]
+    HashMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl NetworkError {
+    /// Whether the download should be retried after this error
+    fn is_retryable(&self) -> bool {
+        match self {
+            NetworkError::Request(err) => !err.is_builder(),
+            NetworkError::Status(_, status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            NetworkError::Incomplete(..) | NetworkError::HashMismatch { .. } => true,
+            NetworkError::IO(_) => false,
+        }
+    }
 }
 
+type NetworkResult<T> = Result<T, NetworkError>;
+
 /// Create a reqwest client that has the User-Agent
 /// header applied. User-Agent is required when connecting
 /// to https://hub.spigotmc.org/versions/ or else the error
@@ -23,10 +68,239 @@ pub fn create_reqwest() -> Result<reqwest::Client, reqwest::Error> {
 
 /// Downloads the file from the provided url and stores it at
 /// the provided path
-pub async fn download_file<A: AsRef<Path>>(url: &str, path: A) -> Result<(), NetworkError> {
+pub async fn download_file<A: AsRef<Path>>(url: &str, path: A) -> NetworkResult<()> {
+    download(url, path, None).await
+}
+
+/// Downloads the file from the provided url and stores it at the provided
+/// path. The response is streamed to a temporary `.part` file next to the
+/// path which is renamed to the path only once the download is complete
+/// and (when `expected` is provided) its hash matches the expected hash.
+///
+/// Failed attempts are retried with exponential backoff and resume from
+/// the existing partial file using Range requests when the server
+/// supports them
+pub async fn download<A: AsRef<Path>>(
+    url: &str,
+    path: A,
+    expected: Option<(HashType, &str)>,
+) -> NetworkResult<()> {
+    let path = path.as_ref();
+    let part_path = part_path(path);
+    ensure_parent_exists(path).await?;
+
     let client = create_reqwest()?;
-    let response = client.get(url).send().await?;
-    let bytes = response.bytes().await?;
-    write(path, bytes).await?;
+    let mut attempt = 1;
+    loop {
+        let result = try_download(&client, url, &part_path, expected.as_ref()).await;
+        match result {
+            Ok(()) => break,
+            Err(err) if attempt < DOWNLOAD_ATTEMPTS && err.is_retryable() => {
+                if let NetworkError::HashMismatch { .. } = err {
+                    // Partial contents are invalid so start again from scratch
+                    delete_existing(&part_path).await?;
+                }
+                let delay = RETRY_BASE_DELAY
+                    .saturating_mul(2u32.pow(attempt - 1))
+                    .min(RETRY_MAX_DELAY);
+                warn!(
+                    "Download attempt {attempt} of {url} failed ({err}). Retrying in {:.1}s",
+                    delay.as_secs_f32()
+                );
+                sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => {
+                if let NetworkError::HashMismatch { .. } = err {
+                    delete_existing(&part_path).await?;
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    move_file(&part_path, path).await?;
+    Ok(())
+}
+
+/// Path of the temporary file used while downloading to `path`
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Makes a single attempt at downloading the remaining contents of the
+/// url into the `part_path` resuming from any existing partial contents
+async fn try_download(
+    client: &reqwest::Client,
+    url: &str,
+    part_path: &Path,
+    expected: Option<&(HashType, &str)>,
+) -> NetworkResult<()> {
+    let existing = match tokio::fs::metadata(part_path).await {
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        _ => 0,
+    };
+
+    let mut request = client.get(url);
+    if existing > 0 {
+        debug!("Resuming download of {url} from {existing} bytes");
+        request = request.header(header::RANGE, format!("bytes={existing}-"));
+    }
+    let mut response = request.send().await?;
+    let status = response.status();
+
+    let resumed = match status {
+        StatusCode::PARTIAL_CONTENT if existing > 0 => true,
+        StatusCode::RANGE_NOT_SATISFIABLE if existing > 0 => {
+            // Partial file is unusable (e.g. the remote file changed)
+            delete_existing(part_path).await?;
+            return Err(NetworkError::Incomplete(url.to_string(), existing, 0));
+        }
+        status if status.is_success() => false,
+        status => return Err(NetworkError::Status(url.to_string(), status)),
+    };
+
+    let mut hasher = expected.map(|(hash_type, _)| Hasher::new(hash_type));
+    let mut file = if resumed {
+        if let Some(hasher) = &mut hasher {
+            let existing = File::open(part_path).await?;
+            hasher
+                .update_reader(existing)
+                .await?;
+        }
+        OpenOptions::new()
+            .append(true)
+            .open(part_path)
+            .await?
+    } else {
+        File::create(part_path).await?
+    };
+
+    let offset = if resumed { existing } else { 0 };
+    let total = response
+        .content_length()
+        .map(|length| length + offset);
+    let mut written = offset;
+
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        if let Some(hasher) = &mut hasher {
+            hasher.update(&chunk);
+        }
+        written += chunk.len() as u64;
+    }
+    file.flush().await?;
+    file.sync_all().await?;
+
+    if let Some(total) = total {
+        if written != total {
+            return Err(NetworkError::Incomplete(url.to_string(), written, total));
+        }
+    }
+
+    if let (Some(hasher), Some((_, hash))) = (hasher, expected) {
+        let actual = hasher.finish();
+        if !actual.eq_ignore_ascii_case(hash) {
+            return Err(NetworkError::HashMismatch {
+                url: url.to_string(),
+                expected: hash.to_string(),
+                actual,
+            });
+        }
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use crate::utils::hash::HashType;
+    use crate::utils::net::download;
+    use crate::utils::testing::{serve_http, test_dir};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Tests that an interrupted download is resumed using a range
+    /// request and verified against the expected hash
+    #[tokio::test]
+    async fn test_download_resume() {
+        let body: Vec<u8> = (0..64 * 1024u32)
+            .map(|value| (value % 251) as u8)
+            .collect();
+        let hash = sha1_smol::Sha1::from(&body)
+            .digest()
+            .to_string();
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let server_body = body.clone();
+        let server_requests = requests.clone();
+        let (address, _) = serve_http(move |request| {
+            let start = request
+                .lines()
+                .find_map(|line| line.strip_prefix("range: bytes="))
+                .and_then(|range| {
+                    range
+                        .trim_end_matches('-')
+                        .parse::<usize>()
+                        .ok()
+                });
+
+            let first = server_requests.fetch_add(1, Ordering::SeqCst) == 0;
+            match start {
+                Some(start) => {
+                    let mut response = format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n",
+                        server_body.len() - start,
+                        start,
+                        server_body.len() - 1,
+                        server_body.len()
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(&server_body[start..]);
+                    response
+                }
+                None => {
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        server_body.len()
+                    )
+                    .into_bytes();
+                    // Only send half of the body for the first request
+                    let end = if first {
+                        server_body.len() / 2
+                    } else {
+                        server_body.len()
+                    };
+                    response.extend_from_slice(&server_body[..end]);
+                    response
+                }
+            }
+        })
+        .await;
+
+        let path = test_dir("download").join("file.bin");
+        download(
+            &format!("http://{address}/file.bin"),
+            &path,
+            Some((HashType::SHA1, &hash)),
+        )
+        .await
+        .unwrap();
+
+        let contents = tokio::fs::read(&path)
+            .await
+            .unwrap();
+        assert_eq!(contents, body);
+        assert!(requests.load(Ordering::SeqCst) >= 2);
+        assert!(!path
+            .with_file_name("file.bin.part")
+            .exists());
+    }
+}
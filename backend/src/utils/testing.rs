@@ -0,0 +1,54 @@
+use std::fs::{create_dir_all, remove_dir_all};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Creates an empty directory for a test named `jars-test-{name}` within
+/// the temp directory removing anything left from previous runs
+pub fn test_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("jars-test-{name}"));
+    if path.exists() {
+        remove_dir_all(&path).unwrap();
+    }
+    create_dir_all(&path).unwrap();
+    path
+}
+
+/// Starts a HTTP server on a random local port which responds to each
+/// request with the response created by `respond` from the lowercase
+/// request head. Connections are closed after each response. Returns
+/// the address of the server and the task running it
+pub async fn serve_http<F>(respond: F) -> (SocketAddr, JoinHandle<()>)
+where
+    F: Fn(&str) -> Vec<u8> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !request
+                .windows(4)
+                .any(|window| window == b"\r\n\r\n")
+            {
+                let count = stream
+                    .read(&mut buffer)
+                    .await
+                    .unwrap();
+                request.extend_from_slice(&buffer[..count]);
+            }
+            let request = String::from_utf8_lossy(&request).to_lowercase();
+            let response = respond(&request);
+            let _ = stream
+                .write_all(&response)
+                .await;
+            let _ = stream.shutdown().await;
+        }
+    });
+    (address, server)
+}
@@ -1,7 +1,13 @@
-use crate::utils::constants::MANIFEST_URL;
+use crate::utils::archive::{metadata_archive, ArchiveError, ArchiveKind};
+use crate::utils::endpoints::endpoints;
+use crate::utils::http_cache::{http_cache, HttpCacheError};
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use std::cmp::Ordering;
+use std::convert::Infallible;
+use std::fmt::{self, Display, Formatter};
 use std::io;
+use std::str::FromStr;
 use thiserror::Error;
 
 #[derive(Debug, Deserialize)]
@@ -40,27 +46,443 @@ pub struct VersionManifest {
     pub versions: Vec<Version>,
 }
 
+impl VersionManifest {
+    /// Finds the version with the provided id
+    pub fn get(&self, id: &str) -> Option<&Version> {
+        self.versions
+            .iter()
+            .find(|version| version.id == id)
+    }
+}
+
+/// Java version required by a version package
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct JavaVersion {
+    /// The name of the runtime component (e.g. java-runtime-gamma)
+    pub component: String,
+    pub major_version: u16,
+}
+
+/// Downloadable file from a version package
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct PackageDownload {
+    pub sha1: String,
+    pub size: u64,
+    pub url: String,
+}
+
+impl PackageDownload {
+    /// The download url with the endpoint rewrites applied
+    pub fn get_url(&self) -> String {
+        endpoints().rewrite(&self.url)
+    }
+}
+
+/// Downloads for the jars and mappings of a version. Older
+/// versions don't have mappings or server downloads
+#[derive(Debug, Deserialize)]
+pub struct PackageDownloads {
+    pub client: Option<PackageDownload>,
+    pub client_mappings: Option<PackageDownload>,
+    pub server: Option<PackageDownload>,
+    pub server_mappings: Option<PackageDownload>,
+}
+
+/// Maven artifact download for a library
+#[derive(Debug, Deserialize)]
+pub struct LibraryArtifact {
+    /// Path of the artifact in the maven repository layout
+    pub path: String,
+    pub sha1: String,
+    pub size: u64,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LibraryDownloads {
+    pub artifact: Option<LibraryArtifact>,
+}
+
+/// Library used by a version. Libraries with rules are only
+/// used on specific platforms
+#[derive(Debug, Deserialize)]
+pub struct Library {
+    /// Maven coordinate of the library
+    pub name: String,
+    pub downloads: Option<LibraryDownloads>,
+    #[serde(default)]
+    pub rules: Vec<serde_json::Value>,
+}
+
+/// Per-version package JSON which is fetched from the url
+/// of the version in the version manifest
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionPackage {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub version_type: VersionType,
+    pub downloads: PackageDownloads,
+    /// Missing for versions older than 1.7
+    pub java_version: Option<JavaVersion>,
+    /// Level of player safety features supported by the version
+    pub compliance_level: Option<u8>,
+    #[serde(default)]
+    pub libraries: Vec<Library>,
+    pub main_class: Option<String>,
+    pub time: DateTime<Utc>,
+    pub release_time: DateTime<Utc>,
+}
+
+impl VersionPackage {
+    /// The major Java version required to run this version. Versions
+    /// without a Java version predate the field and run on Java 8
+    pub fn java_major_version(&self) -> u16 {
+        self.java_version
+            .as_ref()
+            .map(|version| version.major_version)
+            .unwrap_or(8)
+    }
+
+    /// The official server mappings download if the version has them
+    pub fn server_mappings(&self) -> Option<&PackageDownload> {
+        self.downloads
+            .server_mappings
+            .as_ref()
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum VersionsError {
     #[error(transparent)]
     IO(#[from] io::Error),
     #[error(transparent)]
     Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    Cache(#[from] HttpCacheError),
+    #[error(transparent)]
+    Parse(#[from] serde_json::Error),
+    #[error(transparent)]
+    Archive(#[from] ArchiveError),
 }
 
-/// Load the versions manifest from the `MANIFEST_URL` this is a JSON value
-/// and is parsed into the VersionManifest struct.
+/// Name the version manifest snapshots are archived as
+const MANIFEST_ARCHIVE_NAME: &str = "version_manifest";
+
+/// Load the versions manifest from the manifest url this is a JSON value
+/// and is parsed into the VersionManifest struct. Changed manifests are
+/// archived in the metadata archive.
 pub async fn get_versions() -> Result<VersionManifest, VersionsError> {
-    let manifest = reqwest::get(MANIFEST_URL)
-        .await?
-        .json::<VersionManifest>()
+    let url = &endpoints().manifest_url;
+    let contents = http_cache().get(url).await?;
+    let manifest = serde_json::from_slice::<VersionManifest>(&contents)?;
+    metadata_archive()
+        .archive(
+            ArchiveKind::VersionManifest,
+            MANIFEST_ARCHIVE_NAME,
+            url,
+            &contents,
+        )
         .await?;
     Ok(manifest)
 }
 
+/// Loads the package JSON for the provided manifest version from its url
+pub async fn get_version_package(version: &Version) -> Result<VersionPackage, VersionsError> {
+    let url = endpoints().rewrite(&version.url);
+    let contents = http_cache().get(&url).await?;
+    let package = serde_json::from_slice::<VersionPackage>(&contents)?;
+    Ok(package)
+}
+
+/// Stage of a release version. Stages are ordered so that snapshots
+/// come before pre-releases which come before release candidates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReleaseStage {
+    /// New style snapshots of a release (e.g. 26.1-snapshot-1)
+    Snapshot(u32),
+    /// Pre-release (e.g. 1.19-pre1 or 1.14 Pre-Release 1)
+    PreRelease(u32),
+    /// Release candidate (e.g. 1.19-rc1)
+    ReleaseCandidate(u32),
+    Release,
+}
+
+/// The parsed form of a Minecraft version id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionKind {
+    /// Release version (e.g. 1.19.2) and its pre-releases
+    Release {
+        numbers: Vec<u32>,
+        stage: ReleaseStage,
+    },
+    /// Weekly snapshot (e.g. 22w13a)
+    Snapshot { year: u32, week: u32, build: char },
+    /// Versions that don't follow any known format (e.g. old alpha
+    /// and beta versions or april fools versions)
+    Unknown,
+}
+
+/// Minecraft version which can be ordered against other versions. Release
+/// versions are ordered by their version numbers and stage, snapshots by
+/// their year, week and build. Comparisons between other kinds of versions
+/// use the manifest release times which are only known when the version
+/// was created from the manifest (see `from_manifest`)
+#[derive(Debug, Clone)]
+pub struct MinecraftVersion {
+    pub id: String,
+    pub kind: VersionKind,
+    /// Release time from the version manifest
+    pub release_time: Option<DateTime<Utc>>,
+}
+
+impl MinecraftVersion {
+    /// Parses the provided version id
+    pub fn parse(id: &str) -> Self {
+        let id = id.trim();
+        Self {
+            id: id.to_string(),
+            kind: parse_kind(id).unwrap_or(VersionKind::Unknown),
+            release_time: None,
+        }
+    }
+
+    /// Creates a version from a manifest entry including its release time
+    pub fn from_manifest(version: &Version) -> Self {
+        let mut parsed = Self::parse(&version.id);
+        parsed.release_time = Some(version.release_time);
+        parsed
+    }
+
+    /// Whether the version is a full release
+    pub fn is_release(&self) -> bool {
+        matches!(
+            self.kind,
+            VersionKind::Release {
+                stage: ReleaseStage::Release,
+                ..
+            }
+        )
+    }
+}
+
+impl Display for MinecraftVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+impl FromStr for MinecraftVersion {
+    type Err = Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(Self::parse(value))
+    }
+}
+
+impl PartialEq for MinecraftVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for MinecraftVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (&self.kind, &other.kind) {
+            (
+                VersionKind::Release { numbers, stage },
+                VersionKind::Release {
+                    numbers: other_numbers,
+                    stage: other_stage,
+                },
+            ) => Some(compare_numbers(numbers, other_numbers).then(stage.cmp(other_stage))),
+            (
+                VersionKind::Snapshot { year, week, build },
+                VersionKind::Snapshot {
+                    year: other_year,
+                    week: other_week,
+                    build: other_build,
+                },
+            ) => Some((year, week, build).cmp(&(other_year, other_week, other_build))),
+            (VersionKind::Unknown, VersionKind::Unknown) if self.id == other.id => {
+                Some(Ordering::Equal)
+            }
+            _ => match (self.release_time, other.release_time) {
+                (Some(time), Some(other_time)) => Some(time.cmp(&other_time)),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Compares version numbers treating missing numbers as zero
+/// (e.g. 1.19 is equal to 1.19.0)
+fn compare_numbers(a: &[u32], b: &[u32]) -> Ordering {
+    let length = a.len().max(b.len());
+    (0..length)
+        .map(|index| {
+            let left = a.get(index).unwrap_or(&0);
+            let right = b.get(index).unwrap_or(&0);
+            left.cmp(right)
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Parses the kind of version from the version id
+fn parse_kind(id: &str) -> Option<VersionKind> {
+    if let Some(snapshot) = parse_snapshot(id) {
+        return Some(snapshot);
+    }
+
+    let lower = id.to_ascii_lowercase();
+    let (numbers, stage) = if let Some((numbers, number)) = lower.split_once(" pre-release ") {
+        (numbers, ReleaseStage::PreRelease(number.parse().ok()?))
+    } else if let Some((numbers, number)) = lower.split_once("-snapshot-") {
+        (numbers, ReleaseStage::Snapshot(number.parse().ok()?))
+    } else if let Some((numbers, number)) = lower.split_once("-pre") {
+        (numbers, ReleaseStage::PreRelease(number.parse().ok()?))
+    } else if let Some((numbers, number)) = lower.split_once("-rc") {
+        (
+            numbers,
+            ReleaseStage::ReleaseCandidate(number.parse().ok()?),
+        )
+    } else {
+        (lower.as_str(), ReleaseStage::Release)
+    };
+
+    let numbers = numbers
+        .split('.')
+        .map(|value| {
+            if value.is_empty()
+                || !value
+                    .bytes()
+                    .all(|byte| byte.is_ascii_digit())
+            {
+                return None;
+            }
+            value.parse::<u32>().ok()
+        })
+        .collect::<Option<Vec<u32>>>()?;
+    if numbers.len() < 2 {
+        return None;
+    }
+    Some(VersionKind::Release { numbers, stage })
+}
+
+/// Parses weekly snapshot versions (e.g. 22w13a)
+fn parse_snapshot(id: &str) -> Option<VersionKind> {
+    let (year, rest) = id.split_once('w')?;
+    if year.len() != 2 || rest.len() != 3 {
+        return None;
+    }
+    let (week, build) = rest.split_at(2);
+    let build = build
+        .chars()
+        .next()
+        .filter(|build| build.is_ascii_lowercase())?;
+    if !week
+        .bytes()
+        .all(|byte| byte.is_ascii_digit())
+    {
+        return None;
+    }
+    Some(VersionKind::Snapshot {
+        year: year.parse().ok()?,
+        week: week.parse().ok()?,
+        build,
+    })
+}
+
+#[derive(Debug, Error)]
+pub enum VersionRangeError {
+    #[error("Invalid version range comparator \"{0}\"")]
+    InvalidComparator(String),
+}
+
+/// Operator for a version range comparator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RangeOperator {
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+}
+
+/// Range of Minecraft versions made up of comparators separated by spaces
+/// or commas that must all match (e.g. `>=1.17 <1.20`). Comparators without
+/// an operator must match exactly and `*` matches all versions
+#[derive(Debug, Clone)]
+pub struct VersionRange {
+    comparators: Vec<(RangeOperator, MinecraftVersion)>,
+}
+
+impl VersionRange {
+    /// Parses a version range
+    pub fn parse(value: &str) -> Result<Self, VersionRangeError> {
+        let comparators = value
+            .split(|char: char| char.is_whitespace() || char == ',')
+            .filter(|value| !value.is_empty() && *value != "*")
+            .map(|comparator| {
+                let (operator, version) = [
+                    (">=", RangeOperator::GreaterEqual),
+                    ("<=", RangeOperator::LessEqual),
+                    (">", RangeOperator::Greater),
+                    ("<", RangeOperator::Less),
+                    ("=", RangeOperator::Equal),
+                ]
+                .into_iter()
+                .find_map(|(prefix, operator)| {
+                    comparator
+                        .strip_prefix(prefix)
+                        .map(|version| (operator, version))
+                })
+                .unwrap_or((RangeOperator::Equal, comparator));
+                if version.is_empty() {
+                    return Err(VersionRangeError::InvalidComparator(comparator.to_string()));
+                }
+                Ok((operator, MinecraftVersion::parse(version)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { comparators })
+    }
+
+    /// Checks whether the version is within this range. Versions that
+    /// can't be compared against the range bounds aren't included
+    pub fn contains(&self, version: &MinecraftVersion) -> bool {
+        self.comparators
+            .iter()
+            .all(|(operator, bound)| {
+                let Some(ordering) = version.partial_cmp(bound) else {
+                    return false;
+                };
+                match operator {
+                    RangeOperator::Equal => ordering.is_eq(),
+                    RangeOperator::Greater => ordering.is_gt(),
+                    RangeOperator::GreaterEqual => ordering.is_ge(),
+                    RangeOperator::Less => ordering.is_lt(),
+                    RangeOperator::LessEqual => ordering.is_le(),
+                }
+            })
+    }
+}
+
+impl FromStr for VersionRange {
+    type Err = VersionRangeError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::utils::versions::{get_versions, VersionManifest, VersionType};
+    use crate::utils::versions::{
+        get_versions, MinecraftVersion, ReleaseStage, VersionKind, VersionManifest, VersionPackage,
+        VersionRange, VersionType,
+    };
 
     /// Retrieves a the current version JSON from Minecraft
     /// and check it.
@@ -99,4 +521,137 @@ mod test {
 
         check_version_manifest(parsed);
     }
+
+    /// Tests parsing the different version formats
+    #[test]
+    pub fn test_parse_minecraft_version() {
+        let cases = [
+            ("1.19.2", vec![1, 19, 2], ReleaseStage::Release),
+            ("1.19-pre1", vec![1, 19], ReleaseStage::PreRelease(1)),
+            (
+                "1.16.5-rc1",
+                vec![1, 16, 5],
+                ReleaseStage::ReleaseCandidate(1),
+            ),
+            (
+                "1.14 Pre-Release 3",
+                vec![1, 14],
+                ReleaseStage::PreRelease(3),
+            ),
+            ("26.1-snapshot-2", vec![26, 1], ReleaseStage::Snapshot(2)),
+        ];
+        for (id, numbers, stage) in cases {
+            assert_eq!(
+                MinecraftVersion::parse(id).kind,
+                VersionKind::Release { numbers, stage },
+                "{id}"
+            );
+        }
+        assert_eq!(
+            MinecraftVersion::parse("22w13a").kind,
+            VersionKind::Snapshot {
+                year: 22,
+                week: 13,
+                build: 'a'
+            }
+        );
+        for id in ["b1.7.3", "3D Shareware v1.34", "20w14infinite", "1.RV-Pre1"] {
+            assert_eq!(
+                MinecraftVersion::parse(id).kind,
+                VersionKind::Unknown,
+                "{id}"
+            );
+        }
+    }
+
+    /// Tests the ordering of versions including snapshots ordered
+    /// using their manifest release times
+    #[test]
+    pub fn test_version_ordering() {
+        let ordered = [
+            "1.8",
+            "1.8.9",
+            "1.19-pre1",
+            "1.19-rc2",
+            "1.19",
+            "1.19.0.1",
+            "1.19.2",
+        ];
+        for pair in ordered.windows(2) {
+            let (a, b) = (
+                MinecraftVersion::parse(pair[0]),
+                MinecraftVersion::parse(pair[1]),
+            );
+            assert!(a < b, "{a} < {b}");
+        }
+        assert_eq!(
+            MinecraftVersion::parse("1.19"),
+            MinecraftVersion::parse("1.19.0")
+        );
+        assert!(MinecraftVersion::parse("22w13a") < MinecraftVersion::parse("22w14a"));
+        assert_eq!(
+            MinecraftVersion::parse("22w13a").partial_cmp(&MinecraftVersion::parse("1.19")),
+            None
+        );
+
+        let contents = include_bytes!("../../test/version_manifest.json");
+        let manifest = serde_json::from_slice::<VersionManifest>(contents).unwrap();
+        let find = |id: &str| {
+            manifest
+                .versions
+                .iter()
+                .find(|version| version.id == id)
+                .map(MinecraftVersion::from_manifest)
+                .unwrap()
+        };
+        assert!(find("1.18.2") < find("22w13a"));
+        assert!(find("22w13a") < find("1.19"));
+    }
+
+    /// Tests matching versions against ranges
+    #[test]
+    pub fn test_version_range() {
+        let range: VersionRange = ">=1.17 <1.20"
+            .parse()
+            .unwrap();
+        for id in ["1.17", "1.18.2", "1.19.4", "1.20-pre1"] {
+            assert!(range.contains(&MinecraftVersion::parse(id)), "{id}");
+        }
+        for id in ["1.16.5", "1.17-rc1", "1.20", "22w13a"] {
+            assert!(!range.contains(&MinecraftVersion::parse(id)), "{id}");
+        }
+
+        let exact = VersionRange::parse("1.19").unwrap();
+        assert!(exact.contains(&MinecraftVersion::parse("1.19.0")));
+        assert!(VersionRange::parse("*")
+            .unwrap()
+            .contains(&MinecraftVersion::parse("b1.7.3")));
+        assert!(VersionRange::parse(">=").is_err());
+    }
+
+    /// Tests parsing a local copy of a version package
+    #[test]
+    pub fn test_parse_version_package() {
+        let contents = include_bytes!("../../test/version_package.json");
+        let package = serde_json::from_slice::<VersionPackage>(contents).unwrap();
+        assert_eq!(package.id, "1.19.2");
+        assert_eq!(package.version_type, VersionType::Release);
+        assert_eq!(package.java_major_version(), 17);
+        assert_eq!(package.compliance_level, Some(1));
+        let mappings = package
+            .server_mappings()
+            .unwrap();
+        assert_eq!(mappings.sha1, "5555555555555555555555555555555555555555");
+        assert!(package
+            .downloads
+            .server
+            .is_some());
+        assert_eq!(package.libraries.len(), 2);
+        assert_eq!(
+            package.libraries[1]
+                .rules
+                .len(),
+            1
+        );
+    }
 }
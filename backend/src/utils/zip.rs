@@ -1,17 +1,23 @@
-use crate::utils::files::{delete_existing, ensure_parent_exists, move_file};
+use crate::utils::files::{delete_existing, ensure_dir_exists, ensure_parent_exists, move_file};
+use crate::utils::manifest::{Manifest, ManifestError};
 use async_zip::{
     error::ZipError as ZipErrorInternal,
     tokio::{read::seek::ZipFileReader, write::ZipFileWriter},
-    ZipEntryBuilder,
+    AttributeCompatibility, Compression, ZipDateTime, ZipEntry, ZipEntryBuilder,
 };
+use chrono::{TimeZone, Utc};
 use futures::AsyncWriteExt;
+use log::warn;
 use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    env,
     fmt::Debug,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 use thiserror::Error;
 use tokio::{
-    fs::{create_dir_all, File},
+    fs::{symlink_metadata, File},
     io::{self, copy, AsyncReadExt},
 };
 
@@ -19,6 +25,10 @@ use tokio::{
 pub enum ZipError {
     #[error("Missing file")]
     MissingFile,
+    #[error("Unsafe zip entry path: {0}")]
+    UnsafeEntry(String),
+    #[error(transparent)]
+    Manifest(#[from] ManifestError),
     #[error(transparent)]
     IO(#[from] io::Error),
     #[error(transparent)]
@@ -27,80 +37,396 @@ pub enum ZipError {
 
 type ZipResult<T> = Result<T, ZipError>;
 
+/// Environment variable containing a unix timestamp (in seconds) used
+/// for entries written in deterministic mode
+pub const SOURCE_DATE_EPOCH_KEY: &str = "SOURCE_DATE_EPOCH";
+
+/// Timestamp used for deterministic entries when SOURCE_DATE_EPOCH isn't
+/// set (1980-02-01 00:00:00 UTC, the earliest date safe for all zip tools)
+const DEFAULT_ENTRY_TIMESTAMP: i64 = 318211200;
+
+/// Latest timestamp that can be represented in a zip entry
+/// (2107-12-31 23:59:58 UTC)
+const MAX_ENTRY_TIMESTAMP: i64 = 4354819198;
+
+/// Unix mode used for directories in deterministic mode
+const DETERMINISTIC_DIR_MODE: u16 = 0o040755;
+/// Unix mode used for files in deterministic mode
+const DETERMINISTIC_FILE_MODE: u16 = 0o100644;
+
+/// Controls how the entries of a zip are written when it is rewritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZipWriteMode {
+    /// Entries keep their original order and timestamps
+    Preserve,
+    /// Entries are sorted (with the manifest first), given the provided
+    /// timestamp and normalised attributes so that identical inputs
+    /// produce byte-identical outputs
+    Deterministic(ZipDateTime),
+}
+
+impl ZipWriteMode {
+    /// Creates a deterministic mode using the timestamp from the
+    /// SOURCE_DATE_EPOCH environment variable if it's set and valid
+    /// otherwise DEFAULT_ENTRY_TIMESTAMP is used
+    pub fn deterministic() -> Self {
+        let timestamp = env::var(SOURCE_DATE_EPOCH_KEY)
+            .ok()
+            .and_then(|value| {
+                value
+                    .trim()
+                    .parse::<i64>()
+                    .ok()
+            });
+        Self::from_timestamp(timestamp.unwrap_or(DEFAULT_ENTRY_TIMESTAMP))
+    }
+
+    /// Creates a deterministic mode from the provided unix timestamp. The
+    /// timestamp is clamped to the range representable by zip entries
+    pub fn from_timestamp(timestamp: i64) -> Self {
+        let timestamp = timestamp.clamp(DEFAULT_ENTRY_TIMESTAMP, MAX_ENTRY_TIMESTAMP);
+        let date = Utc
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .unwrap_or_default();
+        Self::Deterministic(ZipDateTime::from_chrono(&date))
+    }
+
+    /// Creates the builder for an entry named `name` being
+    /// copied from `entry`
+    fn builder(&self, entry: &ZipEntry, name: &str) -> ZipEntryBuilder {
+        let builder = ZipEntryBuilder::new(name.to_string(), entry.compression());
+        match self {
+            Self::Preserve => builder.last_modification_date(*entry.last_modification_date()),
+            Self::Deterministic(date) => {
+                let (compression, mode) = if entry.dir() {
+                    (Compression::Stored, DETERMINISTIC_DIR_MODE)
+                } else {
+                    (entry.compression(), DETERMINISTIC_FILE_MODE)
+                };
+                builder
+                    .compression(compression)
+                    .last_modification_date(*date)
+                    .attribute_compatibility(AttributeCompatibility::Unix)
+                    .unix_permissions(mode)
+            }
+        }
+    }
+}
+
+/// Ordering used for entries in deterministic mode. The META-INF directory
+/// and manifest come first (as expected by jar readers) followed by all
+/// other entries ordered by name
+fn compare_entries(a: &str, b: &str) -> Ordering {
+    fn priority(name: &str) -> u8 {
+        match name {
+            "META-INF/" => 0,
+            "META-INF/MANIFEST.MF" => 1,
+            _ => 2,
+        }
+    }
+    priority(a)
+        .cmp(&priority(b))
+        .then_with(|| a.cmp(b))
+}
+
+/// Action to take for an entry when rewriting a zip
+pub enum EntryAction {
+    /// Copy the entry contents unchanged
+    Copy,
+    /// Exclude the entry from the output
+    Skip,
+    /// Replace the entry contents with the result of the function
+    Transform(fn(Vec<u8>) -> ZipResult<Vec<u8>>),
+    /// Replace the entry contents with the provided contents
+    Replace(Vec<u8>),
+    /// Write the provided contents under a new name
+    Rename(String, Vec<u8>),
+}
+
+/// Copies the entries of the `input` zip into a new zip at the
+/// `output` path using `action` to decide what happens to each
+/// entry. Entries are written according to `mode` and deterministic
+/// ordering uses the names the entries are written as
+pub async fn rewrite_zip<F: FnMut(&str) -> EntryAction>(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    mode: ZipWriteMode,
+    mut action: F,
+) -> ZipResult<()> {
+    let input = input.as_ref();
+    let output = output.as_ref();
+
+    if !input.exists() {
+        return Err(ZipError::MissingFile);
+    }
+    delete_existing(output).await?;
+
+    let file = File::open(input).await?;
+    let mut zip = ZipFileReader::new(file).await?;
+    let entries = zip.file().entries();
+    let out_file = File::create(output).await?;
+    let mut out_zip = ZipFileWriter::new(out_file);
+
+    // Actions are resolved first so that entries can be
+    // ordered by the name they are written as
+    let mut planned = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let entry = entry.entry();
+        let action = action(entry.filename());
+        let name = match &action {
+            EntryAction::Skip => continue,
+            EntryAction::Rename(name, _) => name.clone(),
+            _ => entry.filename().to_string(),
+        };
+        planned.push((i, name, action));
+    }
+    if let ZipWriteMode::Deterministic(_) = mode {
+        planned.sort_by(|a, b| compare_entries(&a.1, &b.1));
+    }
+
+    for (i, name, action) in planned {
+        let entry = zip
+            .file()
+            .entries()
+            .get(i)
+            .ok_or(ZipError::MissingFile)?
+            .entry();
+
+        let b = mode
+            .builder(entry, &name)
+            .build();
+
+        if entry.dir() {
+            out_zip
+                .write_entry_whole(b, &[])
+                .await?;
+        } else if let EntryAction::Transform(transform) = action {
+            let mut reader = zip.entry(i).await?;
+            let mut contents = Vec::new();
+            reader
+                .read_to_end(&mut contents)
+                .await?;
+            let contents = transform(contents)?;
+            out_zip
+                .write_entry_whole(b, &contents)
+                .await?;
+        } else if let EntryAction::Replace(contents) | EntryAction::Rename(_, contents) = action {
+            out_zip
+                .write_entry_whole(b, &contents)
+                .await?;
+        } else {
+            let mut stream = out_zip
+                .write_entry_stream(b)
+                .await?;
+
+            let mut reader = zip.entry(i).await?;
+
+            let mut buffer = [0u8; 1024];
+
+            loop {
+                let count = reader
+                    .read(&mut buffer)
+                    .await?;
+
+                if count == 0 {
+                    break;
+                }
+
+                let slice = &buffer[..count];
+                stream
+                    .write_all(slice)
+                    .await?;
+            }
+
+            stream.close().await?;
+        }
+    }
+    out_zip.close().await?;
+    Ok(())
+}
+
 /// Removes files that match the provided names from the
 /// zip at the provided path. Copies all the contents of
 /// the provided `input` zip file to the `output` path
 /// but excluding any file / directory names specified
-/// in `files`
+/// in `files`. Entries are written according to `mode`
 pub async fn remove_from_zip(
     input: impl AsRef<Path> + Debug,
     output: impl AsRef<Path> + Debug,
     files: &[&str],
+    mode: ZipWriteMode,
 ) -> Result<(), ZipError> {
     let input = input.as_ref();
     let output = output.as_ref();
 
-    if !input.exists() {
-        return Err(ZipError::MissingFile);
+    rewrite_zip(input, output, mode, |name| {
+        if files.contains(&name) {
+            EntryAction::Skip
+        } else {
+            EntryAction::Copy
+        }
+    })
+    .await?;
+
+    if output.exists() {
+        move_file(output, input).await?;
     }
-    delete_existing(output).await?;
-    {
-        let file = File::open(input).await?;
-        let mut zip = ZipFileReader::new(file).await?;
-        let entries = zip.file().entries();
-        let out_file = File::create(output).await?;
-        let mut out_zip = ZipFileWriter::new(out_file);
-
-        for i in 0..entries.len() {
-            let entry = zip
-                .file()
-                .entries()
-                .get(i)
-                .ok_or(ZipError::MissingFile)?
-                .entry();
-            let name = entry.filename();
+    Ok(())
+}
 
-            // Skip ignored entries
-            if files.contains(&name) {
-                continue;
-            }
+/// Writes a copy of the jar at the `input` path without its signature
+/// to the `output` path. All signature files in META-INF (*.SF, *.RSA,
+/// *.DSA, *.EC and SIG-*) are removed and the per-entry digests are
+/// stripped from the manifest. The `input` jar is left unchanged
+pub async fn strip_signatures(
+    input: impl AsRef<Path> + Debug,
+    output: impl AsRef<Path> + Debug,
+    mode: ZipWriteMode,
+) -> ZipResult<()> {
+    let input = input.as_ref();
+    let output = output.as_ref();
 
-            let b = ZipEntryBuilder::new(name.to_string(), entry.compression()).build();
+    rewrite_zip(input, output, mode, |name| {
+        if is_signature_file(name) {
+            EntryAction::Skip
+        } else if name.eq_ignore_ascii_case(MANIFEST_PATH) {
+            EntryAction::Transform(strip_manifest_digests)
+        } else {
+            EntryAction::Copy
+        }
+    })
+    .await
+}
 
-            if entry.dir() {
-                out_zip
-                    .write_entry_whole(b, &[])
-                    .await?;
-            } else {
-                let mut stream = out_zip
-                    .write_entry_stream(b)
-                    .await?;
+/// Path of the manifest within a jar
+pub const MANIFEST_PATH: &str = "META-INF/MANIFEST.MF";
 
-                let mut reader = zip.entry(i).await?;
+/// Checks whether the provided entry name is a jar signature related
+/// file. These are files directly inside META-INF ending with .SF,
+/// .RSA, .DSA or .EC or starting with SIG- (case insensitive)
+pub fn is_signature_file(name: &str) -> bool {
+    let Some(file_name) = name.strip_prefix("META-INF/") else {
+        return false;
+    };
+    if file_name.is_empty() || file_name.contains('/') {
+        return false;
+    }
+    let file_name = file_name.to_ascii_uppercase();
+    if file_name.starts_with("SIG-") {
+        return true;
+    }
+    match file_name.rsplit_once('.') {
+        Some((_, extension)) => matches!(extension, "SF" | "RSA" | "DSA" | "EC"),
+        None => false,
+    }
+}
 
-                let mut buffer = [0u8; 1024];
+/// Removes the digest attributes (e.g. SHA-256-Digest) from the per-entry
+/// sections of the provided manifest contents. Sections left with only
+/// a Name are removed entirely and the main section is left unchanged
+pub fn strip_manifest_digests(contents: Vec<u8>) -> ZipResult<Vec<u8>> {
+    let mut manifest = Manifest::parse(&contents)?;
+    manifest.strip_digests();
+    Ok(manifest.to_bytes())
+}
 
-                loop {
-                    let count = reader
-                        .read(&mut buffer)
-                        .await?;
+/// Reads the contents of the file entry with the provided name from
+/// the zip at `input`. Returns None if the entry doesn't exist
+pub async fn read_entry(input: impl AsRef<Path>, file_name: &str) -> ZipResult<Option<Vec<u8>>> {
+    let file = File::open(input).await?;
+    let mut zip = ZipFileReader::new(file).await?;
+    let index = zip
+        .file()
+        .entries()
+        .iter()
+        .position(|entry| {
+            let entry = entry.entry();
+            !entry.dir() && entry.filename() == file_name
+        });
+    let Some(index) = index else {
+        return Ok(None);
+    };
+    let mut reader = zip.entry(index).await?;
+    let mut contents = Vec::new();
+    reader
+        .read_to_end(&mut contents)
+        .await?;
+    Ok(Some(contents))
+}
 
-                    if count == 0 {
-                        break;
-                    }
+/// Reads the names and contents of every file entry in the zip at
+/// `input` whose name is accepted by the `filter` function
+pub async fn read_files<F: Fn(&str) -> bool>(
+    input: impl AsRef<Path>,
+    filter: F,
+) -> ZipResult<Vec<(String, Vec<u8>)>> {
+    if !input.as_ref().exists() {
+        return Err(ZipError::MissingFile);
+    }
+    let file = File::open(input).await?;
+    let mut zip = ZipFileReader::new(file).await?;
+    let mut files = Vec::new();
+    for i in 0..zip.file().entries().len() {
+        let entry = zip
+            .file()
+            .entries()
+            .get(i)
+            .ok_or(ZipError::MissingFile)?
+            .entry();
+        if entry.dir() || !filter(entry.filename()) {
+            continue;
+        }
+        let name = entry.filename().to_string();
+        let mut reader = zip.entry(i).await?;
+        let mut contents = Vec::new();
+        reader
+            .read_to_end(&mut contents)
+            .await?;
+        files.push((name, contents));
+    }
+    Ok(files)
+}
 
-                    let slice = &buffer[..count];
-                    stream
-                        .write_all(slice)
-                        .await?;
-                }
+/// Reads and parses the manifest of the jar at `input`. Returns
+/// None if the jar doesn't have a manifest
+pub async fn read_manifest(input: impl AsRef<Path>) -> ZipResult<Option<Manifest>> {
+    match read_entry(input, MANIFEST_PATH).await? {
+        Some(contents) => Ok(Some(Manifest::parse(&contents)?)),
+        None => Ok(None),
+    }
+}
 
-                stream.close().await?;
-            }
-        }
-        out_zip.close().await?;
+/// Replaces the manifest of the jar at the `input` path with the
+/// provided `manifest`. The `output` path is used as the temporary
+/// file that replaces the `input` jar. Returns ZipError::MissingFile
+/// if the jar doesn't already have a manifest
+pub async fn write_manifest(
+    input: impl AsRef<Path> + Debug,
+    output: impl AsRef<Path> + Debug,
+    manifest: &Manifest,
+    mode: ZipWriteMode,
+) -> ZipResult<()> {
+    let input = input.as_ref();
+    let output = output.as_ref();
+
+    if read_entry(input, MANIFEST_PATH)
+        .await?
+        .is_none()
+    {
+        return Err(ZipError::MissingFile);
     }
 
+    let contents = manifest.to_bytes();
+    rewrite_zip(input, output, mode, |name| {
+        if name == MANIFEST_PATH {
+            EntryAction::Replace(contents.clone())
+        } else {
+            EntryAction::Copy
+        }
+    })
+    .await?;
+
     if output.exists() {
         move_file(output, input).await?;
     }
@@ -140,15 +466,36 @@ pub async fn extract_file(input: &PathBuf, output: &PathBuf, file_name: &str) ->
 /// `output` directory. Will return ZipError::Missing file if the input
 /// file does not exist.
 pub async fn unzip(input: &PathBuf, output: &PathBuf) -> ZipResult<()> {
-    if !input.exists() {
+    unzip_filtered(input, output, |_| true).await
+}
+
+/// Unzips the zip at the `input` path and extracts its contents to the
+/// `output` directory. Will return ZipError::Missing file if the input
+/// file does not exist. Will only unzip files when their names return
+/// yes in the filer function.
+///
+/// Entry names are normalised and entries that would be written outside
+/// of the `output` directory (absolute names, `..` components or through
+/// existing symbolic links) result in ZipError::UnsafeEntry. Symbolic
+/// link entries are skipped and only the first of any duplicate file
+/// entries is extracted (later duplicates are skipped).
+pub async fn unzip_filtered<F: Fn(&str) -> bool>(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    filter: F,
+) -> ZipResult<()> {
+    if !input.as_ref().exists() {
         return Err(ZipError::MissingFile);
     }
 
+    let output = output.as_ref();
     let file = File::open(input).await?;
 
     let mut zip = ZipFileReader::new(file).await?;
     let entries = zip.file().entries();
 
+    let mut extracted = HashSet::new();
+
     for i in 0..entries.len() {
         let entry = zip
             .file()
@@ -156,11 +503,34 @@ pub async fn unzip(input: &PathBuf, output: &PathBuf) -> ZipResult<()> {
             .get(i)
             .ok_or(ZipError::MissingFile)?
             .entry();
-        let out_path = output.join(entry.filename());
-        delete_existing(&out_path).await?;
+        let name = entry.filename();
+        if !filter(name) {
+            continue;
+        }
+
+        if is_symlink(entry) {
+            warn!("Skipping symbolic link entry in zip: {name}");
+            continue;
+        }
+
+        let relative = entry_path(name)?;
+        if relative
+            .as_os_str()
+            .is_empty()
+        {
+            continue;
+        }
+        check_no_symlinks(output, &relative).await?;
+
+        let out_path = output.join(&relative);
         if entry.dir() {
-            create_dir_all(out_path).await?;
+            ensure_dir_exists(out_path).await?;
         } else {
+            if !extracted.insert(relative) {
+                warn!("Skipping duplicate entry in zip: {name}");
+                continue;
+            }
+            delete_existing(&out_path).await?;
             ensure_parent_exists(&out_path).await?;
             let mut reader = zip.entry(i).await?;
             let mut out_file = File::create(out_path).await?;
@@ -171,46 +541,437 @@ pub async fn unzip(input: &PathBuf, output: &PathBuf) -> ZipResult<()> {
     Ok(())
 }
 
-/// Unzips the zip at the `input` path and extracts its contents to the
-/// `output` directory. Will return ZipError::Missing file if the input
-/// file does not exist. Will only unzip files when their names return
-/// yes in the filer function
-pub async fn unzip_filtered<F: Fn(&str) -> bool>(
-    input: impl AsRef<Path>,
-    output: impl AsRef<Path>,
-    filter: F,
-) -> ZipResult<()> {
-    if !input.as_ref().exists() {
-        return Err(ZipError::MissingFile);
+/// Validates and normalises the name of a zip entry into a relative path.
+/// Backslashes are treated as separators and empty or `.` components are
+/// removed. Names that are absolute or contain `..` or drive prefix
+/// components are rejected with ZipError::UnsafeEntry
+pub fn entry_path(name: &str) -> ZipResult<PathBuf> {
+    let unsafe_entry = || ZipError::UnsafeEntry(name.to_string());
+    let normalised = name.replace('\\', "/");
+    if normalised.starts_with('/') || normalised.contains('\0') {
+        return Err(unsafe_entry());
+    }
+    let mut path = PathBuf::new();
+    for part in normalised.split('/') {
+        match part {
+            "" | "." => continue,
+            ".." => return Err(unsafe_entry()),
+            part => {
+                // Drive prefixes (e.g. C:) replace the whole path when pushed
+                let rooted = Path::new(part)
+                    .components()
+                    .any(|component| {
+                        matches!(component, Component::Prefix(_) | Component::RootDir)
+                    });
+                if rooted {
+                    return Err(unsafe_entry());
+                }
+                path.push(part)
+            }
+        }
     }
+    Ok(path)
+}
 
-    let output = output.as_ref();
-    let file = File::open(input).await?;
+/// Ensures none of the existing paths between `output` and the `relative`
+/// entry path are symbolic links which could redirect writes outside of
+/// the output directory
+async fn check_no_symlinks(output: &Path, relative: &Path) -> ZipResult<()> {
+    let mut current = output.to_path_buf();
+    for component in relative.components() {
+        current.push(component);
+        match symlink_metadata(&current).await {
+            Ok(metadata) => {
+                if metadata
+                    .file_type()
+                    .is_symlink()
+                {
+                    return Err(ZipError::UnsafeEntry(
+                        relative
+                            .to_string_lossy()
+                            .to_string(),
+                    ));
+                }
+            }
+            // Nothing exists past this point
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
 
-    let mut zip = ZipFileReader::new(file).await?;
-    let entries = zip.file().entries();
+/// Checks whether the provided entry is a unix symbolic link
+fn is_symlink(entry: &ZipEntry) -> bool {
+    const FILE_TYPE_MASK: u16 = 0o170000;
+    const SYMLINK_TYPE: u16 = 0o120000;
+    entry
+        .unix_permissions()
+        .map(|mode| mode & FILE_TYPE_MASK == SYMLINK_TYPE)
+        .unwrap_or(false)
+}
 
-    for i in 0..entries.len() {
-        let entry = zip
+#[cfg(test)]
+mod test {
+    use crate::utils::manifest::{Manifest, MAIN_CLASS};
+    use crate::utils::testing::test_dir;
+    use crate::utils::zip::{
+        entry_path, is_signature_file, read_manifest, remove_from_zip, rewrite_zip,
+        strip_manifest_digests, strip_signatures, unzip, write_manifest, EntryAction, ZipError,
+        ZipWriteMode,
+    };
+    use async_zip::tokio::read::seek::ZipFileReader;
+    use async_zip::{
+        tokio::write::ZipFileWriter, AttributeCompatibility, Compression, ZipEntryBuilder,
+    };
+    use std::path::{Path, PathBuf};
+    use tokio::fs::{create_dir_all, read, File};
+
+    /// Creates a fresh test directory containing a zip with the
+    /// provided entries. Entries with a mode are written using the
+    /// unix attribute compatibility
+    async fn create_zip(name: &str, entries: &[(&str, Option<u16>)]) -> (PathBuf, PathBuf) {
+        let root = test_dir(&format!("zip-{name}"));
+
+        let zip_path = root.join("test.zip");
+        let file = File::create(&zip_path)
+            .await
+            .unwrap();
+        let mut writer = ZipFileWriter::new(file);
+        for (entry_name, mode) in entries {
+            let mut builder = ZipEntryBuilder::new(entry_name.to_string(), Compression::Stored);
+            if let Some(mode) = mode {
+                builder = builder
+                    .attribute_compatibility(AttributeCompatibility::Unix)
+                    .unix_permissions(*mode);
+            }
+            writer
+                .write_entry_whole(builder.build(), entry_name.as_bytes())
+                .await
+                .unwrap();
+        }
+        writer.close().await.unwrap();
+        (zip_path, root.join("out"))
+    }
+
+    /// Asserts that the provided path doesn't exist
+    fn assert_missing(path: &Path) {
+        assert!(!path.exists(), "{path:?} should not exist");
+    }
+
+    /// Tests that normal and normalisable entries are extracted
+    /// inside the output directory
+    #[tokio::test]
+    async fn test_unzip() {
+        let (zip, out) = create_zip(
+            "normal",
+            &[("a/", None), ("a/b.txt", None), ("./c\\d.txt", None)],
+        )
+        .await;
+        unzip(&zip, &out)
+            .await
+            .unwrap();
+        assert!(out.join("a/b.txt").is_file());
+        assert!(out.join("c/d.txt").is_file());
+    }
+
+    /// Tests that entries escaping the output directory are rejected
+    #[tokio::test]
+    async fn test_unsafe_entries() {
+        let mut entries = vec![
+            ("parent", "../evil.txt"),
+            ("nested-parent", "a/../../evil.txt"),
+            ("absolute", "/tmp/evil.txt"),
+            ("backslash", "..\\evil.txt"),
+        ];
+        // Drive prefixes only exist on windows
+        if cfg!(windows) {
+            entries.push(("drive", "C:/evil.txt"));
+        }
+        for (name, entry) in entries {
+            let (zip, out) = create_zip(name, &[(entry, None)]).await;
+            let result = unzip(&zip, &out).await;
+            assert!(
+                matches!(result, Err(ZipError::UnsafeEntry(_))),
+                "{entry} should be rejected"
+            );
+            assert_missing(
+                &out.parent()
+                    .unwrap()
+                    .join("evil.txt"),
+            );
+        }
+
+        // Colons are allowed outside of drive prefixes
+        assert_eq!(
+            entry_path("a/data:file.txt").unwrap(),
+            Path::new("a").join("data:file.txt")
+        );
+    }
+
+    /// Tests that symbolic link entries are skipped and that existing
+    /// symbolic links in the output aren't written through
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlinks() {
+        let (zip, out) = create_zip(
+            "symlink",
+            &[("link", Some(0o120777)), ("file.txt", Some(0o100644))],
+        )
+        .await;
+        unzip(&zip, &out)
+            .await
+            .unwrap();
+        assert_missing(&out.join("link"));
+        assert!(out.join("file.txt").is_file());
+
+        let (zip, out) = create_zip("symlink-existing", &[("link/evil.txt", None)]).await;
+        let target = out
+            .parent()
+            .unwrap()
+            .join("target");
+        create_dir_all(&target)
+            .await
+            .unwrap();
+        create_dir_all(&out)
+            .await
+            .unwrap();
+        tokio::fs::symlink(&target, out.join("link"))
+            .await
+            .unwrap();
+        let result = unzip(&zip, &out).await;
+        assert!(matches!(result, Err(ZipError::UnsafeEntry(_))));
+        assert_missing(&target.join("evil.txt"));
+    }
+
+    /// Tests that only the first of duplicate file entries is extracted
+    #[tokio::test]
+    async fn test_duplicate_entries() {
+        let (zip, out) = create_zip("duplicate", &[("a.txt", None), ("./a.txt", None)]).await;
+        unzip(&zip, &out)
+            .await
+            .unwrap();
+        assert_eq!(
+            read(out.join("a.txt"))
+                .await
+                .unwrap(),
+            b"a.txt"
+        );
+    }
+
+    /// Tests that rewriting zips with the same contents but different
+    /// entry orders and attributes produces byte-identical output with
+    /// the manifest first
+    #[tokio::test]
+    async fn test_deterministic_rewrite() {
+        let (first, _) = create_zip(
+            "deterministic-1",
+            &[
+                ("b.txt", None),
+                ("META-INF/MANIFEST.MF", None),
+                ("a/", None),
+                ("a/c.txt", Some(0o100755)),
+                ("META-INF/MOJANGCS.SF", None),
+            ],
+        )
+        .await;
+        let (second, _) = create_zip(
+            "deterministic-2",
+            &[
+                ("a/", Some(0o040700)),
+                ("a/c.txt", None),
+                ("META-INF/MOJANGCS.SF", None),
+                ("META-INF/MANIFEST.MF", Some(0o100600)),
+                ("b.txt", None),
+            ],
+        )
+        .await;
+
+        let mode = ZipWriteMode::from_timestamp(1_600_000_000);
+        for path in [&first, &second] {
+            remove_from_zip(
+                path,
+                path.with_extension("tmp"),
+                &["META-INF/MOJANGCS.SF"],
+                mode,
+            )
+            .await
+            .unwrap();
+        }
+
+        let first_bytes = read(&first).await.unwrap();
+        let second_bytes = read(&second).await.unwrap();
+        assert_eq!(first_bytes, second_bytes);
+
+        let file = File::open(&first)
+            .await
+            .unwrap();
+        let zip = ZipFileReader::new(file)
+            .await
+            .unwrap();
+        let names: Vec<&str> = zip
             .file()
             .entries()
-            .get(i)
-            .ok_or(ZipError::MissingFile)?
-            .entry();
-        let name = entry.filename();
-        if filter(name) {
-            let out_path = output.join(name);
-            delete_existing(&out_path).await?;
-            if entry.dir() {
-                create_dir_all(out_path).await?;
-            } else {
-                ensure_parent_exists(&out_path).await?;
-                let mut reader = zip.entry(i).await?;
-                let mut out_file = File::create(out_path).await?;
-                copy(&mut reader, &mut out_file).await?;
-            }
+            .iter()
+            .map(|entry| entry.entry().filename())
+            .collect();
+        assert_eq!(names, ["META-INF/MANIFEST.MF", "a/", "a/c.txt", "b.txt"]);
+
+        // Renamed entries are ordered by the name they are written as
+        let renamed = first.with_extension("renamed");
+        rewrite_zip(&first, &renamed, mode, |name| match name {
+            "b.txt" => EntryAction::Rename("0.txt".to_string(), b"b".to_vec()),
+            _ => EntryAction::Copy,
+        })
+        .await
+        .unwrap();
+        let file = File::open(&renamed)
+            .await
+            .unwrap();
+        let zip = ZipFileReader::new(file)
+            .await
+            .unwrap();
+        let names: Vec<&str> = zip
+            .file()
+            .entries()
+            .iter()
+            .map(|entry| entry.entry().filename())
+            .collect();
+        assert_eq!(names, ["META-INF/MANIFEST.MF", "0.txt", "a/", "a/c.txt"]);
+    }
+
+    /// Tests detection of signature files
+    #[test]
+    fn test_signature_files() {
+        for name in [
+            "META-INF/MOJANGCS.SF",
+            "META-INF/MOJANGCS.RSA",
+            "META-INF/mojang.dsa",
+            "META-INF/VENDOR.EC",
+            "META-INF/SIG-VENDOR",
+        ] {
+            assert!(is_signature_file(name), "{name} should be a signature file");
+        }
+        for name in [
+            "META-INF/MANIFEST.MF",
+            "META-INF/versions/9/TEST.SF",
+            "TEST.SF",
+            "META-INF/services/a.b.RSA",
+        ] {
+            assert!(
+                !is_signature_file(name),
+                "{name} shouldn't be a signature file"
+            );
         }
     }
 
-    Ok(())
+    /// Tests that digests are stripped from manifest sections while
+    /// keeping the main section and other attributes
+    #[test]
+    fn test_strip_manifest_digests() {
+        let manifest = "Manifest-Version: 1.0\r\nMain-Class: net.minecraft.server.Main\r\n\r\n\
+            Name: net/minecraft/server/Main.class\r\nSHA-256-Digest: abc\r\n\r\n\
+            Name: a/very/long/path/that/needs/a/continuation/line/because/it/is/lo\r\n ng.class\r\n\
+            SHA1-Digest: def\r\n\r\n\
+            Name: data/\r\nSHA-256-Digest: ghi\r\nSealed: true\r\n\r\n";
+        let stripped = strip_manifest_digests(manifest.as_bytes().to_vec()).unwrap();
+        assert_eq!(
+            String::from_utf8(stripped).unwrap(),
+            "Manifest-Version: 1.0\r\nMain-Class: net.minecraft.server.Main\r\n\r\n\
+            Name: data/\r\nSealed: true\r\n\r\n"
+        );
+    }
+
+    /// Tests that signature files are removed from jars
+    #[tokio::test]
+    async fn test_strip_signatures() {
+        let (zip, _) = create_zip(
+            "signatures",
+            &[
+                ("META-INF/MANIFEST.MF", None),
+                ("META-INF/MOJANGCS.SF", None),
+                ("META-INF/MOJANGCS.RSA", None),
+                ("META-INF/SIG-TEST", None),
+                ("a.class", None),
+            ],
+        )
+        .await;
+        let mut manifest = Manifest::default();
+        manifest
+            .section_mut("a.class")
+            .attributes
+            .set("SHA-256-Digest", "abc");
+        write_manifest(
+            &zip,
+            zip.with_extension("tmp"),
+            &manifest,
+            ZipWriteMode::Preserve,
+        )
+        .await
+        .unwrap();
+        let stripped = zip.with_extension("unsigned.jar");
+        strip_signatures(&zip, &stripped, ZipWriteMode::Preserve)
+            .await
+            .unwrap();
+        // The original jar is left signed
+        let file = File::open(&zip)
+            .await
+            .unwrap();
+        let original = ZipFileReader::new(file)
+            .await
+            .unwrap();
+        assert_eq!(
+            original
+                .file()
+                .entries()
+                .len(),
+            5
+        );
+        let zip = stripped;
+        let manifest = read_manifest(&zip)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(manifest.sections.is_empty());
+
+        let file = File::open(&zip)
+            .await
+            .unwrap();
+        let zip = ZipFileReader::new(file)
+            .await
+            .unwrap();
+        let names: Vec<&str> = zip
+            .file()
+            .entries()
+            .iter()
+            .map(|entry| entry.entry().filename())
+            .collect();
+        assert_eq!(names, ["META-INF/MANIFEST.MF", "a.class"]);
+    }
+
+    /// Tests editing and reading back the manifest of a jar
+    #[tokio::test]
+    async fn test_edit_manifest() {
+        let (zip, _) = create_zip(
+            "manifest",
+            &[("META-INF/MANIFEST.MF", None), ("a.class", None)],
+        )
+        .await;
+        let mut manifest = Manifest::default();
+        manifest
+            .main
+            .set(MAIN_CLASS, "org.bukkit.craftbukkit.Main");
+        write_manifest(
+            &zip,
+            zip.with_extension("tmp"),
+            &manifest,
+            ZipWriteMode::Preserve,
+        )
+        .await
+        .unwrap();
+        let read = read_manifest(&zip)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read, manifest);
+    }
 }
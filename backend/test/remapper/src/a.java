@@ -0,0 +1,19 @@
+public class a {
+    protected int b;
+
+    public a(int b) {
+        this.b = b;
+    }
+
+    int c() {
+        return b;
+    }
+
+    public static class d<T extends a> {
+        public T e;
+
+        public d(T e) {
+            this.e = e;
+        }
+    }
+}
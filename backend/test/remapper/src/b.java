@@ -0,0 +1,30 @@
+import java.util.ArrayList;
+import java.util.List;
+import java.util.function.Supplier;
+
+public class b extends a implements Supplier<a.d<b>> {
+    public b() {
+        super(2);
+    }
+
+    @Override
+    int c() {
+        return b * 3;
+    }
+
+    @Override
+    public a.d<b> get() {
+        return new a.d<>(this);
+    }
+
+    public static void main(String[] args) {
+        List<a.d<b>> list = new ArrayList<>();
+        b value = new b();
+        list.add(value.get());
+        Supplier<Integer> supplier = () -> list.get(0).e.c() + value.b;
+        e pair = new e(value, "pair");
+        f lambda = other -> other.c() + 1;
+        f reference = a::c;
+        System.out.println(supplier.get() + " " + pair.f().c() + " " + lambda.g(value) + " " + reference.g(value));
+    }
+}
@@ -0,0 +1,2 @@
+public record e(a f, String g) {
+}
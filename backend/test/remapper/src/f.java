@@ -0,0 +1,3 @@
+public interface f {
+    int g(a value);
+}
@@ -0,0 +1,6 @@
+public enum g {
+    FIRST,
+    SECOND;
+
+    private static final org.apache.logging.log4j.Logger h = null;
+}
@@ -0,0 +1,8 @@
+public class i extends j<String> {
+    @Override
+    public String k() {
+        int count = 2;
+        boolean flag = count > 1;
+        return flag ? "i" : "j";
+    }
+}
@@ -0,0 +1,3 @@
+public abstract class j<T> {
+    public abstract T k();
+}
@@ -0,0 +1,4 @@
+package org.apache.logging.log4j;
+
+public interface Logger {
+}